@@ -0,0 +1,143 @@
+#![allow(clippy::result_large_err)]
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::program::{get_return_data, invoke};
+
+declare_id!("EZxaFJ2RGDSJ7HNck79NNtA6QpX66SFEphpCMgSWQbdG");
+
+/// Test double for the voting program's `on_vote` hook. It records every
+/// callback so tests can assert what the voting program passed along, and can
+/// be switched to a malicious mode that calls back into the caller.
+#[program]
+pub mod vote_hook {
+    use super::*;
+
+    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+        ctx.accounts.hook_state.calls = 0;
+        Ok(())
+    }
+
+    pub fn set_reenter(ctx: Context<SetReenter>, reenter: bool) -> Result<()> {
+        ctx.accounts.hook_state.reenter = reenter;
+        Ok(())
+    }
+
+    pub fn on_vote<'info>(
+        ctx: Context<'_, '_, '_, 'info, OnVote<'info>>,
+        poll_id: u64,
+        candidate: Pubkey,
+        voter: Pubkey
+    ) -> Result<()> {
+        let hook_state = &mut ctx.accounts.hook_state;
+        hook_state.calls += 1;
+        hook_state.last_poll_id = poll_id;
+        hook_state.last_candidate = candidate;
+        hook_state.last_voter = voter;
+
+        // calls the first remaining account, expected to be the voting program
+        if hook_state.reenter {
+            let target = ctx.remaining_accounts.first().ok_or(ErrorCode::AccountNotEnoughKeys)?;
+            invoke(
+                &Instruction {
+                    program_id: target.key(),
+                    accounts: vec![],
+                    data: vec![],
+                },
+                std::slice::from_ref(target),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Calls the voting program's `get_poll_status` and keeps what it returned,
+    /// so tests can check the return data a composing program would see.
+    pub fn read_poll_status(ctx: Context<ReadPollStatus>, poll_id: u64) -> Result<()> {
+        let voting_program = &ctx.accounts.voting_program;
+        let mut data = hash(b"global:get_poll_status").to_bytes()[..8].to_vec();
+        data.extend_from_slice(&poll_id.to_le_bytes());
+        invoke(
+            &Instruction {
+                program_id: voting_program.key(),
+                accounts: vec![AccountMeta::new_readonly(ctx.accounts.poll.key(), false)],
+                data,
+            },
+            &[ctx.accounts.poll.to_account_info(), voting_program.to_account_info()],
+        )?;
+
+        let (program_id, data) = get_return_data().ok_or(ErrorCode::InstructionDidNotDeserialize)?;
+        require_keys_eq!(program_id, voting_program.key(), ErrorCode::InvalidProgramId);
+        let status = PollStatus::try_from_slice(&data)?;
+        let hook_state = &mut ctx.accounts.hook_state;
+        hook_state.last_poll_id = status.poll_id;
+        hook_state.last_status = status.status;
+        hook_state.last_winner = status.winner;
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + HookState::INIT_SPACE,
+        seeds = [b"hook_state"],
+        bump
+    )]
+    pub hook_state: Account<'info, HookState>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetReenter<'info> {
+    #[account(mut, seeds = [b"hook_state"], bump)]
+    pub hook_state: Account<'info, HookState>,
+}
+
+#[derive(Accounts)]
+pub struct OnVote<'info> {
+    #[account(mut, seeds = [b"hook_state"], bump)]
+    pub hook_state: Account<'info, HookState>,
+}
+
+#[derive(Accounts)]
+pub struct ReadPollStatus<'info> {
+    #[account(mut, seeds = [b"hook_state"], bump)]
+    pub hook_state: Account<'info, HookState>,
+
+    /// CHECK: passed through to the voting program, which validates it.
+    pub poll: UncheckedAccount<'info>,
+
+    /// CHECK: the program whose return data is read; checked against the
+    /// return data's program id.
+    #[account(executable)]
+    pub voting_program: UncheckedAccount<'info>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct HookState {
+    pub calls: u64,
+    pub last_poll_id: u64,
+    pub last_candidate: Pubkey,
+    pub last_voter: Pubkey,
+    pub reenter: bool,
+    /// `status` from the last `read_poll_status`.
+    pub last_status: u8,
+    /// `winner` from the last `read_poll_status`.
+    pub last_winner: Option<Pubkey>,
+}
+
+/// Mirror of the voting program's `PollStatus` return data.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct PollStatus {
+    pub poll_id: u64,
+    pub status: u8,
+    pub is_finalized: bool,
+    pub winner: Option<Pubkey>,
+}
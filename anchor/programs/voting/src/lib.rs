@@ -1,154 +1,7715 @@
 #![allow(clippy::result_large_err)]
 
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::{invoke, invoke_signed};
+use anchor_lang::solana_program::sysvar::slot_hashes;
+use anchor_lang::system_program::{
+    allocate, assign, create_account, transfer, Allocate, Assign, CreateAccount, Transfer,
+};
 
 declare_id!("coUnmi3oBUtwtd9fjeAvSsJssXh5A5xyPbhpewyzRVF");
 
+pub const MAX_DESCRIPTION_LEN: usize = 200;
+pub const MAX_CANDIDATE_NAME_LEN: usize = 32;
+pub const MAX_DISPLAY_NAME_LEN: usize = 64;
+pub const MAX_PARTY_NAME_LEN: usize = 32;
+/// Distinct candidates a single voter's allocation can track.
+pub const MAX_ALLOCATION_ENTRIES: usize = 16;
+/// Weight of an undecayed vote, in basis points.
+pub const FULL_VOTE_WEIGHT_BPS: u64 = 10_000;
+/// Decayed votes never weigh less than this, so late votes still count.
+pub const MIN_VOTE_WEIGHT_BPS: u64 = 1_000;
+/// Candidates a single `read_candidates` call can report.
+pub const MAX_SNAPSHOT_CANDIDATES: usize = 20;
+/// Polls a voter's `VoterIndex` remembers. Later polls still accept the voter's
+/// votes but are left out of the index.
+pub const MAX_VOTER_INDEX_POLLS: usize = 64;
+/// Candidates a `PollResults` account has room for; larger polls can't be finalized.
+pub const MAX_RESULTS_CANDIDATES: usize = 64;
+/// The SPL Token program, owner of the mints `snapshot_supply` reads.
+pub const SPL_TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+/// Size of an SPL Token mint account.
+pub const MINT_ACCOUNT_LEN: usize = 82;
+/// The Token-2022 program, owner of the gate tokens `vote_by_token_tier` reads.
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+/// Size of a token account without extensions. Token-2022 accounts with
+/// extensions store their account type at this offset, followed by the
+/// extensions' type-length-value entries.
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+/// Token-2022 extension type of embedded `TokenMetadata`.
+pub const TOKEN_METADATA_EXTENSION: u16 = 19;
+/// Token-2022 extension type of `TokenGroupMember`, which ties a mint to its
+/// collection (group) mint.
+pub const TOKEN_GROUP_MEMBER_EXTENSION: u16 = 23;
+/// Token-2022 extension type of `NonTransferable`, which makes a mint's tokens
+/// soulbound.
+pub const NON_TRANSFERABLE_EXTENSION: u16 = 9;
+/// Additional-metadata key holding a gate token's tier, a decimal index into
+/// `tier_weights`.
+pub const TIER_METADATA_KEY: &str = "tier";
+/// Entries in `PollSettings::tier_weights`.
+pub const TOKEN_TIERS: usize = 4;
+/// Display names a poll's `DisplayNameSet` can hold.
+pub const MAX_DISPLAY_NAMES: usize = 64;
+/// Source polls a destination's `merged_from` can record.
+pub const MAX_MERGED_POLLS: usize = 8;
+/// Votes one `vote_many` call may cast, to stay within the compute budget.
+pub const MAX_BATCH_VOTES: usize = 4;
+/// Jurisdictions a poll's `allowed_jurisdictions` can list.
+pub const MAX_JURISDICTIONS: usize = 16;
+/// Candidates one `initialize_poll_with_candidates` call may create, to stay
+/// within the compute budget.
+pub const MAX_INITIAL_CANDIDATES: usize = 8;
+/// Largest `endorse_candidate` multiplier, 2x. The smallest is
+/// `FULL_VOTE_WEIGHT_BPS`: an endorsement never discounts a candidate.
+pub const MAX_ENDORSEMENT_MULTIPLIER_BPS: u64 = 20_000;
+/// Entries a poll's `VoteLog` ring buffer holds before overwriting the oldest.
+pub const VOTE_LOG_ENTRIES: usize = 64;
+/// Candidates kept on a poll's live `TopK` leaderboard.
+pub const TOP_K: usize = 5;
+/// Hourly turnout buckets kept per poll; later votes land in the last one.
+pub const VOTE_BUCKETS: usize = 24;
+/// Narrowest turnout bucket. Polls longer than `VOTE_BUCKETS` hours get wider
+/// buckets so the whole poll still fits; see [`bucket_seconds`].
+pub const MIN_BUCKET_SECONDS: u64 = 3600;
+/// Notice voters get between `initialize_poll` and voting opening. Only enforced
+/// in builds with the `min-notice` feature, so tests can open polls right away.
+pub const MIN_NOTICE_SECONDS: u64 = 24 * 60 * 60;
+
+const MIN_UNIX_TIMESTAMP: u64 = 1_000_000_000;
+const MAX_UNIX_TIMESTAMP: u64 = 4_000_000_000;
+
+/// Ties go to the candidate with the lowest pubkey.
+pub const TIE_BREAK_LOWEST_PUBKEY: u8 = 0;
+/// Ties are settled by the most recent slot hash at finalization. See [`slot_hash_index`].
+pub const TIE_BREAK_SLOT_HASH: u8 = 3;
+
+/// One selection per ballot: candidate votes add up to `total_votes`.
+pub const COUNTING_SINGLE_CHOICE: u8 = 0;
+/// Voters may select several candidates, so candidate votes only have to cover
+/// every voter once.
+pub const COUNTING_APPROVAL: u8 = 1;
+
+/// The candidate with the most votes wins.
+pub const WIN_RULE_PLURALITY: u8 = 0;
+/// The leader only wins with more than half of `total_votes`.
+pub const WIN_RULE_MAJORITY: u8 = 1;
+
+/// `PollTiming::status` values.
+pub const POLL_STATUS_UPCOMING: u8 = 0;
+pub const POLL_STATUS_ACTIVE: u8 = 1;
+pub const POLL_STATUS_ENDED: u8 = 2;
+
+/// `AuditLog` action codes, one per authority-gated instruction.
+pub const AUDIT_UPDATE_SETTINGS: u8 = 0;
+pub const AUDIT_UPDATE_WIN_THRESHOLD: u8 = 1;
+pub const AUDIT_APPROVE: u8 = 2;
+pub const AUDIT_REGISTER_VOTER: u8 = 3;
+pub const AUDIT_FINALIZE: u8 = 4;
+pub const AUDIT_REOPEN: u8 = 5;
+pub const AUDIT_ARCHIVE: u8 = 6;
+pub const AUDIT_RESCHEDULE: u8 = 7;
+pub const AUDIT_ADJUST_VOTES: u8 = 8;
+pub const AUDIT_LOCK_CANDIDATES: u8 = 9;
+pub const AUDIT_VERIFY_CANDIDATE: u8 = 10;
+pub const AUDIT_COMMIT_SORTITION: u8 = 11;
+pub const AUDIT_RECOMPUTE_TOTAL: u8 = 12;
+pub const AUDIT_RESERVE_SLOT: u8 = 13;
+pub const AUDIT_FILL_SLOT: u8 = 14;
+pub const AUDIT_RESUME_POLL: u8 = 15;
+pub const AUDIT_ADVANCE_ROUND: u8 = 16;
+pub const AUDIT_SEAL_CANDIDATES: u8 = 17;
+pub const AUDIT_ENDORSE_CANDIDATE: u8 = 18;
+pub const AUDIT_ENABLE_VOTE_LOG: u8 = 19;
+/// Entries an `AuditLog` keeps; older ones are dropped as new ones arrive.
+pub const MAX_AUDIT_ENTRIES: usize = 32;
+
+/// Candidate seed scheme for new polls. Version 0 seeds were `[poll_id, name]`,
+/// so an empty name derived the poll's own address; version 1 puts
+/// [`CANDIDATE_SEED_SEPARATOR`] between the two.
+pub const CANDIDATE_SEED_VERSION: u8 = 1;
+pub const CANDIDATE_SEED_SEPARATOR: &[u8] = b"::";
+/// Seed names starting with this belong to slots from `reserve_candidate_slot`,
+/// so `initialize_candidate` won't take them.
+pub const SLOT_SEED_PREFIX: &str = "#";
+
+/// Layout of newly written voter records. Version 0 records predate the
+/// `version` field, version 1 records the `choice` field, version 2 records
+/// the `round` field and version 3 records the `jurisdiction` field; all are
+/// shorter, and `migrate_voter_record` brings them up to date.
+pub const VOTER_RECORD_VERSION: u8 = 4;
+
 #[program]
 pub mod voting {
     use super::*;
 
+    /// Creates the program-wide config with the signer as admin. Run it right
+    /// after deploying: until it exists the program can't be paused, and whoever
+    /// calls it first becomes the admin. It uses plain `init`, so of two racing
+    /// calls the second fails rather than resetting the admin.
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.paused = false;
+        config.initialized = true;
+        config.max_polls_per_voter = 0;
+        Ok(())
+    }
+
+    /// Emergency stop: while paused, no polls, candidates or votes can be created.
+    pub fn set_program_paused(ctx: Context<SetProgramPaused>, paused: bool) -> Result<()> {
+        ctx.accounts.config.paused = paused;
+        msg!("Program paused: {}", paused);
+        Ok(())
+    }
+
+    /// Caps how many polls any one voter may vote in across the program, against
+    /// reputation gaming with mass participation. 0 removes the cap. Voters
+    /// already past a lowered cap keep their votes but can't join more polls.
+    pub fn set_max_polls_per_voter(ctx: Context<SetMaxPollsPerVoter>, max_polls_per_voter: u64) -> Result<()> {
+        ctx.accounts.config.max_polls_per_voter = max_polls_per_voter;
+        msg!("Max polls per voter: {}", max_polls_per_voter);
+        Ok(())
+    }
+
+    /// Creates a poll. A `poll_start` in the past opens voting immediately: it is
+    /// stored as the current time and settings can no longer be changed.
+    /// Candidates stay votable when added before the first vote is cast.
     pub fn initialize_poll(
         ctx: Context<InitializePoll>,
         poll_id: u64,
         description: String,
         poll_start: u64,
-        poll_end: u64
+        poll_end: u64,
+        result_callback_hash: [u8; 32]
+    ) -> Result<()> {
+        open_poll(ctx.accounts, poll_id, description, poll_start, poll_end, result_callback_hash)
+    }
+
+    /// Creates a poll together with its first candidates, so it never exists
+    /// with an empty ballot. Remaining accounts are the candidate PDAs for
+    /// `names`, in order, all writable; at most `MAX_INITIAL_CANDIDATES`. Any
+    /// invalid or repeated name fails the whole transaction.
+    pub fn initialize_poll_with_candidates<'info>(
+        ctx: Context<'_, '_, '_, 'info, InitializePoll<'info>>,
+        poll_id: u64,
+        description: String,
+        poll_start: u64,
+        poll_end: u64,
+        names: Vec<String>
+    ) -> Result<()> {
+        if names.len() > MAX_INITIAL_CANDIDATES {
+            return Err(Errors::TooManyCandidates.into());
+        }
+        if ctx.remaining_accounts.len() != names.len() {
+            return Err(Errors::CandidateCountMismatch.into());
+        }
+        open_poll(ctx.accounts, poll_id, description, poll_start, poll_end, [0u8; 32])?;
+
+        let now = now()?;
+        for (name, info) in names.iter().zip(ctx.remaining_accounts) {
+            // a pre-funded address is still system-owned and gets created below
+            if info.owner == &crate::ID {
+                msg!("Candidate {} is listed twice", name);
+                return Err(Errors::DuplicateCandidate.into());
+            }
+            let candidate = Candidate {
+                display_name: ctx.accounts.poll.candidate_seed_name(name),
+                created_at: now,
+                candidate_authority: ctx.accounts.signer.key(),
+                ..Default::default()
+            };
+            create_candidate_account(
+                &mut ctx.accounts.poll,
+                info,
+                &ctx.accounts.signer,
+                &ctx.accounts.system_program,
+                name,
+                candidate,
+            )?;
+        }
+
+        msg!("Poll {} created with {} candidates", poll_id, names.len());
+        Ok(())
+    }
+
+    /// Replaces the poll's optional rules. Only allowed before voting opens so the
+    /// rules can't change under voters who already cast a ballot.
+    pub fn update_poll_settings(
+        ctx: Context<UpdatePollSettings>,
+        _poll_id: u64,
+        settings: PollSettings
     ) -> Result<()> {
         let poll = &mut ctx.accounts.poll;
-        let clock = Clock::get()?;
-        let now = clock.unix_timestamp as u64;
+        let now = now()?;
 
-        let min_unix_timestamp = 1_000_000_000;
-        let max_unix_timestamp = 4_000_000_000;
+        if now >= poll.poll_start {
+            return Err(Errors::PollAlreadyStarted.into());
+        }
 
-        if poll_start < min_unix_timestamp || poll_start > max_unix_timestamp {
-            return Err(Errors::InvalidTimestamp.into());
+        // both change which names earlier candidates were checked against
+        if poll.candidate_amount > 0
+            && (settings.case_insensitive_names != poll.settings.case_insensitive_names
+                || settings.require_unique_display_names != poll.settings.require_unique_display_names)
+        {
+            return Err(Errors::CandidatesAlreadyAdded.into());
         }
 
-        if poll_end <= now {
-            return Err(Errors::PollEndInThePast.into());
+        if settings.hook_program == Some(crate::ID) {
+            return Err(Errors::InvalidHookProgram.into());
         }
 
-        if poll_start >= poll_end {
-            return Err(Errors::InvalidEndDate.into());
+        if settings.approver == Some(poll.authority) {
+            return Err(Errors::ApproverIsAuthority.into());
         }
 
-        poll.poll_id = poll_id;
-        poll.description = description;
-        poll.poll_start = poll_start;
-        poll.poll_end = poll_end;
-        poll.candidate_amount = 0;
+        if !matches!(settings.tie_break, TIE_BREAK_LOWEST_PUBKEY | TIE_BREAK_SLOT_HASH) {
+            return Err(Errors::InvalidTieBreak.into());
+        }
+
+        if !matches!(settings.counting_mode, COUNTING_SINGLE_CHOICE | COUNTING_APPROVAL) {
+            return Err(Errors::InvalidCountingMode.into());
+        }
+
+        if !matches!(settings.win_rule, WIN_RULE_PLURALITY | WIN_RULE_MAJORITY) {
+            return Err(Errors::InvalidWinRule.into());
+        }
+
+        if settings.depends_on == Some(poll.poll_id)
+            || (settings.required_winner.is_some() && settings.depends_on.is_none())
+        {
+            return Err(Errors::InvalidPrerequisite.into());
+        }
+
+        if settings.votes_per_cast == 0 {
+            return Err(Errors::InvalidVotesPerCast.into());
+        }
+
+        if settings.allowed_jurisdictions.len() > MAX_JURISDICTIONS {
+            return Err(Errors::TooManyJurisdictions.into());
+        }
+
+        if settings.quorum_bps > FULL_VOTE_WEIGHT_BPS
+            || (settings.quorum_bps > 0 && settings.total_eligible_weight == 0)
+        {
+            return Err(Errors::InvalidQuorum.into());
+        }
+
+        // the set is only paid for by polls that ask for unique display names
+        if settings.require_unique_display_names && !poll.settings.require_unique_display_names {
+            let info = ctx.accounts.display_names.as_ref().ok_or(Errors::DisplayNameSetMissing)?;
+            let poll_id = poll.poll_id.to_le_bytes();
+            let (expected, bump) = Pubkey::find_program_address(&[b"display_names", poll_id.as_ref()], &crate::ID);
+            if info.key() != expected {
+                return Err(Errors::DisplayNameSetMissing.into());
+            }
+            // an earlier toggle may have created it; no candidates were added since
+            if info.owner != &crate::ID {
+                create_pda_account(
+                    &ctx.accounts.authority.to_account_info(),
+                    info,
+                    &ctx.accounts.system_program.to_account_info(),
+                    8 + DisplayNameSet::INIT_SPACE,
+                    &[b"display_names", poll_id.as_ref(), &[bump]],
+                )?;
+                let set = DisplayNameSet { poll: poll.key(), hashes: Vec::new() };
+                set.try_serialize(&mut &mut info.data.borrow_mut()[..])?;
+            }
+        }
+
+        poll.settings = settings;
+        // any approval was given for the previous rules
+        poll.approved = false;
+        ctx.accounts.audit_log.record(AUDIT_UPDATE_SETTINGS, ctx.accounts.authority.key(), now as i64);
+        Ok(())
+    }
+
+    /// Moves both ends of a poll that hasn't started yet, applying the same checks
+    /// as `initialize_poll`. A start in the past opens the poll right away.
+    pub fn reschedule_poll(
+        ctx: Context<ReschedulePoll>,
+        poll_id: u64,
+        new_start: u64,
+        new_end: u64
+    ) -> Result<()> {
+        let poll = &mut ctx.accounts.poll;
+        let now = now()?;
+
+        if now >= poll.poll_start {
+            return Err(Errors::PollAlreadyStarted.into());
+        }
+
+        validate_schedule(poll_id, new_start, new_end, now)?;
+
+        poll.poll_start = new_start.max(now);
+        poll.opened_at_creation = new_start <= now;
+        poll.poll_end = new_end;
+        poll.bucket_seconds = bucket_seconds(poll.poll_start, new_end);
+        ctx.accounts.audit_log.record(AUDIT_RESCHEDULE, ctx.accounts.authority.key(), now as i64);
+
+        emit!(PollRescheduled {
+            poll_id,
+            authority: ctx.accounts.authority.key(),
+            poll_start: poll.poll_start,
+            poll_end: new_end,
+        });
+        Ok(())
+    }
+
+    /// Moves `min_winning_votes` while the poll is running, e.g. to adjust a
+    /// "first to N" bar before anyone reaches it. All of the poll's candidates must
+    /// be passed as remaining accounts so their current votes can be checked.
+    pub fn update_win_threshold(
+        ctx: Context<UpdateWinThreshold>,
+        poll_id: u64,
+        new_threshold: u64
+    ) -> Result<()> {
+        let poll = &mut ctx.accounts.poll;
+        if poll.is_finalized {
+            return Err(Errors::PollAlreadyFinalized.into());
+        }
+
+        let candidates = load_candidates(poll, ctx.remaining_accounts)?;
+        let top_votes = candidates
+            .iter()
+            .map(|(_, candidate)| candidate.candidate_votes)
+            .max()
+            .unwrap_or(0);
+
+        let current = poll.settings.min_winning_votes;
+        if current > 0 && top_votes >= current {
+            msg!("A candidate already has {} of the {} votes needed", top_votes, current);
+            return Err(Errors::ThresholdAlreadyReached.into());
+        }
+
+        if new_threshold < top_votes {
+            msg!("Threshold {} is below the leader's {} votes", new_threshold, top_votes);
+            return Err(Errors::ThresholdBelowExistingVotes.into());
+        }
+
+        poll.settings.min_winning_votes = new_threshold;
+        msg!("Poll {} win threshold moved from {} to {}", poll_id, current, new_threshold);
+        ctx.accounts.audit_log.record(AUDIT_UPDATE_WIN_THRESHOLD, ctx.accounts.authority.key(), now()? as i64);
         Ok(())
     }
 
+    /// Greenlights a poll that requires a second sign-off before voting.
+    pub fn approve_poll(ctx: Context<ApprovePoll>, poll_id: u64) -> Result<()> {
+        let poll = &mut ctx.accounts.poll;
+        poll.approved = true;
+
+        msg!("Poll {} approved by {}", poll_id, ctx.accounts.approver.key());
+        ctx.accounts.audit_log.record(AUDIT_APPROVE, ctx.accounts.approver.key(), now()? as i64);
+        Ok(())
+    }
+
+    /// Creates `voter`'s record ahead of voting, with the authority paying its
+    /// rent. Polls with `require_registration` only accept votes from registered
+    /// voters; a fresh record starts with `votes_used == 0`, i.e. not yet voted.
+    pub fn register_voter(ctx: Context<RegisterVoter>, poll_id: u64, voter: Pubkey) -> Result<()> {
+        let voter_record = &mut ctx.accounts.voter_record;
+        voter_record.poll = ctx.accounts.poll.key();
+        voter_record.voter = voter;
+        voter_record.version = VOTER_RECORD_VERSION;
+        voter_record.registered = true;
+        ctx.accounts.poll.registered_voter_count += 1;
+
+        let voter_age = &mut ctx.accounts.voter_age;
+        if voter_age.voter == Pubkey::default() {
+            voter_age.voter = voter;
+            voter_age.first_seen_slot = clock()?.slot;
+        }
+
+        msg!("Registered {} for poll {}", voter, poll_id);
+        ctx.accounts.audit_log.record(AUDIT_REGISTER_VOTER, ctx.accounts.authority.key(), now()? as i64);
+        Ok(())
+    }
+
+    /// Adds a candidate keyed by the short `candidate_name`. `display_name` is what
+    /// clients show; it can be longer and defaults to the seed name when empty.
     pub fn initialize_candidate(
         ctx: Context<InitializeCandidate>,
         candidate_name: String,
-        _poll_id: u64
+        poll_id: u64,
+        display_name: String,
+        party: String
     ) -> Result<()> {
+        require_valid_signer(&ctx.accounts.signer.key())?;
+        ensure_not_paused(&ctx.accounts.config)?;
+        // the poll PDA is seeded by `poll_id`, so this only trips if the stored id
+        // and the seed ever disagree
+        require_eq!(ctx.accounts.poll.poll_id, poll_id, Errors::PollIdMismatch);
+
+        let now = now()?;
+        if now > ctx.accounts.poll.poll_end {
+            msg!("Poll closed at {}, it is {}", ctx.accounts.poll.poll_end, now);
+            return Err(Errors::PollEnded.into());
+        }
+
+        if ctx.accounts.poll.candidates_locked {
+            return Err(Errors::CandidatesLocked.into());
+        }
+
+        let candidate_name = ctx.accounts.poll.new_candidate_seed_name(&candidate_name)?;
+
+        if display_name.len() > MAX_DISPLAY_NAME_LEN {
+            return Err(Errors::DisplayNameTooLong.into());
+        }
+
+        if party.len() > MAX_PARTY_NAME_LEN {
+            return Err(Errors::PartyNameTooLong.into());
+        }
+
         let candidate = &mut ctx.accounts.candidate;
+        if candidate.poll != Pubkey::default() {
+            msg!("Candidate {} already exists in this poll", candidate.candidate_name);
+            return Err(Errors::DuplicateCandidate.into());
+        }
+
+        let display_name = if display_name.is_empty() {
+            candidate_name.clone()
+        } else {
+            display_name
+        };
+        if ctx.accounts.poll.settings.require_unique_display_names {
+            ctx.accounts.display_names.as_mut().ok_or(Errors::DisplayNameSetMissing)?.insert(&display_name)?;
+        }
+
+        candidate.display_name = display_name;
         candidate.candidate_name = candidate_name;
+        candidate.party = party;
         candidate.candidate_votes = 0;
+        candidate.poll = ctx.accounts.poll.key();
+        candidate.created_at = now;
+        candidate.candidate_authority = ctx.accounts.signer.key();
+        candidate.next_candidate = ctx.accounts.poll.candidate_head;
+        candidate.candidate_index = ctx.accounts.poll.candidate_amount;
+        ctx.accounts.poll.candidate_head = Some(candidate.key());
+
+        ctx.accounts.poll.candidate_amount += 1;
+        Ok(())
+    }
+
+    /// Runs `initialize_candidate`'s name checks without creating anything.
+    /// `reason_code` is the error code the name would fail with, or 0.
+    pub fn validate_candidate_name(
+        ctx: Context<ValidateCandidateName>,
+        candidate_name: String,
+        poll_id: u64
+    ) -> Result<()> {
+        let checked = ctx.accounts.poll.new_candidate_seed_name(&candidate_name);
+        emit!(NameValidation {
+            poll_id,
+            valid: checked.is_ok(),
+            reason_code: checked.err().map_or(0, u32::from),
+            seed_name: ctx.accounts.poll.candidate_seed_name(&candidate_name),
+        });
         Ok(())
     }
 
-    pub fn vote(ctx: Context<Vote>, _candidate_name: String, _poll_id: u64) -> Result<()> {
+    /// Commits to the ballot before voting opens: stores [`candidate_set_hash`]
+    /// over every candidate, passed as remaining accounts, and locks the
+    /// candidates. It can only be done once, and the hash can later be checked
+    /// with `verify_candidate_seal`.
+    pub fn seal_candidates(ctx: Context<SealCandidates>, poll_id: u64) -> Result<()> {
+        let now = now()?;
+        let poll = &ctx.accounts.poll;
+        if now >= poll.poll_start {
+            return Err(Errors::PollAlreadyStarted.into());
+        }
+        if poll.candidate_amount == 0 {
+            return Err(Errors::NoCandidates.into());
+        }
+
+        let candidates = load_candidates(poll, ctx.remaining_accounts)?;
+        let poll = &mut ctx.accounts.poll;
+        poll.sealed_candidate_hash = candidate_set_hash(&candidates);
+        poll.candidates_locked = true;
+        ctx.accounts.audit_log.record(AUDIT_SEAL_CANDIDATES, ctx.accounts.authority.key(), now as i64);
+
+        msg!("Poll {} sealed with {} candidates", poll_id, candidates.len());
+        Ok(())
+    }
+
+    /// Checks the candidates passed as remaining accounts still hash to the
+    /// poll's `sealed_candidate_hash`, i.e. the ballot is the one sealed.
+    pub fn verify_candidate_seal(ctx: Context<VerifyCandidateSeal>, poll_id: u64) -> Result<()> {
+        let poll = &ctx.accounts.poll;
+        if poll.sealed_candidate_hash == [0u8; 32] {
+            return Err(Errors::CandidatesNotSealed.into());
+        }
+
+        let candidates = load_candidates(poll, ctx.remaining_accounts)?;
+        if candidate_set_hash(&candidates) != poll.sealed_candidate_hash {
+            msg!("Poll {} candidates don't match the sealed ballot", poll_id);
+            return Err(Errors::CandidateSealMismatch.into());
+        }
+
+        msg!("Poll {} ballot matches its seal", poll_id);
+        Ok(())
+    }
+
+    /// Lets the poll's `endorser` amplify a candidate before voting opens: the
+    /// winner is picked by `candidate_votes * multiplier_bps / 10_000` (see
+    /// [`Candidate::ranked_votes`]) while the raw `candidate_votes` stays as cast.
+    /// Endorsing again replaces the multiplier.
+    pub fn endorse_candidate(
+        ctx: Context<EndorseCandidate>,
+        _candidate_name: String,
+        poll_id: u64,
+        multiplier_bps: u64
+    ) -> Result<()> {
+        let now = now()?;
+        if now >= ctx.accounts.poll.poll_start {
+            return Err(Errors::PollAlreadyStarted.into());
+        }
+        if !(FULL_VOTE_WEIGHT_BPS..=MAX_ENDORSEMENT_MULTIPLIER_BPS).contains(&multiplier_bps) {
+            return Err(Errors::InvalidEndorsementMultiplier.into());
+        }
+
         let candidate = &mut ctx.accounts.candidate;
-        candidate.candidate_votes += 1;
+        candidate.endorsement_multiplier_bps = multiplier_bps;
+        ctx.accounts.audit_log.record(AUDIT_ENDORSE_CANDIDATE, ctx.accounts.endorser.key(), now as i64);
 
-        msg!("Voted for candidate: {}", candidate.candidate_name);
-        msg!("Votes: {}", candidate.candidate_votes);
+        msg!("Candidate {} endorsed at {} bps in poll {}", candidate.candidate_name, multiplier_bps, poll_id);
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-#[instruction(candidate_name: String, poll_id: u64)]
-pub struct Vote<'info> {
-    #[account(mut)]
-    pub signer: Signer<'info>,
+    /// Opens the poll's [`VoteLog`] before voting starts. From then on `vote`,
+    /// `vote_quadratic`, `vote_by_token_tier` and `confirm_vote` must pass it and
+    /// append each counted ballot; batched, anonymous and identity ballots, which
+    /// can't, are rejected. Abstentions and blank ballots aren't logged.
+    pub fn enable_vote_log(ctx: Context<EnableVoteLog>, poll_id: u64) -> Result<()> {
+        let now = now()?;
+        if now >= ctx.accounts.poll.poll_start {
+            return Err(Errors::PollAlreadyStarted.into());
+        }
 
-    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
-    pub poll: Account<'info, Poll>,
+        ctx.accounts.poll.vote_log = true;
+        ctx.accounts.vote_log.poll = ctx.accounts.poll.key();
+        ctx.accounts.audit_log.record(AUDIT_ENABLE_VOTE_LOG, ctx.accounts.authority.key(), now as i64);
 
-    #[account(
-      mut,
-      seeds = [poll_id.to_le_bytes().as_ref(), candidate_name.as_ref()],
-      bump
-    )]
-    pub candidate: Account<'info, Candidate>,
+        msg!("Poll {} logs its votes", poll_id);
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    /// Freezes the ballot: no more candidates can be added, whatever the time.
+    pub fn lock_candidates(ctx: Context<LockCandidates>, poll_id: u64) -> Result<()> {
+        ctx.accounts.poll.candidates_locked = true;
+        ctx.accounts.audit_log.record(
+            AUDIT_LOCK_CANDIDATES,
+            ctx.accounts.authority.key(),
+            now()? as i64,
+        );
 
-#[derive(Accounts)]
-#[instruction(candidate_name: String, poll_id: u64)]
-pub struct InitializeCandidate<'info> {
-    #[account(mut)]
-    pub signer: Signer<'info>,
+        msg!("Poll {} candidates locked", poll_id);
+        Ok(())
+    }
 
-    #[account(
-        mut,
-        seeds = [poll_id.to_le_bytes().as_ref()],
-        bump
-      )]
-    pub poll: Account<'info, Poll>,
+    /// Reopens voting on a poll that paused itself after a burst of votes. The
+    /// slot's count starts over, so the next ballot doesn't pause it again.
+    pub fn resume_poll(ctx: Context<ResumePoll>, poll_id: u64) -> Result<()> {
+        let poll = &mut ctx.accounts.poll;
+        require!(poll.paused, Errors::PollNotPaused);
+        poll.paused = false;
+        poll.slot_votes = 0;
+        ctx.accounts.audit_log.record(
+            AUDIT_RESUME_POLL,
+            ctx.accounts.authority.key(),
+            now()? as i64,
+        );
 
-    #[account(
-        init,
-        payer = signer,
-        space = 8 + Candidate::INIT_SPACE,
-        seeds = [poll_id.to_le_bytes().as_ref(), candidate_name.as_ref()],
-        bump
-    )]
-    pub candidate: Account<'info, Candidate>,
-    pub system_program: Program<'info, System>,
-}
+        msg!("Poll {} resumed", poll_id);
+        Ok(())
+    }
 
-#[account]
-#[derive(InitSpace)]
-pub struct Candidate {
-    #[max_len(32)]
-    pub candidate_name: String,
-    pub candidate_votes: u64,
-}
+    /// Copies candidates from one of the authority's polls into another, with
+    /// zeroed tallies. Remaining accounts come in pairs: a source candidate, then
+    /// the (uninitialized, writable) destination candidate PDA for the same name.
+    pub fn clone_candidates<'info>(
+        ctx: Context<'_, '_, '_, 'info, CloneCandidates<'info>>,
+        _source_poll_id: u64,
+        _dest_poll_id: u64
+    ) -> Result<()> {
+        ensure_not_paused(&ctx.accounts.config)?;
 
-#[derive(Accounts)]
-#[instruction(poll_id: u64)]
-pub struct InitializePoll<'info> {
-    #[account(mut)]
-    pub signer: Signer<'info>,
-    #[account(
-        init,
-        payer = signer,
-        space = 8 + Poll::INIT_SPACE,
-        seeds = [poll_id.to_le_bytes().as_ref()],
-        bump
-    )]
-    pub poll: Account<'info, Poll>,
-    pub system_program: Program<'info, System>,
-}
+        let now = now()?;
+        if now > ctx.accounts.dest_poll.poll_end {
+            return Err(Errors::PollEnded.into());
+        }
 
-#[account]
-#[derive(InitSpace)]
-pub struct Poll {
-    pub poll_id: u64,
-    #[max_len(200)]
-    pub description: String,
-    pub poll_start: u64,
-    pub poll_end: u64,
-    pub candidate_amount: u64,
-}
+        if ctx.accounts.dest_poll.candidates_locked {
+            return Err(Errors::CandidatesLocked.into());
+        }
 
-#[error_code]
-pub enum Errors {
-    #[msg("Poll end date is in the past")]
-    PollEndInThePast,
+        let pairs = ctx.remaining_accounts.chunks_exact(2);
+        if !pairs.remainder().is_empty() {
+            return Err(Errors::CandidateCountMismatch.into());
+        }
 
-    #[msg("Provided timestamp is not a valid Unix timestamp.")]
-    InvalidTimestamp,
+        for pair in pairs {
+            let (source_info, dest_info) = (&pair[0], &pair[1]);
+            let source = load_candidate(&ctx.accounts.source_poll, source_info)?;
+            if !dest_info.data_is_empty() {
+                msg!("Candidate {} already exists in the destination poll", source.candidate_name);
+                return Err(Errors::DuplicateCandidate.into());
+            }
 
-    #[msg("Poll end date is before poll start date")]
-    InvalidEndDate,
+            let candidate = Candidate {
+                display_name: source.display_name.clone(),
+                party: source.party.clone(),
+                created_at: now,
+                candidate_authority: ctx.accounts.authority.key(),
+                ..Default::default()
+            };
+            create_candidate_account(
+                &mut ctx.accounts.dest_poll,
+                dest_info,
+                &ctx.accounts.authority,
+                &ctx.accounts.system_program,
+                &source.candidate_name,
+                candidate,
+            )?;
+        }
+
+        msg!("Cloned {} candidates", ctx.remaining_accounts.len() / 2);
+        Ok(())
+    }
+
+    /// Opens a runoff between the top two candidates of a majority poll that
+    /// finalized without a winner. Remaining accounts are every candidate of the
+    /// source poll, then the (uninitialized, writable) candidate PDAs of the new
+    /// poll for the leader and the runner-up, in that order. Candidates with equal
+    /// votes rank by lowest pubkey.
+    pub fn create_runoff<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateRunoff<'info>>,
+        source_poll_id: u64,
+        new_poll_id: u64,
+        poll_start: u64,
+        poll_end: u64
+    ) -> Result<()> {
+        ensure_not_paused(&ctx.accounts.config)?;
+
+        let source = &ctx.accounts.source_poll;
+        if !source.is_finalized {
+            return Err(Errors::PollNotFinalized.into());
+        }
+        if source.settings.win_rule != WIN_RULE_MAJORITY || source.winner.is_some() {
+            return Err(Errors::RunoffNotNeeded.into());
+        }
+
+        let split = ctx.remaining_accounts.len().checked_sub(2).ok_or(Errors::CandidateCountMismatch)?;
+        let (source_infos, runoff_infos) = ctx.remaining_accounts.split_at(split);
+        let mut candidates = load_candidates(source, source_infos)?;
+        if candidates.len() < 2 {
+            return Err(Errors::RunoffNeedsTwoCandidates.into());
+        }
+        candidates.sort_by(|(a_key, a), (b_key, b)| b.votes().cmp(&a.votes()).then(a_key.cmp(b_key)));
+
+        let now = now()?;
+        validate_schedule(new_poll_id, poll_start, poll_end, now)?;
+
+        let poll = &mut ctx.accounts.poll;
+        poll.poll_id = new_poll_id;
+        poll.description = format!("Runoff of poll {}", source_poll_id);
+        poll.poll_start = poll_start.max(now);
+        poll.opened_at_creation = poll_start <= now;
+        poll.poll_end = poll_end;
+        poll.authority = ctx.accounts.authority.key();
+        poll.settings = PollSettings::default();
+        poll.candidate_seed_version = CANDIDATE_SEED_VERSION;
+        poll.bucket_seconds = bucket_seconds(poll.poll_start, poll_end);
+        poll.runoff_of = Some(source_poll_id);
+        ctx.accounts.audit_log.poll = poll.key();
+
+        for ((_, source_candidate), info) in candidates.iter().zip(runoff_infos) {
+            let candidate = Candidate {
+                display_name: source_candidate.display_name.clone(),
+                party: source_candidate.party.clone(),
+                created_at: now,
+                candidate_authority: ctx.accounts.authority.key(),
+                ..Default::default()
+            };
+            create_candidate_account(
+                &mut ctx.accounts.poll,
+                info,
+                &ctx.accounts.authority,
+                &ctx.accounts.system_program,
+                &source_candidate.candidate_name,
+                candidate,
+            )?;
+        }
+
+        msg!("Poll {} is a runoff of poll {}", new_poll_id, source_poll_id);
+        Ok(())
+    }
+
+    /// Adds a finalized poll's tallies and abstentions to another of the
+    /// authority's polls, updating its leaderboard. Remaining accounts come in
+    /// pairs, one for every source candidate: the source candidate, then the
+    /// destination candidate PDA for the same name, which is created (writable,
+    /// uninitialized) if the destination lacks it.
+    pub fn merge_polls<'info>(
+        ctx: Context<'_, '_, '_, 'info, MergePolls<'info>>,
+        source_poll_id: u64,
+        dest_poll_id: u64
+    ) -> Result<()> {
+        ensure_not_paused(&ctx.accounts.config)?;
+
+        if source_poll_id == dest_poll_id {
+            return Err(Errors::PollAlreadyMerged.into());
+        }
+        if !ctx.accounts.source_poll.is_finalized {
+            return Err(Errors::PollNotFinalized.into());
+        }
+        if ctx.accounts.dest_poll.is_finalized {
+            return Err(Errors::PollFinalized.into());
+        }
+
+        let dest_poll = &ctx.accounts.dest_poll;
+        if dest_poll.merged_from.contains(&source_poll_id) {
+            msg!("Poll {} was already merged into poll {}", source_poll_id, dest_poll_id);
+            return Err(Errors::PollAlreadyMerged.into());
+        }
+        if dest_poll.merged_from.len() >= MAX_MERGED_POLLS {
+            return Err(Errors::TooManyMergedPolls.into());
+        }
+
+        let pairs = ctx.remaining_accounts.chunks_exact(2);
+        if !pairs.remainder().is_empty()
+            || pairs.len() as u64 != ctx.accounts.source_poll.candidate_amount
+        {
+            return Err(Errors::CandidateCountMismatch.into());
+        }
+
+        let mut merged: Vec<Pubkey> = Vec::with_capacity(pairs.len());
+        for pair in pairs {
+            let (source_info, dest_info) = (&pair[0], &pair[1]);
+            if merged.contains(&source_info.key()) {
+                return Err(Errors::CandidateCountMismatch.into());
+            }
+            merged.push(source_info.key());
+            let source = load_candidate(&ctx.accounts.source_poll, source_info)?;
+
+            if dest_info.data_is_empty() {
+                if ctx.accounts.dest_poll.candidates_locked {
+                    return Err(Errors::CandidatesLocked.into());
+                }
+                // dated no later than the destination's start so it stays votable there
+                let candidate = Candidate {
+                    candidate_votes: source.candidate_votes,
+                    weighted_votes: source.weighted_votes,
+                    display_name: source.display_name.clone(),
+                    party: source.party.clone(),
+                    created_at: source.created_at.min(ctx.accounts.dest_poll.poll_start),
+                    candidate_authority: ctx.accounts.authority.key(),
+                    ..Default::default()
+                };
+                ctx.accounts.top_k.record(dest_info.key(), candidate.votes());
+                create_candidate_account(
+                    &mut ctx.accounts.dest_poll,
+                    dest_info,
+                    &ctx.accounts.authority,
+                    &ctx.accounts.system_program,
+                    &source.candidate_name,
+                    candidate,
+                )?;
+                continue;
+            }
+
+            let mut candidate = load_candidate(&ctx.accounts.dest_poll, dest_info)?;
+            if candidate.candidate_name != ctx.accounts.dest_poll.candidate_seed_name(&source.candidate_name) {
+                return Err(Errors::CandidatePollMismatch.into());
+            }
+            candidate.candidate_votes = candidate
+                .candidate_votes
+                .checked_add(source.candidate_votes)
+                .ok_or(Errors::VoteOverflow)?;
+            candidate.weighted_votes = candidate
+                .weighted_votes
+                .checked_add(source.weighted_votes)
+                .ok_or(Errors::VoteOverflow)?;
+            ctx.accounts.top_k.record(dest_info.key(), candidate.votes());
+            candidate.try_serialize(&mut &mut dest_info.data.borrow_mut()[..])?;
+        }
+
+        let source_poll = &ctx.accounts.source_poll;
+        let dest_poll = &mut ctx.accounts.dest_poll;
+        // either poll may count abstentions in its total; move them separately
+        let mut merged_total = source_poll.candidate_vote_total()?;
+        if dest_poll.settings.include_abstentions_in_total {
+            merged_total = merged_total.checked_add(source_poll.abstentions).ok_or(Errors::VoteOverflow)?;
+        }
+        dest_poll.total_votes = dest_poll
+            .total_votes
+            .checked_add(merged_total)
+            .ok_or(Errors::VoteOverflow)?;
+        dest_poll.abstentions = dest_poll
+            .abstentions
+            .checked_add(source_poll.abstentions)
+            .ok_or(Errors::VoteOverflow)?;
+        dest_poll.weighted_total = dest_poll
+            .weighted_total
+            .checked_add(source_poll.weighted_total)
+            .ok_or(Errors::VoteOverflow)?;
+        dest_poll.merged_from.push(source_poll_id);
+        ctx.accounts.top_k.poll = dest_poll.key();
+
+        msg!("Merged poll {} into poll {}", source_poll_id, dest_poll_id);
+        Ok(())
+    }
+
+    /// Hands management of a candidate to `new_authority`. Either the current
+    /// candidate authority or the poll authority may do this.
+    pub fn transfer_candidate_authority(
+        ctx: Context<TransferCandidateAuthority>,
+        _candidate_name: String,
+        _poll_id: u64,
+        new_authority: Pubkey
+    ) -> Result<()> {
+        require_valid_signer(&new_authority)?;
+
+        let candidate = &mut ctx.accounts.candidate;
+        msg!(
+            "Candidate {} authority moved from {} to {}",
+            candidate.candidate_name,
+            candidate.candidate_authority,
+            new_authority
+        );
+        candidate.candidate_authority = new_authority;
+        Ok(())
+    }
+
+    /// Claims the next ballot position with a placeholder candidate seeded by
+    /// [`slot_seed_name`], to be described later with `fill_candidate_slot`. The
+    /// placeholder can't receive votes until then.
+    pub fn reserve_candidate_slot(ctx: Context<ReserveCandidateSlot>, poll_id: u64) -> Result<()> {
+        let now = now()?;
+        let poll = &mut ctx.accounts.poll;
+        if now > poll.poll_end {
+            return Err(Errors::PollEnded.into());
+        }
+        if poll.candidates_locked {
+            return Err(Errors::CandidatesLocked.into());
+        }
+
+        let slot = poll.reserved_slots;
+        let candidate = &mut ctx.accounts.candidate;
+        candidate.candidate_name = slot_seed_name(slot);
+        candidate.display_name = candidate.candidate_name.clone();
+        candidate.poll = poll.key();
+        candidate.created_at = now;
+        candidate.candidate_authority = ctx.accounts.authority.key();
+        candidate.next_candidate = poll.candidate_head;
+        candidate.candidate_index = poll.candidate_amount;
+        candidate.unfilled = true;
+        poll.candidate_head = Some(candidate.key());
+        poll.candidate_amount += 1;
+        poll.reserved_slots += 1;
+        ctx.accounts.audit_log.record(AUDIT_RESERVE_SLOT, ctx.accounts.authority.key(), now as i64);
+
+        msg!("Reserved candidate slot {} in poll {}", slot, poll_id);
+        Ok(())
+    }
+
+    /// Sets the display name and party of a reserved slot, which opens it to
+    /// votes. Slots can be refilled until the poll starts.
+    pub fn fill_candidate_slot(
+        ctx: Context<FillCandidateSlot>,
+        poll_id: u64,
+        slot: u64,
+        display_name: String,
+        party: String
+    ) -> Result<()> {
+        let now = now()?;
+        if now >= ctx.accounts.poll.poll_start {
+            return Err(Errors::PollAlreadyStarted.into());
+        }
+        if ctx.accounts.poll.sealed_candidate_hash != [0u8; 32] {
+            return Err(Errors::CandidatesAlreadySealed.into());
+        }
+        if display_name.len() > MAX_DISPLAY_NAME_LEN {
+            return Err(Errors::DisplayNameTooLong.into());
+        }
+        if party.len() > MAX_PARTY_NAME_LEN {
+            return Err(Errors::PartyNameTooLong.into());
+        }
+
+        let candidate = &mut ctx.accounts.candidate;
+        if !display_name.is_empty() {
+            candidate.display_name = display_name;
+        }
+        candidate.party = party;
+        candidate.unfilled = false;
+        ctx.accounts.audit_log.record(AUDIT_FILL_SLOT, ctx.accounts.authority.key(), now as i64);
+
+        msg!("Filled candidate slot {} in poll {} as {}", slot, poll_id, candidate.display_name);
+        Ok(())
+    }
+
+    /// Marks a candidate as verified by the poll authority.
+    pub fn verify_candidate(
+        ctx: Context<VerifyCandidate>,
+        _candidate_name: String,
+        poll_id: u64
+    ) -> Result<()> {
+        let candidate = &mut ctx.accounts.candidate;
+        candidate.verified = true;
+        ctx.accounts.audit_log.record(
+            AUDIT_VERIFY_CANDIDATE,
+            ctx.accounts.authority.key(),
+            now()? as i64,
+        );
+
+        msg!("Candidate {} verified in poll {}", candidate.candidate_name, poll_id);
+        Ok(())
+    }
+
+    /// Casts one ballot for the candidate. A client passing `exact_rent` states
+    /// the voter record rent it expects to pay; a different rent-exempt minimum
+    /// (e.g. after the record layout grew) fails the vote with `RentMismatch`,
+    /// undoing the record's creation with it.
+    ///
+    /// `jurisdiction` declares where the voter votes from, verified off-chain.
+    /// It is kept on the voter record, can't be changed once declared, and must
+    /// be one of the poll's `allowed_jurisdictions` when it lists any.
+    pub fn vote(
+        ctx: Context<Vote>,
+        _candidate_name: String,
+        poll_id: u64,
+        exact_rent: Option<u64>,
+        jurisdiction: Option<u32>
+    ) -> Result<()> {
+        if let Some(expected) = exact_rent {
+            let rent = Rent::get()?.minimum_balance(8 + VoterRecord::INIT_SPACE);
+            if rent != expected {
+                msg!("Voter record rent is {} lamports, the client expected {}", rent, expected);
+                return Err(Errors::RentMismatch.into());
+            }
+        }
+        require_valid_signer(&ctx.accounts.signer.key())?;
+        ensure_not_paused(&ctx.accounts.config)?;
+        ensure_record_matches_poll(&ctx.accounts.voter_record, &ctx.accounts.poll.key())?;
+        ctx.accounts.voter_record.start_round(ctx.accounts.poll.round);
+        ctx.accounts.voter_allocation.start_round(ctx.accounts.poll.round);
+        if let Some(jurisdiction) = jurisdiction {
+            let voter_record = &mut ctx.accounts.voter_record;
+            if voter_record.jurisdiction.is_some_and(|declared| declared != jurisdiction) {
+                return Err(Errors::WrongJurisdiction.into());
+            }
+            voter_record.jurisdiction = Some(jurisdiction);
+        }
+
+        let now = now()?;
+        let poll = &ctx.accounts.poll;
+        let candidate = &ctx.accounts.candidate;
+        let voter = ctx.accounts.signer.key();
+        if let Some(blocker) = ballot_blocker(poll, Some(candidate), &ctx.accounts.voter_record, now, BallotMode::Standard) {
+            match blocker {
+                Errors::PollFinalized => {
+                    msg!("Poll {} is finalized, rejecting vote from {}", poll_id, voter)
+                }
+                Errors::PollNotStarted => {
+                    msg!("Poll {} opens at {}, it is {}", poll_id, poll.poll_start, now)
+                }
+                Errors::PollEnded => {
+                    msg!("Poll {} closed at {}, it is {}", poll_id, poll.poll_end, now)
+                }
+                Errors::PollNotApproved => msg!("Poll {} is waiting for its approver", poll_id),
+                Errors::VoterNotRegistered => {
+                    msg!("{} is not registered for poll {}", voter, poll_id)
+                }
+                Errors::WrongJurisdiction => msg!(
+                    "Poll {} is limited to jurisdictions {:?}, {} declared {:?}",
+                    poll_id,
+                    poll.settings.allowed_jurisdictions,
+                    voter,
+                    ctx.accounts.voter_record.jurisdiction
+                ),
+                Errors::VoteBudgetExhausted => msg!(
+                    "{} has used all {} votes in poll {}",
+                    voter,
+                    poll.settings.votes_per_voter,
+                    poll_id
+                ),
+                Errors::CandidateAddedAfterStart => msg!(
+                    "Candidate {} was created at {}, after poll {} started at {}",
+                    candidate.candidate_name,
+                    candidate.created_at,
+                    poll_id,
+                    poll.candidate_cutoff()
+                ),
+                Errors::CandidateNotVerified => msg!(
+                    "Candidate {} is not verified for poll {}",
+                    candidate.candidate_name,
+                    poll_id
+                ),
+                _ => {}
+            }
+            return Err(blocker.into());
+        }
+        let gate = vote_gate_blocker(
+            &ctx.accounts.poll,
+            &ctx.accounts.voter_age,
+            ctx.accounts.prerequisite_poll.as_deref(),
+            ctx.accounts.vote_log.is_some(),
+            clock()?.slot,
+        );
+        if let Some(blocker) = gate {
+            return Err(blocker.into());
+        }
+
+        if ctx.accounts.poll.settings.require_confirmation {
+            let voter_record = &mut ctx.accounts.voter_record;
+            voter_record.poll = ctx.accounts.poll.key();
+            voter_record.voter = ctx.accounts.signer.key();
+            voter_record.version = VOTER_RECORD_VERSION;
+            voter_record.candidate = ctx.accounts.candidate.key();
+            voter_record.voted_at = now as i64;
+            voter_record.confirmed = false;
+
+            msg!("Vote for {} is pending confirmation", ctx.accounts.candidate.candidate_name);
+            return Ok(());
+        }
+
+        let votes = ctx.accounts.poll.settings.votes_per_cast;
+        count_vote(ctx.accounts, poll_id, ctx.remaining_accounts, votes)
+    }
+
+    /// Casts `votes` votes for the candidate on a quadratic poll, paid for from
+    /// the voter's `credits_per_voter` budget. Holding N votes for one candidate
+    /// costs N² credits in total, so adding to an earlier allocation is charged
+    /// the difference.
+    pub fn vote_quadratic(
+        ctx: Context<Vote>,
+        _candidate_name: String,
+        poll_id: u64,
+        votes: u64
+    ) -> Result<()> {
+        require_valid_signer(&ctx.accounts.signer.key())?;
+        ensure_not_paused(&ctx.accounts.config)?;
+        ensure_record_matches_poll(&ctx.accounts.voter_record, &ctx.accounts.poll.key())?;
+        ctx.accounts.voter_record.start_round(ctx.accounts.poll.round);
+        ctx.accounts.voter_allocation.start_round(ctx.accounts.poll.round);
+
+        let now = now()?;
+        let poll = &ctx.accounts.poll;
+        if let Some(blocker) = poll.voting_blocker(now) {
+            msg!("Poll {} is not accepting votes", poll_id);
+            return Err(blocker.into());
+        }
+
+        if poll.settings.credits_per_voter == 0
+            || poll.settings.require_confirmation
+            || poll.settings.gate_mint.is_some()
+            || !poll.settings.allowed_jurisdictions.is_empty()
+        {
+            return Err(Errors::VotingModeMismatch.into());
+        }
+
+        if poll.settings.require_registration && !ctx.accounts.voter_record.registered {
+            return Err(Errors::VoterNotRegistered.into());
+        }
+
+        if let Some(blocker) = poll.candidate_blocker(&ctx.accounts.candidate) {
+            return Err(blocker.into());
+        }
+        ensure_account_age(poll, &ctx.accounts.voter_age, clock()?.slot)?;
+        ensure_prerequisite_met(poll, ctx.accounts.prerequisite_poll.as_deref())?;
+
+        if votes == 0 {
+            return Err(Errors::NoVotesCast.into());
+        }
+
+        let candidate_key = ctx.accounts.candidate.key();
+        let held = ctx
+            .accounts
+            .voter_allocation
+            .allocations
+            .iter()
+            .find(|entry| entry.candidate == candidate_key)
+            .map_or(0, |entry| entry.votes);
+        let cost = quadratic_cost(held, votes).ok_or(Errors::VoteOverflow)?;
+        let credits_used = ctx.accounts.voter_record.credits_used;
+        let credits_per_voter = poll.settings.credits_per_voter;
+        let total_cost = credits_used.checked_add(cost).ok_or(Errors::VoteOverflow)?;
+        if total_cost > credits_per_voter {
+            msg!(
+                "{} votes cost {} credits, {} has {} of {} left",
+                votes,
+                cost,
+                ctx.accounts.signer.key(),
+                credits_per_voter.saturating_sub(credits_used),
+                credits_per_voter
+            );
+            return Err(Errors::InsufficientCredits.into());
+        }
+        ctx.accounts.voter_record.credits_used = total_cost;
+
+        count_vote(ctx.accounts, poll_id, ctx.remaining_accounts, votes)
+    }
+
+    /// Casts a vote weighted by the tier of the signer's Token-2022 gate token.
+    /// The `tier` entry in the token mint's metadata picks a weight from
+    /// `tier_weights`, and the ballot counts as that many votes. The token
+    /// account must belong to the signer, not be empty, and hold `gate_mint` or
+    /// a member of the `gate_mint` group; see [`token_tier_weight`].
+    ///
+    /// Each mint votes once per poll: the ballot creates its `TokenBallot`, so
+    /// moving the token to another wallet doesn't earn a second ballot. A
+    /// fungible `gate_mint` therefore only ever casts one; tiered polls are meant
+    /// for one-of-one collection tokens.
+    pub fn vote_by_token_tier(ctx: Context<Vote>, _candidate_name: String, poll_id: u64) -> Result<()> {
+        require_valid_signer(&ctx.accounts.signer.key())?;
+        ensure_not_paused(&ctx.accounts.config)?;
+        ensure_record_matches_poll(&ctx.accounts.voter_record, &ctx.accounts.poll.key())?;
+        ctx.accounts.voter_record.start_round(ctx.accounts.poll.round);
+        ctx.accounts.voter_allocation.start_round(ctx.accounts.poll.round);
+
+        let now = now()?;
+        let poll = &ctx.accounts.poll;
+        let ballot = ballot_blocker(poll, Some(&ctx.accounts.candidate), &ctx.accounts.voter_record, now, BallotMode::TokenTier);
+        if let Some(blocker) = ballot {
+            msg!("Token-tier vote in poll {} rejected", poll_id);
+            return Err(blocker.into());
+        }
+        let gate_mint = poll.settings.gate_mint.ok_or(Errors::VotingModeMismatch)?;
+        if poll.settings.require_confirmation {
+            return Err(Errors::VotingModeMismatch.into());
+        }
+        ensure_account_age(poll, &ctx.accounts.voter_age, clock()?.slot)?;
+        ensure_prerequisite_met(poll, ctx.accounts.prerequisite_poll.as_deref())?;
+
+        let (Some(token_account), Some(token_mint)) = (&ctx.accounts.token_account, &ctx.accounts.token_mint) else {
+            return Err(Errors::InvalidGateToken.into());
+        };
+        let weight = token_tier_weight(poll, &ctx.accounts.signer.key(), gate_mint, token_account, token_mint)?;
+        msg!("Gate token earns {} votes in poll {}", weight, poll_id);
+
+        let mint = token_mint.key();
+        let poll_key = poll.key();
+        let token_ballot = ctx.accounts.token_ballot.as_mut().ok_or(Errors::TokenBallotMissing)?;
+        token_ballot.poll = poll_key;
+        token_ballot.mint = mint;
+
+        count_vote(ctx.accounts, poll_id, ctx.remaining_accounts, weight)
+    }
+
+    /// Casts one ballot like `vote`, then mints the voter a soulbound "I voted"
+    /// token from the poll's `receipt_collection`. The client creates the receipt
+    /// mint first: a Token-2022 mint with 0 decimals, the `NonTransferable`
+    /// extension and a `GroupMemberPointer` to itself, funded for its
+    /// `TokenGroupMember` extension, whose mint authority is the poll's
+    /// `receipt_authority` PDA. That PDA must also be the collection's update
+    /// authority, and the voter's token account for the mint must exist.
+    ///
+    /// The PDA adds the mint to the collection, mints its one token to the voter
+    /// and then gives up the mint authority; see [`mint_receipt`]. Creating the
+    /// voter's `ReceiptRecord` fails if they already have one, so each voter gets
+    /// a single receipt per poll.
+    pub fn vote_with_receipt<'info>(
+        mut ctx: Context<'_, '_, 'info, 'info, VoteWithReceipt<'info>>,
+        candidate_name: String,
+        poll_id: u64
+    ) -> Result<()> {
+        let poll = &ctx.accounts.vote.poll;
+        let collection = poll.settings.receipt_collection.ok_or(Errors::VotingModeMismatch)?;
+        if poll.settings.require_confirmation {
+            return Err(Errors::VotingModeMismatch.into());
+        }
+        if ctx.accounts.receipt_collection.key() != collection {
+            return Err(Errors::ReceiptCollectionMismatch.into());
+        }
+        let authority = ctx.accounts.receipt_authority.key();
+        if !is_receipt_mint(&ctx.accounts.receipt_mint.data.borrow(), &authority) {
+            return Err(Errors::InvalidReceiptMint.into());
+        }
+        let voter = ctx.accounts.vote.signer.key();
+        let mint = ctx.accounts.receipt_mint.key();
+        let token_account = token_2022_account(&ctx.accounts.receipt_token_account.data.borrow());
+        if !token_account.is_some_and(|(account_mint, owner, _)| account_mint == mint && owner == voter) {
+            return Err(Errors::InvalidReceiptAccount.into());
+        }
+
+        vote(
+            Context::new(ctx.program_id, &mut ctx.accounts.vote, ctx.remaining_accounts, ctx.bumps.vote),
+            candidate_name,
+            poll_id,
+            None,
+            None,
+        )?;
+
+        let accounts = &mut ctx.accounts;
+        let poll_id_bytes = poll_id.to_le_bytes();
+        mint_receipt(
+            &accounts.token_program,
+            &accounts.receipt_mint,
+            &accounts.receipt_token_account,
+            &accounts.receipt_collection,
+            &accounts.receipt_authority,
+            &[b"receipt_authority", poll_id_bytes.as_ref(), &[ctx.bumps.receipt_authority]],
+        )?;
+
+        let receipt = &mut accounts.receipt;
+        receipt.poll = accounts.vote.poll.key();
+        receipt.voter = voter;
+        receipt.mint = mint;
+        msg!("Receipt {} minted to {} in poll {}", mint, voter, poll_id);
+        Ok(())
+    }
+
+    /// Casts one vote in each of several polls, atomically: if any of them is
+    /// rejected the whole transaction fails. Remaining accounts come in groups
+    /// of five per entry of `votes`, all writable: the poll, the candidate, and
+    /// the poll's signer voter record, `VoterAllocation` and `TopK` PDAs, each
+    /// created if missing.
+    ///
+    /// Batched votes are counted like `vote`, leaderboard, allocation and
+    /// `auto_finalize_on_full_turnout` included, but polls using confirmation,
+    /// a hook, an age gate, a prerequisite or a vote log are rejected.
+    pub fn vote_many<'info>(
+        ctx: Context<'_, '_, 'info, 'info, VoteMany<'info>>,
+        votes: Vec<BatchVote>
+    ) -> Result<()> {
+        require_valid_signer(&ctx.accounts.signer.key())?;
+        ensure_not_paused(&ctx.accounts.config)?;
+
+        if votes.len() > MAX_BATCH_VOTES {
+            return Err(Errors::BatchTooLarge.into());
+        }
+        if ctx.remaining_accounts.len() != votes.len() * 5 {
+            return Err(Errors::CandidateCountMismatch.into());
+        }
+
+        let payer = ctx.accounts.signer.to_account_info();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let voter = ctx.accounts.signer.key();
+        let now = now()? as i64;
+
+        for (entry, accounts) in votes.iter().zip(ctx.remaining_accounts.chunks_exact(5)) {
+            let [poll_info, candidate_info, record_info, allocation_info, top_k_info] = accounts else {
+                unreachable!("chunks_exact yields groups of five");
+            };
+            let poll_id = entry.poll_id.to_le_bytes();
+
+            let mut poll = Account::<Poll>::try_from(poll_info)?;
+            let (expected_poll, _) = Pubkey::find_program_address(&[poll_id.as_ref()], &crate::ID);
+            if poll_info.key() != expected_poll || poll.archived {
+                return Err(Errors::CandidatePollMismatch.into());
+            }
+
+            let seed_name = poll.candidate_seed_name(&entry.candidate_name);
+            let mut candidate = load_candidate(&poll, candidate_info)?;
+            if candidate.candidate_name != seed_name {
+                return Err(Errors::CandidatePollMismatch.into());
+            }
+
+            let mut record: VoterRecord = load_or_create_pda(
+                &payer,
+                record_info,
+                &system_program,
+                8 + VoterRecord::INIT_SPACE,
+                &[b"voter", poll_id.as_ref(), voter.as_ref()],
+            )?;
+            let mut allocation: VoterAllocation = load_or_create_pda(
+                &payer,
+                allocation_info,
+                &system_program,
+                8 + VoterAllocation::INIT_SPACE,
+                &[b"allocation", poll_id.as_ref(), voter.as_ref()],
+            )?;
+            let mut top_k: TopK = load_or_create_pda(
+                &payer,
+                top_k_info,
+                &system_program,
+                8 + TopK::INIT_SPACE,
+                &[b"top_k", poll_id.as_ref()],
+            )?;
+            ensure_record_matches_poll(&record, &poll.key())?;
+            record.start_round(poll.round);
+            allocation.start_round(poll.round);
+
+            if let Some(blocker) = ballot_blocker(&poll, Some(&candidate), &record, now as u64, BallotMode::Standard) {
+                msg!("Batched vote in poll {} rejected", entry.poll_id);
+                return Err(blocker.into());
+            }
+            if poll.settings.require_confirmation
+                || poll.settings.hook_program.is_some()
+                || poll.settings.min_account_age_slots > 0
+                || poll.settings.depends_on.is_some()
+                || poll.vote_log
+            {
+                return Err(Errors::VotingModeMismatch.into());
+            }
+
+            let weight_bps = poll.record_vote(now)?;
+            throttle_vote_velocity(&mut poll, entry.poll_id)?;
+            candidate.record_vote(now, poll.settings.votes_per_cast, weight_bps, poll.settings.high_precision)?;
+
+            let candidate_key = candidate_info.key();
+            let receipt_hash = receipt_hash(entry.poll_id, &candidate_key, &voter, now);
+            if record.votes_used == 0 {
+                poll.unique_voters += 1;
+                ctx.accounts.voter_global.join_poll(voter, max_polls_per_voter(&ctx.accounts.config)?)?;
+            }
+            record.poll = poll.key();
+            record.voter = voter;
+            record.version = VOTER_RECORD_VERSION;
+            record.candidate = candidate_key;
+            record.choice = BallotChoice::Candidate;
+            record.voted_at = now;
+            record.receipt_hash = receipt_hash;
+            record.votes_used += 1;
+            record.confirmed = true;
+            allocation.add(candidate_key, poll.settings.votes_per_cast)?;
+            top_k.poll = poll.key();
+            top_k.record(candidate_key, candidate.votes());
+            auto_finalize(&mut poll, &top_k, entry.poll_id, now as u64);
+
+            poll.exit(&crate::ID)?;
+            candidate.try_serialize(&mut &mut candidate_info.data.borrow_mut()[..])?;
+            record.try_serialize(&mut &mut record_info.data.borrow_mut()[..])?;
+            allocation.try_serialize(&mut &mut allocation_info.data.borrow_mut()[..])?;
+            top_k.try_serialize(&mut &mut top_k_info.data.borrow_mut()[..])?;
+            ctx.accounts.voter_index.record(entry.poll_id);
+
+            emit!(VoteReceipt {
+                poll_id: entry.poll_id,
+                candidate: candidate_key,
+                voter,
+                voted_at: now,
+                receipt_hash,
+            });
+        }
+
+        msg!("Cast {} batched votes", votes.len());
+        Ok(())
+    }
+
+    /// Runs every check `vote` would for the signer and candidate, and emits the
+    /// outcome without touching any account. `reason_code` is the error code
+    /// `vote` would fail with, or 0 if it would succeed.
+    ///
+    /// This is also where a signer too poor to open their voter record finds out:
+    /// `vote` creates the record before its handler runs, so there it fails
+    /// inside the system program instead of with `InsufficientRent`.
+    pub fn simulate_vote(ctx: Context<SimulateVote>, _candidate_name: String, poll_id: u64) -> Result<()> {
+        let now = now()?;
+        let slot = clock()?.slot;
+        let voter = ctx.accounts.signer.key();
+        let voter_record = if ctx.accounts.voter_record.data_is_empty() {
+            VoterRecord::default()
+        } else {
+            VoterRecord::try_deserialize(&mut &ctx.accounts.voter_record.data.borrow()[..])?
+        };
+
+        let record_rent = Rent::get()?.minimum_balance(8 + VoterRecord::INIT_SPACE);
+        let lamports = ctx.accounts.signer.lamports();
+        let blocker = if ctx.accounts.voter_record.data_is_empty() && lamports < record_rent {
+            msg!("{} has {} lamports but a voter record needs {}", voter, lamports, record_rent);
+            Some(Errors::InsufficientRent)
+        } else if voter == Pubkey::default() {
+            Some(Errors::InvalidSigner)
+        } else if is_program_paused(&ctx.accounts.config)? {
+            Some(Errors::ProgramPaused)
+        } else {
+            ballot_blocker(&ctx.accounts.poll, Some(&ctx.accounts.candidate), &voter_record, now, BallotMode::Standard)
+                .or_else(|| {
+                    vote_gate_blocker(
+                        &ctx.accounts.poll,
+                        &ctx.accounts.voter_age,
+                        ctx.accounts.prerequisite_poll.as_deref(),
+                        ctx.accounts.vote_log.is_some(),
+                        slot,
+                    )
+                })
+        };
+
+        emit!(VoteSimulation {
+            poll_id,
+            candidate: ctx.accounts.candidate.key(),
+            voter,
+            would_succeed: blocker.is_none(),
+            reason_code: blocker.map_or(0, u32::from),
+        });
+        Ok(())
+    }
+
+    /// Emits whether the signer passes the poll's voter gates: the whitelist
+    /// (`require_registration`), the gate token (`gate_mint`), account age
+    /// (`min_account_age_slots`) and a registered identity (`require_identity`).
+    /// `reason_code` is the error code of the first gate they fail, in that
+    /// order, or 0 when they pass all of them. Unlike `simulate_vote`, this
+    /// ignores the poll's schedule and the voter's remaining budget.
+    ///
+    /// The token accounts are only read on `gate_mint` polls; pass the signer's
+    /// gate token and its mint as for `vote_by_token_tier`.
+    pub fn check_eligibility(ctx: Context<CheckEligibility>, poll_id: u64) -> Result<()> {
+        let poll = &ctx.accounts.poll;
+        let voter = ctx.accounts.signer.key();
+        let registered = !ctx.accounts.voter_record.data_is_empty()
+            && VoterRecord::try_deserialize(&mut &ctx.accounts.voter_record.data.borrow()[..])?.registered;
+
+        let token_blocker = poll.settings.gate_mint.and_then(|gate_mint| {
+            let token_accounts = ctx.accounts.token_account.as_ref().zip(ctx.accounts.token_mint.as_ref());
+            match token_accounts {
+                Some((token_account, token_mint))
+                    if token_account.owner == &TOKEN_2022_PROGRAM_ID && token_mint.owner == &TOKEN_2022_PROGRAM_ID =>
+                {
+                    token_tier_weight(poll, &voter, gate_mint, token_account, token_mint).err()
+                }
+                _ => Some(Errors::InvalidGateToken),
+            }
+        });
+
+        let blocker = if poll.settings.require_registration && !registered {
+            Some(Errors::VoterNotRegistered)
+        } else if token_blocker.is_some() {
+            token_blocker
+        } else if ensure_account_age(poll, &ctx.accounts.voter_age, clock()?.slot).is_err() {
+            Some(Errors::AccountTooNew)
+        } else if poll.settings.require_identity && ctx.accounts.identity.owner != &crate::ID {
+            Some(Errors::IdentityNotRegistered)
+        } else {
+            None
+        };
+
+        emit!(Eligibility {
+            poll_id,
+            voter,
+            eligible: blocker.is_none(),
+            reason_code: blocker.map_or(0, u32::from),
+        });
+        Ok(())
+    }
+
+    /// Casts a ballot for no candidate. It uses up one of the voter's votes and
+    /// counts as turnout; it only adds to `total_votes` when the poll sets
+    /// `include_abstentions_in_total`.
+    pub fn vote_abstain(ctx: Context<VoteAbstain>, poll_id: u64) -> Result<()> {
+        require_valid_signer(&ctx.accounts.signer.key())?;
+        ensure_not_paused(&ctx.accounts.config)?;
+        ensure_record_matches_poll(&ctx.accounts.voter_record, &ctx.accounts.poll.key())?;
+        ctx.accounts.voter_record.start_round(ctx.accounts.poll.round);
+
+        let now = now()? as i64;
+        if let Some(blocker) = ballot_blocker(&ctx.accounts.poll, None, &ctx.accounts.voter_record, now as u64, BallotMode::Standard) {
+            return Err(blocker.into());
+        }
+        ensure_account_age(&ctx.accounts.poll, &ctx.accounts.voter_age, clock()?.slot)?;
+        ensure_prerequisite_met(&ctx.accounts.poll, ctx.accounts.prerequisite_poll.as_deref())?;
+
+        let poll = &mut ctx.accounts.poll;
+        poll.record_abstention(now)?;
+
+        let voter_record = &mut ctx.accounts.voter_record;
+        if voter_record.votes_used == 0 {
+            poll.unique_voters += 1;
+            let max_polls = max_polls_per_voter(&ctx.accounts.config)?;
+            ctx.accounts.voter_global.join_poll(ctx.accounts.signer.key(), max_polls)?;
+        }
+        voter_record.poll = poll.key();
+        voter_record.voter = ctx.accounts.signer.key();
+        voter_record.version = VOTER_RECORD_VERSION;
+        voter_record.choice = BallotChoice::Abstain;
+        voter_record.voted_at = now;
+        voter_record.votes_used += 1;
+        ctx.accounts.top_k.poll = poll.key();
+        auto_finalize(poll, &ctx.accounts.top_k, poll_id, now as u64);
+
+        msg!("{} abstained in poll {}", ctx.accounts.voter_record.voter, poll_id);
+        Ok(())
+    }
+
+    /// Casts a deliberately blank ballot. Unlike an abstention it is a vote
+    /// against every candidate: it counts as turnout, is tallied in
+    /// `blank_votes`, and with `blank_votes_count_toward_quorum` helps meet the
+    /// quorum, but never adds to `total_votes`.
+    pub fn vote_blank(ctx: Context<VoteAbstain>, poll_id: u64) -> Result<()> {
+        require_valid_signer(&ctx.accounts.signer.key())?;
+        ensure_not_paused(&ctx.accounts.config)?;
+        ensure_record_matches_poll(&ctx.accounts.voter_record, &ctx.accounts.poll.key())?;
+        ctx.accounts.voter_record.start_round(ctx.accounts.poll.round);
+
+        let now = now()? as i64;
+        if let Some(blocker) = ballot_blocker(&ctx.accounts.poll, None, &ctx.accounts.voter_record, now as u64, BallotMode::Standard) {
+            return Err(blocker.into());
+        }
+        ensure_account_age(&ctx.accounts.poll, &ctx.accounts.voter_age, clock()?.slot)?;
+        ensure_prerequisite_met(&ctx.accounts.poll, ctx.accounts.prerequisite_poll.as_deref())?;
+
+        let poll = &mut ctx.accounts.poll;
+        poll.blank_votes += 1;
+
+        let voter_record = &mut ctx.accounts.voter_record;
+        if voter_record.votes_used == 0 {
+            poll.unique_voters += 1;
+            let max_polls = max_polls_per_voter(&ctx.accounts.config)?;
+            ctx.accounts.voter_global.join_poll(ctx.accounts.signer.key(), max_polls)?;
+        }
+        voter_record.poll = poll.key();
+        voter_record.voter = ctx.accounts.signer.key();
+        voter_record.version = VOTER_RECORD_VERSION;
+        voter_record.choice = BallotChoice::Blank;
+        voter_record.voted_at = now;
+        voter_record.votes_used += 1;
+        ctx.accounts.top_k.poll = poll.key();
+        auto_finalize(poll, &ctx.accounts.top_k, poll_id, now as u64);
+
+        msg!("{} cast a blank vote in poll {}", ctx.accounts.voter_record.voter, poll_id);
+        Ok(())
+    }
+
+    /// Emits the signer's own latest ballot in the poll. Fails with `NoBallot`
+    /// if they have neither voted nor abstained.
+    pub fn my_ballot(ctx: Context<ReadMyBallot>, poll_id: u64) -> Result<()> {
+        let info = &ctx.accounts.voter_record;
+        let record = VoterRecord::try_deserialize(&mut &info.data.borrow()[..])?;
+        if record.votes_used == 0 && !record.has_pending_vote() {
+            return Err(Errors::NoBallot.into());
+        }
+
+        let poll = &ctx.accounts.poll;
+        let abstained = record.choice == BallotChoice::Abstain;
+        let blank = record.choice == BallotChoice::Blank;
+        emit!(MyBallot {
+            poll_id,
+            voter: record.voter,
+            candidate: (!abstained && !blank).then_some(record.candidate),
+            abstained,
+            blank,
+            confirmed: record.confirmed,
+            votes_used: record.votes_used,
+            weight_bps: vote_weight_bps(poll.poll_start, record.voted_at, poll.settings.weight_decay_per_hour),
+            voted_at: record.voted_at,
+        });
+        Ok(())
+    }
+
+    /// Emits every poll the signer has cast a counted vote in, per their
+    /// `VoterIndex`, as an attestation for other programs. A voter who has never
+    /// voted gets an empty proof rather than an error.
+    pub fn participation_proof(ctx: Context<ReadParticipation>) -> Result<()> {
+        let info = &ctx.accounts.voter_index;
+        let poll_ids = if info.owner == &crate::ID {
+            VoterIndex::try_deserialize(&mut &info.data.borrow()[..])?.poll_ids
+        } else {
+            Vec::new()
+        };
+
+        emit!(ParticipationProof {
+            voter: ctx.accounts.signer.key(),
+            poll_count: poll_ids.len() as u64,
+            poll_ids,
+        });
+        Ok(())
+    }
+
+    /// Counts the signer's pending vote on a `require_confirmation` poll. The
+    /// candidate must match the one passed to `vote`, and the poll must still be
+    /// accepting votes.
+    pub fn confirm_vote(ctx: Context<Vote>, _candidate_name: String, poll_id: u64) -> Result<()> {
+        ensure_not_paused(&ctx.accounts.config)?;
+        ensure_record_matches_poll(&ctx.accounts.voter_record, &ctx.accounts.poll.key())?;
+        // a vote left pending in an earlier round lapses with it
+        ctx.accounts.voter_record.start_round(ctx.accounts.poll.round);
+        ctx.accounts.voter_allocation.start_round(ctx.accounts.poll.round);
+
+        let now = now()?;
+        if let Some(blocker) = ctx.accounts.poll.voting_blocker(now) {
+            return Err(blocker.into());
+        }
+
+        let voter_record = &ctx.accounts.voter_record;
+        if !voter_record.has_pending_vote() || voter_record.candidate != ctx.accounts.candidate.key() {
+            return Err(Errors::NoPendingVote.into());
+        }
+
+        let votes = ctx.accounts.poll.settings.votes_per_cast;
+        count_vote(ctx.accounts, poll_id, ctx.remaining_accounts, votes)
+    }
+
+    /// Upgrades the signer's voter record from an older layout: grows the account
+    /// to the current size, the signer paying any extra rent, and stamps it with
+    /// [`VOTER_RECORD_VERSION`]. Fields the old layout lacked read as zero, i.e.
+    /// their defaults. Already current records are left as they are.
+    pub fn migrate_voter_record(ctx: Context<MigrateVoterRecord>, poll_id: u64) -> Result<()> {
+        let info = ctx.accounts.voter_record.to_account_info();
+        let old_len = info.data_len();
+        let space = 8 + VoterRecord::INIT_SPACE;
+        if old_len < space {
+            let shortfall = Rent::get()?.minimum_balance(space).saturating_sub(info.lamports());
+            if shortfall > 0 {
+                transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        Transfer {
+                            from: ctx.accounts.signer.to_account_info(),
+                            to: info.clone(),
+                        },
+                    ),
+                    shortfall,
+                )?;
+            }
+            info.realloc(space, true)?;
+        }
+
+        let mut record = VoterRecord::try_deserialize(&mut &info.data.borrow()[..])?;
+        let old_version = record.version;
+        if old_version < 2 && record.votes_used > 0 {
+            // blank votes came with `choice`, so earlier ballots were for a
+            // candidate or an abstention
+            record.choice = if record.candidate == Pubkey::default() {
+                BallotChoice::Abstain
+            } else {
+                BallotChoice::Candidate
+            };
+        }
+        record.version = VOTER_RECORD_VERSION;
+        record.try_serialize(&mut &mut info.data.borrow_mut()[..])?;
+
+        msg!(
+            "Voter record for poll {} migrated from version {} ({} bytes) to {}",
+            poll_id,
+            old_version,
+            old_len,
+            VOTER_RECORD_VERSION
+        );
+        Ok(())
+    }
+
+    /// Drops the signer's pending vote so they can vote again.
+    pub fn cancel_pending_vote(ctx: Context<CancelPendingVote>, poll_id: u64) -> Result<()> {
+        let voter_record = &mut ctx.accounts.voter_record;
+        voter_record.candidate = Pubkey::default();
+
+        msg!("Canceled pending vote of {} in poll {}", voter_record.voter, poll_id);
+        Ok(())
+    }
+
+    /// Casts a vote on an anonymous poll. Instead of a pubkey-keyed voter record,
+    /// the voter supplies a `nullifier` derived from a secret; its record PDA can
+    /// only be created once, so the nullifier can't be reused, but it doesn't
+    /// reveal who voted. Eligibility (e.g. a Merkle proof over registered
+    /// commitments) isn't checked here and has to be proven separately; see
+    /// `PollSettings::anonymous`. The fee payer should be a relayer rather than
+    /// the voter's own wallet. Hooks are not called, since they'd receive the
+    /// payer as the voter.
+    pub fn vote_anonymous(
+        ctx: Context<VoteAnonymous>,
+        _candidate_name: String,
+        poll_id: u64,
+        nullifier: [u8; 32]
+    ) -> Result<()> {
+        ensure_not_paused(&ctx.accounts.config)?;
+
+        let now = now()? as i64;
+        if let Some(blocker) = ctx.accounts.poll.voting_blocker(now as u64) {
+            msg!("Poll {} is not accepting votes", poll_id);
+            return Err(blocker.into());
+        }
+
+        // an anonymous ballot can't carry a jurisdiction
+        if !ctx.accounts.poll.settings.anonymous
+            || !ctx.accounts.poll.settings.allowed_jurisdictions.is_empty()
+            || ctx.accounts.poll.vote_log
+        {
+            return Err(Errors::VotingModeMismatch.into());
+        }
+
+        if let Some(blocker) = ctx.accounts.poll.candidate_blocker(&ctx.accounts.candidate) {
+            return Err(blocker.into());
+        }
+
+        let weight_bps = ctx.accounts.poll.record_vote(now)?;
+        throttle_vote_velocity(&mut ctx.accounts.poll, poll_id)?;
+        let votes = ctx.accounts.poll.settings.votes_per_cast;
+        let high_precision = ctx.accounts.poll.settings.high_precision;
+        ctx.accounts.candidate.record_vote(now, votes, weight_bps, high_precision)?;
+
+        let nullifier_record = &mut ctx.accounts.nullifier_record;
+        nullifier_record.poll = ctx.accounts.poll.key();
+        nullifier_record.nullifier = nullifier;
+
+        msg!("Anonymous vote for candidate: {}", ctx.accounts.candidate.candidate_name);
+        Ok(())
+    }
+
+    /// Links `wallet` to an identity verified off-chain. Every wallet linked to the
+    /// same `identity_hash` shares one voter record on `require_identity` polls.
+    pub fn register_identity(
+        ctx: Context<RegisterIdentity>,
+        identity_hash: [u8; 32],
+        wallet: Pubkey
+    ) -> Result<()> {
+        let identity = &mut ctx.accounts.identity;
+        identity.wallet = wallet;
+        identity.identity_hash = identity_hash;
+
+        msg!("Linked {} to a registered identity", wallet);
+        Ok(())
+    }
+
+    /// Votes on a `require_identity` poll. The voter record is keyed by the
+    /// signer's registered identity rather than the wallet, so the budget is
+    /// shared by every wallet linked to that identity.
+    pub fn vote_with_identity(
+        ctx: Context<VoteWithIdentity>,
+        _candidate_name: String,
+        poll_id: u64
+    ) -> Result<()> {
+        require_valid_signer(&ctx.accounts.signer.key())?;
+        ensure_not_paused(&ctx.accounts.config)?;
+        ensure_record_matches_poll(&ctx.accounts.voter_record, &ctx.accounts.poll.key())?;
+        ctx.accounts.voter_record.start_round(ctx.accounts.poll.round);
+
+        let now = now()? as i64;
+        let poll = &ctx.accounts.poll;
+        if let Some(blocker) = poll.voting_blocker(now as u64) {
+            msg!("Poll {} is not accepting votes", poll_id);
+            return Err(blocker.into());
+        }
+
+        if !poll.settings.require_identity || !poll.settings.allowed_jurisdictions.is_empty() || poll.vote_log {
+            return Err(Errors::VotingModeMismatch.into());
+        }
+
+        let votes_per_voter = poll.settings.votes_per_voter;
+        if votes_per_voter > 0 && ctx.accounts.voter_record.votes_used >= votes_per_voter {
+            msg!("Identity of {} has no votes left", ctx.accounts.signer.key());
+            return Err(Errors::VoteBudgetExhausted.into());
+        }
+
+        if let Some(blocker) = poll.candidate_blocker(&ctx.accounts.candidate) {
+            return Err(blocker.into());
+        }
+
+        let weight_bps = ctx.accounts.poll.record_vote(now)?;
+        throttle_vote_velocity(&mut ctx.accounts.poll, poll_id)?;
+        let votes = ctx.accounts.poll.settings.votes_per_cast;
+        let high_precision = ctx.accounts.poll.settings.high_precision;
+        ctx.accounts.candidate.record_vote(now, votes, weight_bps, high_precision)?;
+
+        let candidate_key = ctx.accounts.candidate.key();
+        let voter_record = &mut ctx.accounts.voter_record;
+        if voter_record.votes_used == 0 {
+            ctx.accounts.poll.unique_voters += 1;
+            let max_polls = max_polls_per_voter(&ctx.accounts.config)?;
+            ctx.accounts.voter_global.join_poll(ctx.accounts.signer.key(), max_polls)?;
+        }
+        voter_record.poll = ctx.accounts.poll.key();
+        voter_record.voter = ctx.accounts.signer.key();
+        voter_record.version = VOTER_RECORD_VERSION;
+        voter_record.candidate = candidate_key;
+        voter_record.choice = BallotChoice::Candidate;
+        voter_record.voted_at = now;
+        voter_record.votes_used += 1;
+        voter_record.confirmed = true;
+
+        ctx.accounts.top_k.poll = ctx.accounts.poll.key();
+        ctx.accounts.top_k.record(candidate_key, ctx.accounts.candidate.votes());
+
+        msg!("Identity vote for candidate: {}", ctx.accounts.candidate.candidate_name);
+        Ok(())
+    }
+
+    /// Closes the voter record `register_voter` opened for `voter` once the poll
+    /// is final without them voting, refunding its rent to the authority who
+    /// paid for it. Final means finalized with the dispute window closed, so
+    /// `reopen_poll` can't let voting resume after the record is gone.
+    /// `registered_voter_count` keeps counting them, so `turnout` still
+    /// measures against the electorate as registered. Records that cast a
+    /// ballot are kept as the voter's receipt.
+    pub fn reclaim_unvoted_record(ctx: Context<ReclaimUnvotedRecord>, poll_id: u64, voter: Pubkey) -> Result<()> {
+        match ctx.accounts.poll.dispute_window_state(now()?) {
+            DisputeWindowState::NotFinalized => return Err(Errors::PollNotFinalized.into()),
+            DisputeWindowState::Open => return Err(Errors::DisputeWindowOpen.into()),
+            DisputeWindowState::Closed => {}
+        }
+        if ctx.accounts.voter_record.has_voted() {
+            return Err(Errors::RecordHasVoted.into());
+        }
+
+        msg!("Reclaimed the unused voter record of {} in poll {}", voter, poll_id);
+        Ok(())
+    }
+
+    /// Shrinks a finalized poll down to an [`ArchivedPoll`] and refunds the rent
+    /// the rest of it took to the authority. The account is no longer a `Poll`,
+    /// so every instruction taking the poll rejects it from then on.
+    pub fn archive_poll(ctx: Context<ArchivePoll>, poll_id: u64) -> Result<()> {
+        let info = ctx.accounts.poll.to_account_info();
+        if info.data.borrow().starts_with(&ArchivedPoll::DISCRIMINATOR) {
+            return Err(Errors::PollArchived.into());
+        }
+        let poll = Poll::try_deserialize(&mut &info.data.borrow()[..])?;
+        if poll.authority != ctx.accounts.authority.key() {
+            return Err(Errors::Unauthorized.into());
+        }
+        if poll.archived {
+            return Err(Errors::PollArchived.into());
+        }
+
+        let now = now()?;
+        match poll.dispute_window_state(now) {
+            DisputeWindowState::NotFinalized => return Err(Errors::PollNotFinalized.into()),
+            DisputeWindowState::Open => return Err(Errors::DisputeWindowOpen.into()),
+            DisputeWindowState::Closed => {}
+        }
+
+        let archived = ArchivedPoll {
+            poll_id: poll.poll_id,
+            winner: poll.winner,
+            total_votes: poll.total_votes,
+            is_finalized: poll.is_finalized,
+        };
+        let space = 8 + ArchivedPoll::INIT_SPACE;
+        info.realloc(space, false)?;
+        archived.try_serialize(&mut &mut info.data.borrow_mut()[..])?;
+        let freed = info.lamports().saturating_sub(Rent::get()?.minimum_balance(space));
+        info.sub_lamports(freed)?;
+        ctx.accounts.authority.add_lamports(freed)?;
+
+        msg!("Poll {} archived, {} lamports refunded", poll_id, freed);
+        ctx.accounts.audit_log.record(AUDIT_ARCHIVE, ctx.accounts.authority.key(), now as i64);
+        Ok(())
+    }
+
+    /// Emits the names and votes of up to `MAX_SNAPSHOT_CANDIDATES` of the poll's
+    /// candidates, passed as remaining accounts, so clients can read a page of
+    /// results in one call.
+    pub fn read_candidates(ctx: Context<ReadCandidates>, poll_id: u64) -> Result<()> {
+        if ctx.remaining_accounts.len() > MAX_SNAPSHOT_CANDIDATES {
+            return Err(Errors::TooManyCandidates.into());
+        }
+
+        let candidates = ctx
+            .remaining_accounts
+            .iter()
+            .map(|info| {
+                let candidate = load_candidate(&ctx.accounts.poll, info)?;
+                Ok(CandidateVotes {
+                    name: candidate.candidate_name,
+                    votes: candidate.candidate_votes,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        emit!(CandidateSnapshot { poll_id, candidates });
+        Ok(())
+    }
+
+    /// Emits the full leaderboard, most votes first with ties in pubkey order. All
+    /// of the poll's candidates must be passed so a partial set can't pass for the
+    /// real ranking. Candidates under `results_min_votes` only count towards
+    /// `other_votes`.
+    pub fn rank_candidates(ctx: Context<RankCandidates>, poll_id: u64) -> Result<()> {
+        let candidates = load_candidates(&ctx.accounts.poll, ctx.remaining_accounts)?;
+        let (mut candidates, other_votes) = split_minor_candidates(&ctx.accounts.poll, candidates);
+        candidates.sort_by(|(a_key, a), (b_key, b)| {
+            b.candidate_votes
+                .cmp(&a.candidate_votes)
+                .then_with(|| a_key.cmp(b_key))
+        });
+
+        let ranking = candidates
+            .into_iter()
+            .map(|(_, candidate)| CandidateVotes {
+                name: candidate.candidate_name,
+                votes: candidate.candidate_votes,
+            })
+            .collect();
+        emit!(CandidateRanking { poll_id, ranking, other_votes });
+        Ok(())
+    }
+
+    /// Emits every candidate's votes and share in one event, in the order passed.
+    /// Shares are of the candidate votes (abstentions excluded) and round down,
+    /// so they sum to at most 10000 and fall short by less than one bp per
+    /// candidate. All of the poll's candidates must be passed as remaining accounts.
+    pub fn results_breakdown(ctx: Context<ResultsBreakdownQuery>, poll_id: u64) -> Result<()> {
+        let candidates = load_candidates(&ctx.accounts.poll, ctx.remaining_accounts)?;
+        let total: u64 = candidates.iter().map(|(_, candidate)| candidate.candidate_votes).sum();
+        let (candidates, other_votes) = split_minor_candidates(&ctx.accounts.poll, candidates);
+
+        emit!(ResultsBreakdown {
+            poll_id,
+            total_votes: total,
+            other_votes,
+            candidates: candidates.iter().map(|(key, _)| *key).collect(),
+            votes: candidates.iter().map(|(_, candidate)| candidate.candidate_votes).collect(),
+            share_bps: candidates
+                .iter()
+                .map(|(_, candidate)| share_bps(candidate.candidate_votes, total))
+                .collect(),
+        });
+        Ok(())
+    }
+
+    /// Emits each party's summed votes, parties sorted by name. Independents are
+    /// grouped under the empty name. All of the poll's candidates must be passed
+    /// as remaining accounts.
+    pub fn party_totals(ctx: Context<PartyTotalsQuery>, poll_id: u64) -> Result<()> {
+        let candidates = load_candidates(&ctx.accounts.poll, ctx.remaining_accounts)?;
+
+        let mut totals: Vec<(String, u64)> = Vec::new();
+        for (_, candidate) in &candidates {
+            match totals.iter_mut().find(|(party, _)| *party == candidate.party) {
+                Some((_, votes)) => *votes += candidate.candidate_votes,
+                None => totals.push((candidate.party.clone(), candidate.candidate_votes)),
+            }
+        }
+        totals.sort();
+
+        emit!(PartyTotals {
+            poll_id,
+            votes: totals.iter().map(|(_, votes)| *votes).collect(),
+            parties: totals.into_iter().map(|(party, _)| party).collect(),
+        });
+        Ok(())
+    }
+
+    /// Emits the candidates that have received no votes, in the order passed. All
+    /// of the poll's candidates must be passed as remaining accounts.
+    pub fn zero_vote_candidates(ctx: Context<ZeroVoteCandidates>, poll_id: u64) -> Result<()> {
+        let candidates = load_candidates(&ctx.accounts.poll, ctx.remaining_accounts)?;
+        let unvoted: Vec<Pubkey> = candidates
+            .iter()
+            .filter(|(_, candidate)| candidate.candidate_votes == 0)
+            .map(|(key, _)| *key)
+            .collect();
+
+        emit!(ZeroVoteReport {
+            poll_id,
+            count: unvoted.len() as u64,
+            candidates: unvoted,
+        });
+        Ok(())
+    }
+
+    /// Emits how far the leader is ahead of the runner-up; see
+    /// [`margin_of_victory`]. All of the poll's candidates must be passed as
+    /// remaining accounts.
+    pub fn victory_margin(ctx: Context<VictoryMargin>, poll_id: u64) -> Result<()> {
+        let poll = &ctx.accounts.poll;
+        let candidates = load_candidates(poll, ctx.remaining_accounts)?;
+        let (winner, runner_up, margin_votes, margin_bps) = margin_of_victory(&candidates, poll.total_votes_wide());
+
+        emit!(VictoryMarginReport {
+            poll_id,
+            winner,
+            runner_up,
+            margin_votes,
+            margin_bps,
+        });
+        Ok(())
+    }
+
+    /// Emits the poll's tally in the canonical [`encode_results`] format for
+    /// bridges to relay. All of the poll's candidates must be passed as remaining
+    /// accounts.
+    pub fn serialize_results(ctx: Context<SerializeResults>, poll_id: u64) -> Result<()> {
+        let candidates = load_candidates(&ctx.accounts.poll, ctx.remaining_accounts)?;
+        let tally: Vec<(u64, Pubkey, u64)> = candidates
+            .iter()
+            .map(|(key, candidate)| (candidate.candidate_index, *key, candidate.candidate_votes))
+            .collect();
+
+        let results = encode_results(poll_id, &tally);
+        let hash = hashv(&[&results]).to_bytes();
+        emit!(SerializedResults { poll_id, results, hash });
+        Ok(())
+    }
+
+    /// Logs the poll's tally as [`results_csv`] lines, one `msg!` each, for log
+    /// scrapers that don't decode events. All of the poll's candidates must be
+    /// passed as remaining accounts.
+    pub fn export_csv(ctx: Context<ExportCsv>, _poll_id: u64) -> Result<()> {
+        let candidates = load_candidates(&ctx.accounts.poll, ctx.remaining_accounts)?;
+        let tally: Vec<(u64, Pubkey, u128)> = candidates
+            .iter()
+            .map(|(key, candidate)| (candidate.candidate_index, *key, candidate.votes()))
+            .collect();
+
+        for line in results_csv(&tally) {
+            msg!("{}", line);
+        }
+        Ok(())
+    }
+
+    /// Stores a Merkle root over the finalized results (see [`results_root`]) so
+    /// auditors can publish one hash and prove any candidate's tally against it.
+    /// All of the poll's candidates must be passed as remaining accounts.
+    pub fn compute_results_commitment(
+        ctx: Context<ComputeResultsCommitment>,
+        poll_id: u64
+    ) -> Result<()> {
+        let poll = &mut ctx.accounts.poll;
+        if !poll.is_finalized {
+            return Err(Errors::PollNotFinalized.into());
+        }
+
+        let candidates = load_candidates(poll, ctx.remaining_accounts)?;
+        poll.results_root = results_root(&results_tree_tally(&candidates));
+
+        msg!("Poll {} results committed", poll_id);
+        Ok(())
+    }
+
+    /// Checks `candidate` finished with `votes` against the poll's committed
+    /// results root, using the sibling hashes in `proof` from leaf to root.
+    pub fn verify_result_proof(
+        ctx: Context<VerifyResultProof>,
+        _poll_id: u64,
+        candidate: Pubkey,
+        votes: u64,
+        proof: Vec<[u8; 32]>
+    ) -> Result<()> {
+        let root = ctx.accounts.poll.results_root;
+        if root == [0u8; 32] {
+            return Err(Errors::ResultsNotCommitted.into());
+        }
+
+        if !verify_merkle_proof(results_leaf(&candidate, votes), &proof, &root) {
+            return Err(Errors::InvalidResultProof.into());
+        }
+
+        msg!("{} finished with {} votes", candidate, votes);
+        Ok(())
+    }
+
+    /// Emits the candidate's share of all votes cast, in basis points.
+    pub fn candidate_share(
+        ctx: Context<CandidateShareQuery>,
+        _candidate_name: String,
+        poll_id: u64
+    ) -> Result<()> {
+        let poll = &ctx.accounts.poll;
+        let candidate = &ctx.accounts.candidate;
+
+        emit!(CandidateShare {
+            poll_id,
+            candidate: candidate.key(),
+            candidate_votes: candidate.candidate_votes,
+            total_votes: poll.total_votes,
+            share_bps: share_bps(candidate.candidate_votes, poll.total_votes),
+        });
+        Ok(())
+    }
+
+    /// Emits the candidate's weighted tally in basis points together with the
+    /// whole-vote figure clients should display, see [`display_votes`].
+    pub fn candidate_display_votes(
+        ctx: Context<CandidateShareQuery>,
+        _candidate_name: String,
+        poll_id: u64
+    ) -> Result<()> {
+        let candidate = &ctx.accounts.candidate;
+
+        emit!(CandidateDisplayVotes {
+            poll_id,
+            candidate: candidate.key(),
+            weighted_votes_bps: candidate.weighted_votes,
+            display_votes: display_votes(candidate.weighted_votes),
+        });
+        Ok(())
+    }
+
+    /// Health check cross-validating the poll against its full candidate set:
+    /// candidate count, per-candidate poll linkage and the vote sum. Single-choice
+    /// polls need the sum to match `total_votes`; approval polls only need one
+    /// selection or abstention per voter.
+    pub fn verify_poll_integrity(ctx: Context<VerifyPollIntegrity>, poll_id: u64) -> Result<()> {
+        let poll = &ctx.accounts.poll;
+        let candidates = load_candidates(poll, ctx.remaining_accounts)?;
+
+        let vote_sum = candidates
+            .iter()
+            .map(|(_, candidate)| candidate.candidate_votes as u128)
+            .sum::<u128>();
+        if poll.settings.counting_mode == COUNTING_APPROVAL {
+            if vote_sum + (poll.abstentions as u128) < poll.unique_voters as u128 {
+                msg!(
+                    "Poll {} candidate votes sum to {} with {} abstentions, short of {} voters",
+                    poll_id,
+                    vote_sum,
+                    poll.abstentions,
+                    poll.unique_voters
+                );
+                return Err(Errors::VoteSumMismatch.into());
+            }
+        } else if vote_sum != poll.candidate_vote_total()? as u128 {
+            msg!(
+                "Poll {} candidate votes sum to {} but total_votes is {} ({} abstentions)",
+                poll_id,
+                vote_sum,
+                poll.total_votes,
+                poll.abstentions
+            );
+            return Err(Errors::VoteSumMismatch.into());
+        }
+
+        msg!("Poll {} passed integrity checks", poll_id);
+        Ok(())
+    }
+
+    /// Repair tool for a `total_votes` that has drifted from the candidate tallies:
+    /// overwrites it with their sum (plus abstentions when the total includes
+    /// them). All of the poll's candidates must be passed as remaining accounts.
+    pub fn recompute_total_votes(ctx: Context<RecomputeTotalVotes>, poll_id: u64) -> Result<()> {
+        let poll = &mut ctx.accounts.poll;
+        let candidates = load_candidates(poll, ctx.remaining_accounts)?;
+
+        let mut new_total = candidates
+            .iter()
+            .try_fold(0u64, |sum, (_, candidate)| sum.checked_add(candidate.candidate_votes))
+            .ok_or(Errors::VoteOverflow)?;
+        if poll.settings.include_abstentions_in_total {
+            new_total = new_total.checked_add(poll.abstentions).ok_or(Errors::VoteOverflow)?;
+        }
+
+        let old_total = poll.total_votes;
+        poll.total_votes = new_total;
+        let now = now()? as i64;
+        ctx.accounts.audit_log.record(AUDIT_RECOMPUTE_TOTAL, ctx.accounts.authority.key(), now);
+
+        emit!(TotalRecomputed { poll_id, old_total, new_total });
+        Ok(())
+    }
+
+    /// Emits how many more votes the signer can cast in this poll. Voters without
+    /// a record yet have the full budget; unlimited polls report `u64::MAX`.
+    pub fn remaining_votes(ctx: Context<RemainingVotes>, poll_id: u64) -> Result<()> {
+        let votes_per_voter = ctx.accounts.poll.settings.votes_per_voter;
+        let voter_record = &ctx.accounts.voter_record;
+
+        let votes_used = if voter_record.data_is_empty() {
+            0
+        } else {
+            VoterRecord::try_deserialize(&mut &voter_record.data.borrow()[..])?.votes_used
+        };
+        let remaining = if votes_per_voter == 0 {
+            u64::MAX
+        } else {
+            votes_per_voter.saturating_sub(votes_used)
+        };
+
+        emit!(RemainingBudget {
+            voter: ctx.accounts.signer.key(),
+            poll_id,
+            remaining,
+        });
+        Ok(())
+    }
+
+    /// Emits whether the poll accepts votes right now. `reason` is the error code
+    /// `vote` would fail with, or 0 when the poll is votable, so clients can skip
+    /// prompting for a ballot that is bound to be rejected.
+    pub fn is_votable(ctx: Context<IsVotable>, poll_id: u64) -> Result<()> {
+        let now = now()?;
+        let blocker = ctx.accounts.poll.voting_blocker(now);
+
+        emit!(PollVotable {
+            poll_id,
+            votable: blocker.is_none(),
+            reason: blocker.map_or(0, u32::from),
+        });
+        Ok(())
+    }
+
+    /// Emits countdowns to the poll's start and end by chain time, both saturating
+    /// at 0 once passed.
+    pub fn time_remaining(ctx: Context<TimeRemaining>, poll_id: u64) -> Result<()> {
+        let poll = &ctx.accounts.poll;
+        let now = now()?;
+
+        emit!(PollTiming {
+            poll_id,
+            seconds_until_start: poll.poll_start.saturating_sub(now),
+            seconds_until_end: poll.poll_end.saturating_sub(now),
+            status: poll.status(now),
+        });
+        Ok(())
+    }
+
+    /// Returns the poll's status and winner as return data rather than an event,
+    /// so a program calling it by CPI can read them with `get_return_data`.
+    pub fn get_poll_status(ctx: Context<GetPollStatus>, poll_id: u64) -> Result<PollStatus> {
+        let poll = &ctx.accounts.poll;
+        Ok(PollStatus {
+            poll_id,
+            status: poll.status(now()?),
+            is_finalized: poll.is_finalized,
+            winner: poll.winner,
+        })
+    }
+
+    /// Emits the share of registered voters who have voted or abstained, in basis
+    /// points, or 0 when nobody is registered.
+    pub fn turnout(ctx: Context<PollTurnout>, poll_id: u64) -> Result<()> {
+        let poll = &ctx.accounts.poll;
+        emit!(Turnout {
+            poll_id,
+            unique_voters: poll.unique_voters,
+            registered_voter_count: poll.registered_voter_count,
+            turnout_bps: share_bps(poll.unique_voters, poll.registered_voter_count),
+        });
+        Ok(())
+    }
+
+    /// Commits to the seed that will pick the sortition winner, as
+    /// `hash(seed)`. Must happen before voting opens so the seed can't be chosen
+    /// with the ballots in view.
+    pub fn commit_sortition_seed(
+        ctx: Context<CommitSortitionSeed>,
+        _poll_id: u64,
+        commitment: [u8; 32]
+    ) -> Result<()> {
+        let now = now()? as i64;
+        if now as u64 >= ctx.accounts.poll.poll_start {
+            return Err(Errors::PollAlreadyStarted.into());
+        }
+
+        ctx.accounts.poll.sortition_commitment = commitment;
+        ctx.accounts.audit_log.record(AUDIT_COMMIT_SORTITION, ctx.accounts.authority.key(), now);
+        Ok(())
+    }
+
+    /// Finalizes an ended poll by sortition: reveals the committed seed and uses
+    /// it to pick one of the poll's voter records, whose candidate becomes the
+    /// winner. Remaining accounts must be every voter record of the poll, in any
+    /// order, and each must hold a ballot. An abstaining or unconfirmed pick leaves
+    /// the poll without a winner. As with `auto_finalize`, `PollResults` is then
+    /// written by calling `finalize_poll` with the candidates.
+    pub fn sortition_select(ctx: Context<SortitionSelect>, poll_id: u64, seed: [u8; 32]) -> Result<()> {
+        let poll = &ctx.accounts.poll;
+        let now = now()? as i64;
+
+        if now as u64 <= poll.poll_end {
+            return Err(Errors::PollNotEnded.into());
+        }
+
+        if poll.is_finalized {
+            return Err(Errors::PollAlreadyFinalized.into());
+        }
+
+        if poll.settings.anonymous {
+            return Err(Errors::VotingModeMismatch.into());
+        }
+
+        if poll.sortition_commitment == [0; 32] {
+            return Err(Errors::SortitionNotCommitted.into());
+        }
+
+        if hashv(&[&seed]).to_bytes() != poll.sortition_commitment {
+            return Err(Errors::SortitionSeedMismatch.into());
+        }
+
+        if ctx.remaining_accounts.len() as u64 != poll.unique_voters || poll.unique_voters == 0 {
+            return Err(Errors::VoterCountMismatch.into());
+        }
+
+        let mut records: Vec<VoterRecord> = Vec::with_capacity(ctx.remaining_accounts.len());
+        for info in ctx.remaining_accounts {
+            if info.owner != &crate::ID {
+                return Err(Errors::VoterPollMismatch.into());
+            }
+            let record = VoterRecord::try_deserialize(&mut &info.data.borrow()[..])?;
+            if record.poll != poll.key() {
+                return Err(Errors::VoterPollMismatch.into());
+            }
+            // registered records exist for any pubkey the authority picks, so only
+            // ballots can be drawn or the seed holder could steer the draw
+            if !record.has_voted() {
+                return Err(Errors::RecordHasNotVoted.into());
+            }
+            // `advance_round` resets `unique_voters`; a record still holding an
+            // earlier round's ballot hasn't voted in this one
+            if record.round != poll.round {
+                return Err(Errors::StaleVoterRecord.into());
+            }
+            if records.iter().any(|other| other.voter == record.voter) {
+                return Err(Errors::VoterCountMismatch.into());
+            }
+            records.push(record);
+        }
+        // the client picks the account order, so rank by voter before indexing
+        records.sort_by_key(|record| record.voter);
+        let selected = &records[sortition_index(&seed, poll_id, records.len())];
+        let candidate = (selected.confirmed && selected.candidate != Pubkey::default())
+            .then_some(selected.candidate);
+
+        let voter = selected.voter;
+        let poll = &mut ctx.accounts.poll;
+        poll.winner = candidate;
+        poll.no_winner = candidate.is_none();
+        poll.is_finalized = true;
+        poll.finalized_at = now as u64;
+        ctx.accounts.audit_log.record(AUDIT_FINALIZE, ctx.accounts.authority.key(), now);
+
+        emit!(SortitionSelected { poll_id, voter, candidate });
+        Ok(())
+    }
+
+    /// Records the supply of an SPL Token mint as the base for `supply_turnout`.
+    /// Can be retaken until voting opens.
+    pub fn snapshot_supply(ctx: Context<SnapshotSupply>, poll_id: u64) -> Result<()> {
+        let now = now()?;
+        if now >= ctx.accounts.poll.poll_start {
+            return Err(Errors::PollAlreadyStarted.into());
+        }
+
+        let supply = mint_supply(&ctx.accounts.mint.data.borrow()).ok_or(Errors::InvalidMint)?;
+        if supply == 0 {
+            return Err(Errors::ZeroSupply.into());
+        }
+
+        ctx.accounts.poll.snapshot_supply = supply;
+        msg!("Poll {} snapshotted a supply of {}", poll_id, supply);
+        Ok(())
+    }
+
+    /// Emits `total_votes` as a share of the snapshotted token supply, in basis
+    /// points.
+    pub fn supply_turnout(ctx: Context<PollSupplyTurnout>, poll_id: u64) -> Result<()> {
+        let poll = &ctx.accounts.poll;
+        if poll.snapshot_supply == 0 {
+            return Err(Errors::SupplyNotSnapshotted.into());
+        }
+
+        emit!(SupplyTurnout {
+            poll_id,
+            total_votes: poll.total_votes,
+            snapshot_supply: poll.snapshot_supply,
+            turnout_bps: share_bps(poll.total_votes, poll.snapshot_supply),
+        });
+        Ok(())
+    }
+
+    /// Closes the poll once voting has ended. All of the poll's candidates must be
+    /// passed as remaining accounts so the winner is picked from the full ballot.
+    /// Polls using `TIE_BREAK_SLOT_HASH` must also pass the `SlotHashes` sysvar.
+    /// Calling it again on a finalized poll leaves the result as it is, but with
+    /// the candidates passed it (re)writes `PollResults`, e.g. after `auto_finalize`
+    /// or `sortition_select`. The winner is also set as return data for programs finalizing by CPI.
+    pub fn finalize_poll(ctx: Context<FinalizePoll>, poll_id: u64) -> Result<Option<Pubkey>> {
+        let poll = &mut ctx.accounts.poll;
+        let now = now()?;
+
+        // the first finalization stands; repeats are no-ops so automation can retry,
+        // apart from writing the results of a poll finalized some other way
+        if poll.is_finalized {
+            msg!("Poll {} was already finalized at {}", poll_id, poll.finalized_at);
+            if !ctx.remaining_accounts.is_empty() {
+                let candidates = load_candidates(poll, ctx.remaining_accounts)?;
+                if candidates.len() > MAX_RESULTS_CANDIDATES {
+                    return Err(Errors::TooManyCandidates.into());
+                }
+                ctx.accounts.results.set_inner(PollResults::tally(poll, &candidates));
+            }
+            return Ok(poll.winner);
+        }
+
+        if now <= poll.poll_end {
+            return Err(Errors::PollNotEnded.into());
+        }
+
+        let candidates = load_candidates(poll, ctx.remaining_accounts)?;
+        if candidates.len() > MAX_RESULTS_CANDIDATES {
+            return Err(Errors::TooManyCandidates.into());
+        }
+        let slot_hash = match &ctx.accounts.slot_hashes {
+            Some(sysvar) if poll.settings.tie_break == TIE_BREAK_SLOT_HASH => {
+                Some(latest_slot_hash(sysvar)?)
+            }
+            _ => None,
+        };
+        poll.winner = tally_winner(poll, &candidates, slot_hash.as_ref())?;
+        poll.no_winner = poll.winner.is_none();
+        poll.valid_result = is_contested(poll, &candidates);
+        poll.is_finalized = true;
+        poll.finalized_at = now;
+
+        ctx.accounts.results.set_inner(PollResults::tally(poll, &candidates));
+        ctx.accounts.audit_log.record(AUDIT_FINALIZE, ctx.accounts.authority.key(), now as i64);
+        Ok(poll.winner)
+    }
+
+    /// Starts the next round of a multi-round poll. Every candidate must be passed
+    /// as a writable remaining account: each tally moves into
+    /// `total_votes_all_rounds` and starts over at 0, as do the poll's turnout
+    /// counts and its leaderboard, and every voter gets a fresh ballot.
+    pub fn advance_round(ctx: Context<AdvanceRound>, poll_id: u64) -> Result<()> {
+        let poll = &mut ctx.accounts.poll;
+        if poll.is_finalized {
+            return Err(Errors::PollFinalized.into());
+        }
+        // nullifiers, identity budgets and token ballots are spent for the whole poll
+        if poll.settings.anonymous || poll.settings.require_identity || poll.settings.gate_mint.is_some() {
+            return Err(Errors::VotingModeMismatch.into());
+        }
+
+        let candidates = load_candidates(poll, ctx.remaining_accounts)?;
+        for ((_, mut candidate), info) in candidates.into_iter().zip(ctx.remaining_accounts) {
+            let votes = u64::try_from(candidate.votes()).map_err(|_| Errors::VoteOverflow)?;
+            candidate.total_votes_all_rounds = candidate
+                .total_votes_all_rounds
+                .checked_add(votes)
+                .ok_or(Errors::VoteOverflow)?;
+            candidate.candidate_votes = 0;
+            candidate.candidate_votes_hi = 0;
+            candidate.weighted_votes = 0;
+            candidate.try_serialize(&mut &mut info.data.borrow_mut()[..])?;
+        }
+
+        let votes = u64::try_from(poll.total_votes_wide()).map_err(|_| Errors::VoteOverflow)?;
+        poll.total_votes_all_rounds = poll.total_votes_all_rounds.checked_add(votes).ok_or(Errors::VoteOverflow)?;
+        poll.total_votes = 0;
+        poll.total_votes_hi = 0;
+        poll.weighted_total = 0;
+        poll.abstentions = 0;
+        poll.blank_votes = 0;
+        poll.unique_voters = 0;
+        poll.round += 1;
+        ctx.accounts.top_k.poll = poll.key();
+        ctx.accounts.top_k.entries = Default::default();
+        ctx.accounts.audit_log.record(
+            AUDIT_ADVANCE_ROUND,
+            ctx.accounts.authority.key(),
+            now()? as i64,
+        );
+
+        emit!(RoundAdvanced {
+            poll_id,
+            round: poll.round,
+            votes_last_round: votes,
+        });
+        Ok(())
+    }
+
+    /// Corrects `candidate`'s tally on a finalized poll while its dispute window is
+    /// open, then picks the winner again and rewrites the poll's `PollResults`,
+    /// leaderboard and (if one was committed) results root to match. All of the
+    /// poll's candidates must be passed as remaining accounts, `candidate` writable.
+    pub fn adjust_votes(
+        ctx: Context<AdjustVotes>,
+        poll_id: u64,
+        candidate: Pubkey,
+        votes: u64
+    ) -> Result<()> {
+        let poll = &mut ctx.accounts.poll;
+        let now = now()?;
+        match poll.dispute_window_state(now) {
+            DisputeWindowState::NotFinalized => return Err(Errors::PollNotFinalized.into()),
+            DisputeWindowState::Closed => return Err(Errors::DisputeWindowClosed.into()),
+            DisputeWindowState::Open => {}
+        }
+
+        let mut candidates = load_candidates(poll, ctx.remaining_accounts)?;
+        let index = candidates
+            .iter()
+            .position(|(key, _)| *key == candidate)
+            .ok_or(Errors::CandidatePollMismatch)?;
+        let adjusted = &mut candidates[index].1;
+        let previous = adjusted.votes();
+        let total = poll
+            .total_votes_wide()
+            .checked_sub(previous)
+            .ok_or(Errors::VoteSumMismatch)?
+            + votes as u128;
+        poll.set_total_votes(total)?;
+
+        // the corrected tally keeps the candidate's average vote weight
+        let weighted = (adjusted.weighted_votes as u128 * votes as u128)
+            .checked_div(previous)
+            .unwrap_or(votes as u128 * FULL_VOTE_WEIGHT_BPS as u128);
+        let weighted = u64::try_from(weighted).map_err(|_| Errors::VoteOverflow)?;
+        poll.weighted_total = poll
+            .weighted_total
+            .checked_sub(adjusted.weighted_votes)
+            .ok_or(Errors::VoteSumMismatch)?
+            .checked_add(weighted)
+            .ok_or(Errors::VoteOverflow)?;
+        adjusted.weighted_votes = weighted;
+        adjusted.candidate_votes = votes;
+        adjusted.candidate_votes_hi = 0;
+        adjusted.try_serialize(&mut &mut ctx.remaining_accounts[index].data.borrow_mut()[..])?;
+
+        // a slot hash tie-break can't be replayed here; an unresolvable tie errors out
+        poll.winner = tally_winner(poll, &candidates, None)?;
+        poll.no_winner = poll.winner.is_none();
+        poll.valid_result = is_contested(poll, &candidates);
+
+        ctx.accounts.top_k.poll = poll.key();
+        ctx.accounts.top_k.rebuild(&candidates);
+        if poll.results_root != [0u8; 32] {
+            poll.results_root = results_root(&results_tree_tally(&candidates));
+        }
+        ctx.accounts.results.set_inner(PollResults::tally(poll, &candidates));
+        ctx.accounts.audit_log.record(AUDIT_ADJUST_VOTES, ctx.accounts.authority.key(), now as i64);
+
+        msg!("Poll {} candidate {} adjusted from {} to {} votes", poll_id, candidate, previous, votes);
+        Ok(())
+    }
+
+    /// Reopens a finalized poll (e.g. after a successful dispute), clearing the
+    /// recorded winner and moving the end date to `new_poll_end`. A poll with a
+    /// dispute window can only be reopened while that window is open.
+    pub fn reopen_poll(ctx: Context<ReopenPoll>, poll_id: u64, new_poll_end: u64) -> Result<()> {
+        let poll = &mut ctx.accounts.poll;
+        let now = now()?;
+
+        match poll.dispute_window_state(now) {
+            DisputeWindowState::NotFinalized => return Err(Errors::PollNotFinalized.into()),
+            DisputeWindowState::Closed if poll.settings.dispute_window_seconds > 0 => {
+                return Err(Errors::DisputeWindowClosed.into());
+            }
+            _ => {}
+        }
+
+        if !is_valid_timestamp(new_poll_end) {
+            return Err(Errors::InvalidTimestamp.into());
+        }
+
+        if new_poll_end <= now {
+            return Err(Errors::PollEndInThePast.into());
+        }
+
+        if poll.poll_start >= new_poll_end {
+            return Err(Errors::InvalidEndDate.into());
+        }
+
+        poll.is_finalized = false;
+        poll.finalized_at = 0;
+        poll.winner = None;
+        poll.no_winner = false;
+        poll.valid_result = false;
+        poll.poll_end = new_poll_end;
+
+        ctx.accounts.audit_log.record(AUDIT_REOPEN, ctx.accounts.authority.key(), now as i64);
+        msg!(
+            "Poll {} reopened by {} until {}",
+            poll_id,
+            ctx.accounts.authority.key(),
+            new_poll_end
+        );
+        emit!(PollReopened {
+            poll_id,
+            authority: ctx.accounts.authority.key(),
+            new_poll_end,
+        });
+        Ok(())
+    }
+}
+
+/// Applies a vote to the tallies: bumps the candidate and poll counts, records the
+/// receipt and allocation, and calls the poll's hook.
+fn count_vote(
+    accounts: &mut Vote,
+    poll_id: u64,
+    remaining_accounts: &[AccountInfo],
+    votes: u64
+) -> Result<()> {
+    let voted_at = now()? as i64;
+    let candidate = &mut accounts.candidate;
+    let weight_bps = accounts.poll.record_votes(voted_at, votes)?;
+    throttle_vote_velocity(&mut accounts.poll, poll_id)?;
+    candidate.record_vote(voted_at, votes, weight_bps, accounts.poll.settings.high_precision)?;
+
+    if accounts.poll.settings.verbose_logging {
+        msg!("Voted for candidate: {}", candidate.candidate_name);
+        msg!("Votes: {}", candidate.candidate_votes);
+    }
+
+    let candidate_key = candidate.key();
+    let voter = accounts.signer.key();
+    let receipt_hash = receipt_hash(poll_id, &candidate_key, &voter, voted_at);
+
+    let voter_record = &mut accounts.voter_record;
+    voter_record.poll = accounts.poll.key();
+    voter_record.voter = voter;
+    voter_record.version = VOTER_RECORD_VERSION;
+    voter_record.candidate = candidate_key;
+    voter_record.choice = BallotChoice::Candidate;
+    voter_record.voted_at = voted_at;
+    voter_record.receipt_hash = receipt_hash;
+    if voter_record.votes_used == 0 {
+        accounts.poll.unique_voters += 1;
+        accounts.voter_global.join_poll(voter, max_polls_per_voter(&accounts.config)?)?;
+    }
+    voter_record.votes_used += 1;
+    voter_record.confirmed = true;
+
+    accounts.voter_allocation.add(candidate_key, votes)?;
+    accounts.voter_index.record(poll_id);
+    accounts.top_k.poll = accounts.poll.key();
+    accounts.top_k.record(candidate_key, accounts.candidate.votes());
+    if accounts.poll.vote_log {
+        let entry = VoteLogEntry {
+            candidate_index: u16::try_from(accounts.candidate.candidate_index).map_err(|_| Errors::VoteOverflow)?,
+            weight: u32::try_from(votes).map_err(|_| Errors::VoteOverflow)?,
+        };
+        accounts.vote_log.as_mut().ok_or(Errors::VoteLogMissing)?.append(entry);
+    }
+
+    emit!(VoteReceipt {
+        poll_id,
+        candidate: candidate_key,
+        voter,
+        voted_at,
+        receipt_hash,
+    });
+    auto_finalize(&mut accounts.poll, &accounts.top_k, poll_id, voted_at as u64);
+
+    if let Some(hook_program) = accounts.poll.settings.hook_program {
+        // every write happens before the external call. The tallies are persisted
+        // so the hook reads them, and the poll is flagged so a call back into the
+        // voting paths fails until the hook returns.
+        accounts.poll.processing = true;
+        accounts.poll.exit(&crate::ID)?;
+        accounts.candidate.exit(&crate::ID)?;
+        invoke_vote_hook(&hook_program, poll_id, &candidate_key, &voter, remaining_accounts)?;
+        accounts.poll.processing = false;
+    }
+    Ok(())
+}
+
+/// Validates and fills in a new poll for `initialize_poll` and
+/// `initialize_poll_with_candidates`.
+fn open_poll(
+    accounts: &mut InitializePoll,
+    poll_id: u64,
+    description: String,
+    poll_start: u64,
+    poll_end: u64,
+    result_callback_hash: [u8; 32]
+) -> Result<()> {
+    require_valid_signer(&accounts.signer.key())?;
+    ensure_not_paused(&accounts.config)?;
+
+    let poll = &mut accounts.poll;
+    let now = now()?;
+
+    validate_schedule(poll_id, poll_start, poll_end, now)?;
+
+    if cfg!(feature = "min-notice") && !has_notice(poll_start, now) {
+        msg!("Poll {} opens at {}, less than {}s after {}", poll_id, poll_start, MIN_NOTICE_SECONDS, now);
+        return Err(Errors::InsufficientNotice.into());
+    }
+
+    if description.len() > MAX_DESCRIPTION_LEN {
+        msg!("Description is {} bytes, the limit is {}", description.len(), MAX_DESCRIPTION_LEN);
+        return Err(Errors::DescriptionTooLong.into());
+    }
+
+    if !is_renderable_description(&description) {
+        return Err(Errors::InvalidDescriptionChars.into());
+    }
+
+    poll.poll_id = poll_id;
+    poll.description = description;
+    poll.poll_start = poll_start.max(now);
+    poll.opened_at_creation = poll_start <= now;
+    poll.poll_end = poll_end;
+    poll.candidate_amount = 0;
+    poll.total_votes = 0;
+    poll.authority = accounts.signer.key();
+    poll.is_finalized = false;
+    poll.winner = None;
+    poll.no_winner = false;
+    poll.settings = PollSettings::default();
+    poll.approved = false;
+    poll.candidate_seed_version = CANDIDATE_SEED_VERSION;
+    poll.bucket_seconds = bucket_seconds(poll.poll_start, poll_end);
+    poll.result_callback_hash = result_callback_hash;
+    accounts.audit_log.poll = poll.key();
+    Ok(())
+}
+
+/// Pauses the poll when the ballot just counted pushed its slot past
+/// `max_votes_per_slot`. That ballot still counts; the ones after it fail with
+/// `PollPaused` until the authority calls `resume_poll`.
+fn throttle_vote_velocity(poll: &mut Poll, poll_id: u64) -> Result<()> {
+    let slot = clock()?.slot;
+    if poll.track_slot_votes(slot) {
+        msg!("Poll {} paused after {} votes in slot {}", poll_id, poll.slot_votes, slot);
+        emit!(SuspiciousVelocityPaused {
+            poll_id,
+            slot,
+            votes_in_slot: poll.slot_votes,
+        });
+    }
+    Ok(())
+}
+
+/// Finalizes an `auto_finalize_on_full_turnout` poll once every registered voter
+/// has voted, taking the winner from the `TopK` leaderboard instead of the full
+/// candidate set. When the leaderboard can't settle the result the way
+/// `finalize_poll` would, the poll stays open for `finalize_poll` instead.
+/// Calling `finalize_poll` with the candidates afterwards writes `PollResults`.
+fn auto_finalize(poll: &mut Poll, top_k: &TopK, poll_id: u64, now: u64) {
+    if !poll.settings.auto_finalize_on_full_turnout
+        || !poll.settings.require_registration
+        || poll.registered_voter_count == 0
+        || poll.unique_voters < poll.registered_voter_count
+    {
+        return;
+    }
+    let Some((winner, valid_result)) = top_k.settled_result(poll) else {
+        msg!("Poll {} has full turnout but needs finalize_poll to settle", poll_id);
+        return;
+    };
+
+    poll.winner = winner;
+    poll.no_winner = winner.is_none();
+    poll.valid_result = valid_result;
+    poll.is_finalized = true;
+    poll.finalized_at = now;
+    emit!(AutoFinalized {
+        poll_id,
+        winner,
+        unique_voters: poll.unique_voters,
+    });
+}
+
+/// Calls `on_vote(poll_id, candidate, voter)` on the poll's hook program. The
+/// first remaining account must be the hook program itself; the rest are
+/// forwarded as the hook instruction's accounts. The hook runs after every state
+/// change in `vote`. The runtime already rejects the hook calling back into this
+/// program; `Poll::processing` is a second line of defense should a future CPI
+/// path allow it.
+fn invoke_vote_hook(
+    hook_program: &Pubkey,
+    poll_id: u64,
+    candidate: &Pubkey,
+    voter: &Pubkey,
+    accounts: &[AccountInfo],
+) -> Result<()> {
+    let (program, hook_accounts) = accounts
+        .split_first()
+        .ok_or(Errors::InvalidHookProgram)?;
+    if program.key() != *hook_program || !program.executable {
+        return Err(Errors::InvalidHookProgram.into());
+    }
+
+    let mut data = hashv(&[b"global:on_vote"]).to_bytes()[..8].to_vec();
+    data.extend_from_slice(&poll_id.to_le_bytes());
+    data.extend_from_slice(candidate.as_ref());
+    data.extend_from_slice(voter.as_ref());
+
+    let metas = hook_accounts
+        .iter()
+        .map(|info| {
+            if info.is_writable {
+                AccountMeta::new(info.key(), info.is_signer)
+            } else {
+                AccountMeta::new_readonly(info.key(), info.is_signer)
+            }
+        })
+        .collect();
+
+    invoke(
+        &Instruction {
+            program_id: *hook_program,
+            accounts: metas,
+            data,
+        },
+        accounts,
+    )?;
+    Ok(())
+}
+
+/// Turns the receipt `mint` into its collection's next member and mints its
+/// single token to `token_account`, then drops the mint authority so no more
+/// can be minted. `authority` signs with `signer_seeds` as both the mint
+/// authority and the collection's update authority. The token group
+/// interface's `InitializeMember` and Token-2022's `MintTo` and `SetAuthority`
+/// are encoded by hand, like the hook call above.
+fn mint_receipt<'info>(
+    token_program: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    token_account: &AccountInfo<'info>,
+    collection: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    signer_seeds: &[&[u8]],
+) -> Result<()> {
+    let initialize_member = Instruction {
+        program_id: TOKEN_2022_PROGRAM_ID,
+        // member, member mint, member mint authority, group, group update authority
+        accounts: vec![
+            AccountMeta::new(mint.key(), false),
+            AccountMeta::new_readonly(mint.key(), false),
+            AccountMeta::new_readonly(authority.key(), true),
+            AccountMeta::new(collection.key(), false),
+            AccountMeta::new_readonly(authority.key(), true),
+        ],
+        data: hashv(&[b"spl_token_group_interface:initialize_member"]).to_bytes()[..8].to_vec(),
+    };
+    let mut mint_to_data = vec![7];
+    mint_to_data.extend_from_slice(&1u64.to_le_bytes());
+    let mint_to = Instruction {
+        program_id: TOKEN_2022_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(mint.key(), false),
+            AccountMeta::new(token_account.key(), false),
+            AccountMeta::new_readonly(authority.key(), true),
+        ],
+        data: mint_to_data,
+    };
+    // the mint tokens authority (0), set to none
+    let revoke_authority = Instruction {
+        program_id: TOKEN_2022_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(mint.key(), false),
+            AccountMeta::new_readonly(authority.key(), true),
+        ],
+        data: vec![6, 0, 0],
+    };
+
+    let infos = [mint.clone(), token_account.clone(), collection.clone(), authority.clone(), token_program.clone()];
+    for instruction in [initialize_member, mint_to, revoke_authority] {
+        invoke_signed(&instruction, &infos, &[signer_seeds])?;
+    }
+    Ok(())
+}
+
+/// `votes / total` in basis points, 0 when nothing has been cast yet.
+fn share_bps(votes: u64, total: u64) -> u64 {
+    if total == 0 {
+        return 0;
+    }
+    (votes as u128 * 10_000 / total as u128) as u64
+}
+
+/// Reads `supply` from the data of an initialized SPL Token mint.
+fn mint_supply(data: &[u8]) -> Option<u64> {
+    if data.len() != MINT_ACCOUNT_LEN || data[45] != 1 {
+        return None;
+    }
+    Some(u64::from_le_bytes(data[36..44].try_into().unwrap()))
+}
+
+/// Mint, owner and amount of an initialized Token-2022 token account.
+fn token_2022_account(data: &[u8]) -> Option<(Pubkey, Pubkey, u64)> {
+    // past the base layout, only the token account type (2) may follow
+    if data.len() < TOKEN_ACCOUNT_LEN || data[108] != 1 || data.get(TOKEN_ACCOUNT_LEN).is_some_and(|ty| *ty != 2) {
+        return None;
+    }
+    let mint = Pubkey::try_from(&data[..32]).ok()?;
+    let owner = Pubkey::try_from(&data[32..64]).ok()?;
+    Some((mint, owner, u64::from_le_bytes(data[64..72].try_into().ok()?)))
+}
+
+/// Value of the `extension_type` extension of an initialized Token-2022 mint.
+fn mint_extension(data: &[u8], extension_type: u16) -> Option<&[u8]> {
+    // a mint with extensions is padded to a token account's length, then has
+    // its account type (1) before the extensions' type-length-value entries
+    if data.len() <= TOKEN_ACCOUNT_LEN || data[45] != 1 || data[TOKEN_ACCOUNT_LEN] != 1 {
+        return None;
+    }
+    let mut extensions = &data[TOKEN_ACCOUNT_LEN + 1..];
+    while extensions.len() >= 4 {
+        let current = u16::from_le_bytes([extensions[0], extensions[1]]);
+        let len = u16::from_le_bytes([extensions[2], extensions[3]]) as usize;
+        let value = extensions.get(4..4 + len)?;
+        if current == extension_type {
+            return Some(value);
+        }
+        extensions = &extensions[4 + len..];
+    }
+    None
+}
+
+/// Value stored under `key` in the additional metadata of a Token-2022 mint's
+/// embedded `TokenMetadata`, if that metadata describes `mint`.
+fn token_metadata_field(data: &[u8], mint: &Pubkey, key: &str) -> Option<String> {
+    let value = mint_extension(data, TOKEN_METADATA_EXTENSION)?;
+    // update authority and mint, then name, symbol and uri
+    if value.get(32..64)? != mint.as_ref() {
+        return None;
+    }
+    let mut fields = &value[64..];
+    for _ in 0..3 {
+        String::deserialize(&mut fields).ok()?;
+    }
+    let additional = Vec::<(String, String)>::deserialize(&mut fields).ok()?;
+    additional.into_iter().find(|(name, _)| name == key).map(|(_, value)| value)
+}
+
+/// Group mint a Token-2022 mint belongs to, from its `TokenGroupMember`
+/// extension, if that extension describes `mint`.
+fn token_group(data: &[u8], mint: &Pubkey) -> Option<Pubkey> {
+    // mint, group, member number
+    let value = mint_extension(data, TOKEN_GROUP_MEMBER_EXTENSION)?;
+    if value.get(..32)? != mint.as_ref() {
+        return None;
+    }
+    Pubkey::try_from(value.get(32..64)?).ok()
+}
+
+/// Whether `data` is a Token-2022 mint `vote_with_receipt` can turn into a
+/// receipt: non-transferable, with 0 decimals, nothing minted yet and
+/// `authority` as its mint authority.
+fn is_receipt_mint(data: &[u8], authority: &Pubkey) -> bool {
+    // mint authority (an option tag, then the key), supply, decimals
+    mint_extension(data, NON_TRANSFERABLE_EXTENSION).is_some()
+        && data[..4] == 1u32.to_le_bytes()
+        && data[4..36] == authority.to_bytes()
+        && data[36..44] == [0; 8]
+        && data[44] == 0
+}
+
+/// Votes the voter's gate token earns on `poll`: the `tier_weights` entry for
+/// the tier in its mint's metadata. The mint is either `gate_mint` itself or a
+/// member of the `gate_mint` group, e.g. one NFT of a tiered collection.
+fn token_tier_weight(
+    poll: &Poll,
+    voter: &Pubkey,
+    gate_mint: Pubkey,
+    token_account: &AccountInfo,
+    token_mint: &AccountInfo
+) -> std::result::Result<u64, Errors> {
+    let (mint, owner, amount) = token_2022_account(&token_account.data.borrow()).ok_or(Errors::InvalidGateToken)?;
+    if mint != token_mint.key() || owner != *voter || amount == 0 {
+        return Err(Errors::InvalidGateToken);
+    }
+    let mint_data = token_mint.data.borrow();
+    if mint != gate_mint && token_group(&mint_data, &mint) != Some(gate_mint) {
+        return Err(Errors::InvalidGateToken);
+    }
+
+    let tier = token_metadata_field(&mint_data, &mint, TIER_METADATA_KEY)
+        .and_then(|tier| tier.parse::<usize>().ok())
+        .filter(|tier| *tier < TOKEN_TIERS)
+        .ok_or(Errors::InvalidTokenTier)?;
+    match poll.settings.tier_weights[tier] {
+        0 => Err(Errors::NoVotesCast),
+        weight => Ok(weight),
+    }
+}
+
+/// Rounds a basis-point tally to whole votes, half up: 1.5 votes shows as 2.
+fn display_votes(weighted_votes_bps: u64) -> u64 {
+    weighted_votes_bps.saturating_add(FULL_VOTE_WEIGHT_BPS / 2) / FULL_VOTE_WEIGHT_BPS
+}
+
+/// The `Clock` sysvar, failing with `ClockUnavailable` rather than the runtime's
+/// generic sysvar error.
+fn clock() -> Result<Clock> {
+    Clock::get().map_err(|_| Errors::ClockUnavailable.into())
+}
+
+/// Chain time in unix seconds; every time check reads it through here.
+fn now() -> Result<u64> {
+    Ok(clock()?.unix_timestamp as u64)
+}
+
+/// Fails with `ProgramPaused` if the program config exists and is paused.
+fn ensure_not_paused(config: &AccountInfo) -> Result<()> {
+    if is_program_paused(config)? {
+        return Err(Errors::ProgramPaused.into());
+    }
+    Ok(())
+}
+
+/// Fails with `AccountTooNew` on polls with `min_account_age_slots` unless the
+/// voter's `VoterAge` shows them first seen long enough before `slot`. A voter
+/// never registered anywhere has no `VoterAge` and is rejected too.
+fn ensure_account_age(poll: &Poll, voter_age: &AccountInfo, slot: u64) -> Result<()> {
+    let min_age_slots = poll.settings.min_account_age_slots;
+    if min_age_slots == 0 {
+        return Ok(());
+    }
+    if voter_age.owner != &crate::ID {
+        return Err(Errors::AccountTooNew.into());
+    }
+    let age = VoterAge::try_deserialize(&mut &voter_age.data.borrow()[..])?;
+    if !age.old_enough(min_age_slots, slot) {
+        msg!("{} was first seen at slot {}, it is {}", age.voter, age.first_seen_slot, slot);
+        return Err(Errors::AccountTooNew.into());
+    }
+    Ok(())
+}
+
+/// First reason `vote` rejects a ballot that [`ballot_blocker`] lets through:
+/// the checks that read accounts beyond the poll and the voter record, i.e. the
+/// voter's age, the prerequisite poll and, when the ballot is counted right
+/// away, the vote log. `simulate_vote` runs them too so its preview matches.
+fn vote_gate_blocker(
+    poll: &Poll,
+    voter_age: &AccountInfo,
+    prerequisite: Option<&Poll>,
+    has_vote_log: bool,
+    slot: u64
+) -> Option<Errors> {
+    if ensure_account_age(poll, voter_age, slot).is_err() {
+        Some(Errors::AccountTooNew)
+    } else if ensure_prerequisite_met(poll, prerequisite).is_err() {
+        Some(Errors::PrerequisiteNotMet)
+    } else if poll.vote_log && !poll.settings.require_confirmation && !has_vote_log {
+        Some(Errors::VoteLogMissing)
+    } else {
+        None
+    }
+}
+
+/// Fails with `PrerequisiteNotMet` on polls with `depends_on` unless
+/// `prerequisite` is that poll, finalized with a winner (`required_winner`, if
+/// set).
+fn ensure_prerequisite_met(poll: &Poll, prerequisite: Option<&Poll>) -> Result<()> {
+    let Some(depends_on) = poll.settings.depends_on else {
+        return Ok(());
+    };
+    let Some(prerequisite) = prerequisite.filter(|prerequisite| prerequisite.poll_id == depends_on) else {
+        msg!("Poll {} needs poll {} passed in", poll.poll_id, depends_on);
+        return Err(Errors::PrerequisiteNotMet.into());
+    };
+    let passed = match poll.settings.required_winner {
+        Some(required) => prerequisite.winner == Some(required),
+        None => prerequisite.winner.is_some(),
+    };
+    if !prerequisite.is_finalized || !passed {
+        msg!("Poll {} has not passed, poll {} stays closed", depends_on, poll.poll_id);
+        return Err(Errors::PrerequisiteNotMet.into());
+    }
+    Ok(())
+}
+
+fn is_program_paused(config: &AccountInfo) -> Result<bool> {
+    if config.data_is_empty() {
+        return Ok(false);
+    }
+    Ok(ProgramConfig::try_deserialize(&mut &config.data.borrow()[..])?.paused)
+}
+
+/// The config's `max_polls_per_voter`; 0, no cap, while there is no config.
+fn max_polls_per_voter(config: &AccountInfo) -> Result<u64> {
+    if config.data_is_empty() {
+        return Ok(0);
+    }
+    Ok(ProgramConfig::try_deserialize(&mut &config.data.borrow()[..])?.max_polls_per_voter)
+}
+
+/// Checks a poll's `poll_start`/`poll_end` pair against the clock.
+fn validate_schedule(poll_id: u64, poll_start: u64, poll_end: u64, now: u64) -> Result<()> {
+    if !is_valid_timestamp(poll_start) {
+        msg!("Invalid poll_start {} for poll {}", poll_start, poll_id);
+        return Err(Errors::InvalidTimestamp.into());
+    }
+
+    if poll_end <= now {
+        msg!("poll_end {} is not after the current time {}", poll_end, now);
+        return Err(Errors::PollEndInThePast.into());
+    }
+
+    if poll_start >= poll_end {
+        msg!("poll_start {} is not before poll_end {}", poll_start, poll_end);
+        return Err(Errors::InvalidEndDate.into());
+    }
+    Ok(())
+}
+
+/// Credits it costs to go from `held` to `held + votes` votes for one candidate,
+/// i.e. `(held + votes)² - held²`. `None` on overflow.
+pub fn quadratic_cost(held: u64, votes: u64) -> Option<u64> {
+    let total = held.checked_add(votes)?;
+    total.checked_mul(total)?.checked_sub(held * held)
+}
+
+/// The instruction a ballot is cast through, as far as [`ballot_blocker`] cares:
+/// `gate_mint` polls only take `TokenTier` ballots, and other polls only
+/// `Standard` ones.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BallotMode {
+    Standard,
+    TokenTier,
+}
+
+/// First reason `vote`, or `vote_by_token_tier` in `TokenTier` mode, would
+/// reject a ballot for `candidate` (`None` for an abstention) from the owner of
+/// `voter_record` at `now`. A voter without a
+/// record yet is a default record. Only `vote` declares a jurisdiction, so on
+/// polls with `allowed_jurisdictions` the other ballots need an earlier `vote`.
+fn ballot_blocker(
+    poll: &Poll,
+    candidate: Option<&Candidate>,
+    voter_record: &VoterRecord,
+    now: u64,
+    mode: BallotMode
+) -> Option<Errors> {
+    if let Some(blocker) = poll.voting_blocker(now) {
+        return Some(blocker);
+    }
+
+    let votes_per_voter = poll.settings.votes_per_voter;
+    if poll.settings.require_registration && !voter_record.registered {
+        Some(Errors::VoterNotRegistered)
+    } else if !poll.jurisdiction_allowed(voter_record.jurisdiction) {
+        Some(Errors::WrongJurisdiction)
+    } else if votes_per_voter > 0 && voter_record.votes_used >= votes_per_voter {
+        Some(Errors::VoteBudgetExhausted)
+    } else if let Some(blocker) = candidate.and_then(|candidate| poll.candidate_blocker(candidate)) {
+        Some(blocker)
+    } else if poll.settings.anonymous
+        || poll.settings.require_identity
+        || poll.settings.credits_per_voter > 0
+        || poll.settings.gate_mint.is_some() != (mode == BallotMode::TokenTier)
+    {
+        Some(Errors::VotingModeMismatch)
+    } else if voter_record.has_pending_vote() {
+        Some(Errors::PendingVoteExists)
+    } else {
+        None
+    }
+}
+
+/// Rejects an existing voter record that belongs to another poll. Seeds already
+/// include the poll id, so this only trips if a seed change ever lets records be
+/// shared across polls. A freshly created record has no poll yet.
+fn ensure_record_matches_poll(voter_record: &VoterRecord, poll: &Pubkey) -> Result<()> {
+    if voter_record.poll != Pubkey::default() {
+        require_keys_eq!(voter_record.poll, *poll, Errors::VoterPollMismatch);
+    }
+    Ok(())
+}
+
+/// Rejects the all-zero pubkey as a signer. The runtime can't produce a signature
+/// for it, so this only guards against CPI or tooling edge cases, but it keeps the
+/// invariant explicit for audits.
+fn require_valid_signer(signer: &Pubkey) -> Result<()> {
+    if *signer == Pubkey::default() {
+        return Err(Errors::InvalidSigner.into());
+    }
+    Ok(())
+}
+
+/// Ballot text may contain any printable text and newlines, but no other ASCII
+/// control characters (NUL, tabs, escapes, ...) that could break rendering.
+fn is_renderable_description(description: &str) -> bool {
+    description.chars().all(|c| c == '\n' || !c.is_ascii_control())
+}
+
+/// A vote count stored as two `u64` words.
+pub fn wide_votes(lo: u64, hi: u64) -> u128 {
+    ((hi as u128) << 64) | lo as u128
+}
+
+/// Stores `value` across `lo` and `hi`. Without `wide` it must fit in `lo`, as
+/// on polls that aren't `high_precision`.
+fn set_wide_votes(lo: &mut u64, hi: &mut u64, value: u128, wide: bool) -> Result<()> {
+    if !wide && value > u64::MAX as u128 {
+        return Err(Errors::VoteOverflow.into());
+    }
+    *lo = value as u64;
+    *hi = (value >> 64) as u64;
+    Ok(())
+}
+
+/// Seed name of the candidate holding reserved slot `slot`, e.g. `#0`.
+pub fn slot_seed_name(slot: u64) -> String {
+    format!("{}{}", SLOT_SEED_PREFIX, slot)
+}
+
+fn is_valid_timestamp(timestamp: u64) -> bool {
+    (MIN_UNIX_TIMESTAMP..=MAX_UNIX_TIMESTAMP).contains(&timestamp)
+}
+
+/// Whether a poll created at `now` and opening at `poll_start` gives voters
+/// `MIN_NOTICE_SECONDS` of notice.
+fn has_notice(poll_start: u64, now: u64) -> bool {
+    poll_start >= now.saturating_add(MIN_NOTICE_SECONDS)
+}
+
+/// Creates the program-owned PDA `target` with `space` bytes, paid by `payer`,
+/// the way Anchor's `init` does. `create_account` fails on an address that
+/// already holds lamports, so anyone could block a predictable PDA by sending
+/// it one; such an account is topped up to rent exemption, then allocated and
+/// assigned instead.
+fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    target: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    signer_seeds: &[&[u8]]
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let lamports = target.lamports();
+    if lamports == 0 {
+        return create_account(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                CreateAccount { from: payer.clone(), to: target.clone() },
+                &[signer_seeds],
+            ),
+            rent,
+            space as u64,
+            &crate::ID,
+        );
+    }
+
+    let top_up = rent.saturating_sub(lamports);
+    if top_up > 0 {
+        transfer(
+            CpiContext::new(system_program.clone(), Transfer { from: payer.clone(), to: target.clone() }),
+            top_up,
+        )?;
+    }
+    allocate(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            Allocate { account_to_allocate: target.clone() },
+            &[signer_seeds],
+        ),
+        space as u64,
+    )?;
+    assign(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            Assign { account_to_assign: target.clone() },
+            &[signer_seeds],
+        ),
+        &crate::ID,
+    )
+}
+
+/// Loads the program account `T` at `info`, the PDA of `seeds`, creating it
+/// like `init_if_needed` would when it doesn't exist yet. For instructions that
+/// take per-poll accounts as remaining accounts, such as `vote_many`; the caller
+/// writes the account back.
+fn load_or_create_pda<'info, T: AccountDeserialize + Default>(
+    payer: &AccountInfo<'info>,
+    info: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    seeds: &[&[u8]]
+) -> Result<T> {
+    let (expected, bump) = Pubkey::find_program_address(seeds, &crate::ID);
+    if info.key() != expected {
+        return Err(Errors::VoterPollMismatch.into());
+    }
+    if info.owner == &crate::ID {
+        return T::try_deserialize(&mut &info.data.borrow()[..]);
+    }
+    let bump = [bump];
+    let signer_seeds: Vec<&[u8]> = seeds.iter().copied().chain([bump.as_slice()]).collect();
+    create_pda_account(payer, info, system_program, space, &signer_seeds)?;
+    Ok(T::default())
+}
+
+/// Creates the `poll` candidate PDA `info` for `candidate_name`, paid by
+/// `authority`, and links it into the poll's candidate list. `candidate` supplies
+/// everything but the name, poll and list link.
+fn create_candidate_account<'info>(
+    poll: &mut Account<'info, Poll>,
+    info: &AccountInfo<'info>,
+    authority: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    candidate_name: &str,
+    candidate: Candidate
+) -> Result<()> {
+    let candidate_name = poll.new_candidate_seed_name(candidate_name)?;
+
+    let (expected, bump) = poll.candidate_address(&candidate_name);
+    if info.key() != expected {
+        return Err(Errors::CandidatePollMismatch.into());
+    }
+
+    let space = Candidate::space(MAX_CANDIDATE_NAME_LEN, MAX_DISPLAY_NAME_LEN);
+    let poll_id = poll.poll_id.to_le_bytes();
+    create_pda_account(
+        &authority.to_account_info(),
+        info,
+        &system_program.to_account_info(),
+        space,
+        &[poll_id.as_ref(), poll.candidate_seed_separator(), candidate_name.as_bytes(), &[bump]],
+    )?;
+
+    let candidate = Candidate {
+        candidate_name,
+        poll: poll.key(),
+        next_candidate: poll.candidate_head,
+        candidate_index: poll.candidate_amount,
+        ..candidate
+    };
+    candidate.try_serialize(&mut &mut info.data.borrow_mut()[..])?;
+    poll.candidate_amount += 1;
+    poll.candidate_head = Some(info.key());
+    Ok(())
+}
+
+/// Index of the voter `sortition_select` picks among `voters` records sorted by
+/// voter pubkey.
+fn sortition_index(seed: &[u8; 32], poll_id: u64, voters: usize) -> usize {
+    let digest = hashv(&[seed, &poll_id.to_le_bytes()]).to_bytes();
+    let draw = u64::from_le_bytes(digest[..8].try_into().unwrap());
+    (draw % voters as u64) as usize
+}
+
+/// Deserializes the poll's candidates from `accounts`, requiring the complete,
+/// duplicate-free set of candidate PDAs belonging to `poll`.
+fn load_candidates(poll: &Account<Poll>, accounts: &[AccountInfo]) -> Result<Vec<(Pubkey, Candidate)>> {
+    if accounts.len() as u64 != poll.candidate_amount {
+        return Err(Errors::CandidateCountMismatch.into());
+    }
+
+    let mut candidates: Vec<(Pubkey, Candidate)> = Vec::with_capacity(accounts.len());
+    for info in accounts {
+        let candidate = load_candidate(poll, info)?;
+        if candidates.iter().any(|(key, _)| *key == info.key()) {
+            return Err(Errors::CandidateCountMismatch.into());
+        }
+        candidates.push((info.key(), candidate));
+    }
+    Ok(candidates)
+}
+
+/// Deserializes a single candidate, checking it is the PDA of one of `poll`'s candidates.
+fn load_candidate(poll: &Account<Poll>, info: &AccountInfo) -> Result<Candidate> {
+    if info.owner != &crate::ID {
+        return Err(Errors::CandidatePollMismatch.into());
+    }
+    let candidate = Candidate::try_deserialize(&mut &info.data.borrow()[..])?;
+
+    let (expected, _) = poll.candidate_address(&candidate.candidate_name);
+    if info.key() != expected || candidate.poll != poll.key() {
+        return Err(Errors::CandidatePollMismatch.into());
+    }
+    Ok(candidate)
+}
+
+/// Picks the candidate with the most votes, settling ties with the poll's
+/// `tie_break` strategy. Tied candidates are sorted by pubkey first so the result
+/// doesn't depend on account order. Returns `None` if no votes were cast, the
+/// leader falls short of `min_winning_votes` or of the majority `win_rule` asks
+/// for, or turnout misses the quorum.
+/// Emits `TieDetected` when more than one candidate shares the lead.
+fn tally_winner(
+    poll: &Poll,
+    candidates: &[(Pubkey, Candidate)],
+    slot_hash: Option<&[u8; 32]>
+) -> Result<Option<Pubkey>> {
+    let top_votes = candidates
+        .iter()
+        .map(|(_, candidate)| candidate.ranked_votes())
+        .max()
+        .unwrap_or(0);
+    // thresholds apply to votes as cast, not as endorsed
+    let leader_votes = candidates
+        .iter()
+        .filter(|(_, candidate)| candidate.ranked_votes() == top_votes)
+        .map(|(_, candidate)| candidate.votes())
+        .max()
+        .unwrap_or(0);
+
+    if top_votes == 0 || leader_votes < poll.settings.min_winning_votes as u128 {
+        return Ok(None);
+    }
+
+    if !poll.quorum_met() {
+        msg!(
+            "Poll {} turnout {} is below {} bps of {} eligible",
+            poll.poll_id,
+            poll.total_votes,
+            poll.settings.quorum_bps,
+            poll.settings.total_eligible_weight
+        );
+        return Ok(None);
+    }
+
+    if !poll.win_rule_met(leader_votes) {
+        msg!("Poll {} leader's {} votes are not a majority of {}", poll.poll_id, leader_votes, poll.total_votes_wide());
+        return Ok(None);
+    }
+
+    let mut tied: Vec<Pubkey> = candidates
+        .iter()
+        .filter(|(_, candidate)| candidate.ranked_votes() == top_votes)
+        .map(|(key, _)| *key)
+        .collect();
+    tied.sort();
+    if tied.len() > 1 {
+        emit!(TieDetected {
+            poll_id: poll.poll_id,
+            tied_candidates: tied.clone(),
+            vote_count: top_votes,
+        });
+    }
+
+    let index = match poll.settings.tie_break {
+        _ if tied.len() == 1 => 0,
+        TIE_BREAK_LOWEST_PUBKEY => 0,
+        TIE_BREAK_SLOT_HASH => {
+            let slot_hash = slot_hash.ok_or(Errors::MissingSlotHashes)?;
+            slot_hash_index(slot_hash, tied.len())
+        }
+        _ => return Err(Errors::InvalidTieBreak.into()),
+    };
+    Ok(Some(tied[index]))
+}
+
+/// The leader, the runner-up, and the leader's lead over the runner-up in votes
+/// and in basis points of `total_votes`. Candidates with equal votes rank by
+/// lowest pubkey, so a tie for first has a margin of 0, and so does a single
+/// candidate, which has no runner-up.
+fn margin_of_victory(
+    candidates: &[(Pubkey, Candidate)],
+    total_votes: u128
+) -> (Option<Pubkey>, Option<Pubkey>, u128, u64) {
+    let mut ranked: Vec<(Pubkey, u128)> = candidates
+        .iter()
+        .map(|(key, candidate)| (*key, candidate.votes()))
+        .collect();
+    ranked.sort_by(|(a_key, a), (b_key, b)| b.cmp(a).then(a_key.cmp(b_key)));
+
+    let (Some(&(winner, top)), Some(&(runner_up, second))) = (ranked.first(), ranked.get(1)) else {
+        return (ranked.first().map(|(key, _)| *key), None, 0, 0);
+    };
+    let margin = top - second;
+    let margin_bps = margin
+        .saturating_mul(FULL_VOTE_WEIGHT_BPS as u128)
+        .checked_div(total_votes)
+        .unwrap_or(0) as u64;
+    (Some(winner), Some(runner_up), margin, margin_bps)
+}
+
+/// Splits off the candidates under the poll's `results_min_votes`, returning
+/// the rest and the votes of those left out.
+fn split_minor_candidates(
+    poll: &Poll,
+    candidates: Vec<(Pubkey, Candidate)>
+) -> (Vec<(Pubkey, Candidate)>, u64) {
+    let min_votes = poll.settings.results_min_votes;
+    let (shown, minor): (Vec<_>, Vec<_>) = candidates
+        .into_iter()
+        .partition(|(_, candidate)| candidate.candidate_votes >= min_votes);
+    (shown, minor.iter().map(|(_, candidate)| candidate.candidate_votes).sum())
+}
+
+/// Whether at least `min_contested_candidates` of the candidates received votes.
+fn is_contested(poll: &Poll, candidates: &[(Pubkey, Candidate)]) -> bool {
+    let with_votes = candidates
+        .iter()
+        .filter(|(_, candidate)| candidate.votes() > 0)
+        .count();
+    with_votes as u64 >= poll.settings.min_contested_candidates
+}
+
+/// Reduces a slot hash to an index into `count` tied candidates, using its first
+/// eight bytes as a little-endian integer.
+///
+/// The result is deterministic: anyone can recompute it from the slot hash, so the
+/// outcome is auditable. It is not unpredictable to everyone, though. The hash is
+/// known once the slot is produced, so whoever submits `finalize_poll` (the poll
+/// authority) can wait for a slot that favours their candidate, and a leader can
+/// influence the hash of the slot it produces. Use it to avoid the systematic bias
+/// of lowest-pubkey ties, not where a motivated authority must be kept out.
+fn slot_hash_index(slot_hash: &[u8; 32], count: usize) -> usize {
+    let mut seed = [0u8; 8];
+    seed.copy_from_slice(&slot_hash[..8]);
+    (u64::from_le_bytes(seed) % count as u64) as usize
+}
+
+/// Most recent hash in the `SlotHashes` sysvar. The sysvar is a vector of
+/// `(slot, hash)` pairs, newest first, behind a u64 length prefix.
+fn latest_slot_hash(sysvar: &AccountInfo) -> Result<[u8; 32]> {
+    let data = sysvar.try_borrow_data()?;
+    let entries = data
+        .get(..8)
+        .map(|len| u64::from_le_bytes(len.try_into().unwrap()))
+        .unwrap_or(0);
+
+    if entries == 0 || data.len() < 48 {
+        return Err(Errors::MissingSlotHashes.into());
+    }
+
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&data[16..48]);
+    Ok(hash)
+}
+
+/// Bucket width that spreads a poll from `poll_start` to `poll_end` over
+/// `VOTE_BUCKETS` buckets, but never narrower than an hour.
+fn bucket_seconds(poll_start: u64, poll_end: u64) -> u64 {
+    poll_end
+        .saturating_sub(poll_start)
+        .div_ceil(VOTE_BUCKETS as u64)
+        .max(MIN_BUCKET_SECONDS)
+}
+
+/// Bucket for a vote cast at `voted_at`, counted in `bucket_seconds` windows from
+/// `poll_start` and clamped to the last bucket. Votes stamped before the start go
+/// in bucket 0. A width of 0 means hourly buckets.
+fn vote_bucket(poll_start: u64, bucket_seconds: u64, voted_at: i64) -> usize {
+    let elapsed = (voted_at.max(0) as u64).saturating_sub(poll_start);
+    ((elapsed / bucket_seconds.max(MIN_BUCKET_SECONDS)) as usize).min(VOTE_BUCKETS - 1)
+}
+
+/// Weight of a vote cast at `voted_at`: `FULL_VOTE_WEIGHT_BPS` at the start, minus
+/// `decay_per_hour_bps` for every full hour elapsed, floored at `MIN_VOTE_WEIGHT_BPS`.
+fn vote_weight_bps(poll_start: u64, voted_at: i64, decay_per_hour_bps: u64) -> u64 {
+    let hours = (voted_at.max(0) as u64).saturating_sub(poll_start) / 3600;
+    FULL_VOTE_WEIGHT_BPS
+        .saturating_sub(hours.saturating_mul(decay_per_hour_bps))
+        .max(MIN_VOTE_WEIGHT_BPS)
+}
+
+/// Canonical, versionless encoding of a poll's results, little-endian throughout:
+///
+/// ```text
+/// poll_id:   u64
+/// count:     u32
+/// count × {  candidate: [u8; 32]   votes: u64  }
+/// ```
+///
+/// `tally` holds `(candidate_index, candidate, votes)`. Candidates are written in
+/// `candidate_index` order, so the bytes don't depend on account order. Any
+/// change to this layout breaks relayers and needs a new instruction.
+pub fn encode_results(poll_id: u64, tally: &[(u64, Pubkey, u64)]) -> Vec<u8> {
+    let mut sorted = tally.to_vec();
+    sorted.sort_by_key(|(index, _, _)| *index);
+
+    let mut results = Vec::with_capacity(12 + sorted.len() * 40);
+    results.extend_from_slice(&poll_id.to_le_bytes());
+    results.extend_from_slice(&(sorted.len() as u32).to_le_bytes());
+    for (_, candidate, votes) in sorted {
+        results.extend_from_slice(candidate.as_ref());
+        results.extend_from_slice(&votes.to_le_bytes());
+    }
+    results
+}
+
+/// Results as CSV lines from `(candidate_index, candidate, votes)`: the header
+/// `candidate,index,votes`, then one `<candidate pubkey>,<candidate_index>,<votes>`
+/// row per candidate, sorted by `candidate_index`. Votes are the full tally in
+/// decimal. Base58 pubkeys never contain commas, so no field is quoted. Log
+/// scrapers depend on this layout; don't change it.
+pub fn results_csv(tally: &[(u64, Pubkey, u128)]) -> Vec<String> {
+    let mut sorted = tally.to_vec();
+    sorted.sort_by_key(|(index, _, _)| *index);
+
+    let mut lines = Vec::with_capacity(sorted.len() + 1);
+    lines.push("candidate,index,votes".to_string());
+    for (index, candidate, votes) in sorted {
+        lines.push(format!("{},{},{}", candidate, index, votes));
+    }
+    lines
+}
+
+/// Merkle root over a poll's results, given as `(candidate_index, candidate,
+/// votes)`. Leaves are `sha256(0x00 || candidate || votes_le)` in
+/// `candidate_index` order; each parent is `sha256(0x01 || min(a, b) || max(a, b))`,
+/// so proofs need no left/right flags. An odd node out is carried up to the next
+/// level unchanged. The domain bytes keep a leaf from being passed off as an
+/// inner node.
+pub fn results_root(tally: &[(u64, Pubkey, u64)]) -> [u8; 32] {
+    let mut sorted = tally.to_vec();
+    sorted.sort_by_key(|(index, _, _)| *index);
+
+    let mut level: Vec<[u8; 32]> = sorted
+        .iter()
+        .map(|(_, candidate, votes)| results_leaf(candidate, *votes))
+        .collect();
+    if level.is_empty() {
+        return [0u8; 32];
+    }
+
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => merkle_parent(left, right),
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    level[0]
+}
+
+/// The `results_root` input for a poll's full candidate set.
+fn results_tree_tally(candidates: &[(Pubkey, Candidate)]) -> Vec<(u64, Pubkey, u64)> {
+    candidates
+        .iter()
+        .map(|(key, candidate)| (candidate.candidate_index, *key, candidate.candidate_votes))
+        .collect()
+}
+
+pub fn results_leaf(candidate: &Pubkey, votes: u64) -> [u8; 32] {
+    hashv(&[&[0u8], candidate.as_ref(), &votes.to_le_bytes()]).to_bytes()
+}
+
+fn merkle_parent(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[&[1u8], low, high]).to_bytes()
+}
+
+fn verify_merkle_proof(leaf: [u8; 32], proof: &[[u8; 32]], root: &[u8; 32]) -> bool {
+    proof
+        .iter()
+        .fold(leaf, |node, sibling| merkle_parent(&node, sibling))
+        == *root
+}
+
+/// Commitment to a ballot: `sha256` over each candidate's pubkey, display name
+/// length (u32, little-endian) and display name, in candidate pubkey order. The
+/// pubkey already commits to the seed name, so the display name is what a
+/// mutable ballot could still change.
+pub fn candidate_set_hash(candidates: &[(Pubkey, Candidate)]) -> [u8; 32] {
+    let mut sorted: Vec<&(Pubkey, Candidate)> = candidates.iter().collect();
+    sorted.sort_by_key(|(key, _)| *key);
+
+    let mut encoded = Vec::new();
+    for (key, candidate) in sorted {
+        encoded.extend_from_slice(key.as_ref());
+        encoded.extend_from_slice(&(candidate.display_name.len() as u32).to_le_bytes());
+        encoded.extend_from_slice(candidate.display_name.as_bytes());
+    }
+    hashv(&[&encoded]).to_bytes()
+}
+
+/// Receipt hash binding a vote to its poll, candidate, voter and time:
+/// `sha256(poll_id_le || candidate || voter || voted_at_le)`.
+pub fn receipt_hash(poll_id: u64, candidate: &Pubkey, voter: &Pubkey, voted_at: i64) -> [u8; 32] {
+    hashv(&[
+        &poll_id.to_le_bytes(),
+        candidate.as_ref(),
+        voter.as_ref(),
+        &voted_at.to_le_bytes(),
+    ])
+    .to_bytes()
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct Vote<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+      mut,
+      seeds = [poll_id.to_le_bytes().as_ref()],
+      bump,
+      constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(
+      mut,
+      seeds = [
+        poll_id.to_le_bytes().as_ref(),
+        poll.candidate_seed_separator(),
+        poll.candidate_seed_name(&candidate_name).as_bytes()
+      ],
+      bump
+    )]
+    pub candidate: Account<'info, Candidate>,
+
+    #[account(
+      init_if_needed,
+      payer = signer,
+      space = 8 + VoterRecord::INIT_SPACE,
+      seeds = [b"voter", poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+      bump
+    )]
+    pub voter_record: Account<'info, VoterRecord>,
+
+    #[account(
+      init_if_needed,
+      payer = signer,
+      space = 8 + VoterAllocation::INIT_SPACE,
+      seeds = [b"allocation", poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+      bump
+    )]
+    pub voter_allocation: Account<'info, VoterAllocation>,
+
+    #[account(
+      init_if_needed,
+      payer = signer,
+      space = 8 + VoterIndex::INIT_SPACE,
+      seeds = [b"voter_index", signer.key().as_ref()],
+      bump
+    )]
+    pub voter_index: Account<'info, VoterIndex>,
+
+    #[account(
+      init_if_needed,
+      payer = signer,
+      space = 8 + TopK::INIT_SPACE,
+      seeds = [b"top_k", poll_id.to_le_bytes().as_ref()],
+      bump
+    )]
+    pub top_k: Account<'info, TopK>,
+
+    /// CHECK: the voter's `VoterAge`, which only exists once they have been
+    /// registered somewhere. Read only by polls with `min_account_age_slots`;
+    /// see `ensure_account_age`.
+    #[account(seeds = [b"voter_age", signer.key().as_ref()], bump)]
+    pub voter_age: UncheckedAccount<'info>,
+
+    /// The poll named by `depends_on`; only needed on dependent polls. See
+    /// `ensure_prerequisite_met`.
+    pub prerequisite_poll: Option<Account<'info, Poll>>,
+
+    /// CHECK: the signer's gate token, parsed by hand in `token_tier_weight`.
+    /// Only read by `vote_by_token_tier`.
+    #[account(owner = TOKEN_2022_PROGRAM_ID @ Errors::InvalidGateToken)]
+    pub token_account: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: the gate token's mint, parsed by hand in `token_tier_weight`.
+    /// Only read by `vote_by_token_tier`.
+    #[account(owner = TOKEN_2022_PROGRAM_ID @ Errors::InvalidGateToken)]
+    pub token_mint: Option<UncheckedAccount<'info>>,
+
+    /// The poll's `VoteLog`; only needed once `enable_vote_log` has run.
+    #[account(mut, has_one = poll @ Errors::VoteLogMissing)]
+    pub vote_log: Option<Account<'info, VoteLog>>,
+
+    /// Spends `token_mint` in this poll; only needed by `vote_by_token_tier`,
+    /// where `init` fails if the mint has already voted.
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + TokenBallot::INIT_SPACE,
+        seeds = [
+            b"token_ballot",
+            poll_id.to_le_bytes().as_ref(),
+            token_mint.as_ref().map(|mint| mint.key()).unwrap_or_default().as_ref()
+        ],
+        bump
+    )]
+    pub token_ballot: Option<Account<'info, TokenBallot>>,
+
+    #[account(
+      init_if_needed,
+      payer = signer,
+      space = 8 + VoterGlobalState::INIT_SPACE,
+      seeds = [b"voter_global", signer.key().as_ref()],
+      bump
+    )]
+    pub voter_global: Account<'info, VoterGlobalState>,
+
+    /// CHECK: the program config PDA, which may not exist yet. It is only
+    /// deserialized when initialized; see `ensure_not_paused`.
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct VoteWithReceipt<'info> {
+    pub vote: Vote<'info>,
+
+    /// The signer's receipt in this poll; `init` fails if they already have one.
+    #[account(
+        init,
+        payer = vote.signer,
+        space = 8 + ReceiptRecord::INIT_SPACE,
+        seeds = [b"receipt", poll_id.to_le_bytes().as_ref(), vote.signer.key().as_ref()],
+        bump
+    )]
+    pub receipt: Account<'info, ReceiptRecord>,
+
+    /// CHECK: the receipt mint, parsed by hand in `is_receipt_mint`.
+    #[account(mut, owner = TOKEN_2022_PROGRAM_ID @ Errors::InvalidReceiptMint)]
+    pub receipt_mint: UncheckedAccount<'info>,
+
+    /// CHECK: the signer's token account for `receipt_mint`, parsed by hand in
+    /// the handler.
+    #[account(mut, owner = TOKEN_2022_PROGRAM_ID @ Errors::InvalidReceiptAccount)]
+    pub receipt_token_account: UncheckedAccount<'info>,
+
+    /// CHECK: the poll's `receipt_collection` group mint, checked in the handler.
+    #[account(mut, owner = TOKEN_2022_PROGRAM_ID @ Errors::ReceiptCollectionMismatch)]
+    pub receipt_collection: UncheckedAccount<'info>,
+
+    /// CHECK: holds no data; the mint authority of the poll's receipt mints and
+    /// update authority of its collection, which signs `mint_receipt`.
+    #[account(seeds = [b"receipt_authority", poll_id.to_le_bytes().as_ref()], bump)]
+    pub receipt_authority: UncheckedAccount<'info>,
+
+    /// CHECK: the Token-2022 program `mint_receipt` calls.
+    #[account(address = TOKEN_2022_PROGRAM_ID)]
+    pub token_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct CancelPendingVote<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+      mut,
+      seeds = [b"voter", poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+      bump,
+      constraint = voter_record.has_pending_vote() @ Errors::NoPendingVote
+    )]
+    pub voter_record: Account<'info, VoterRecord>,
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64, nullifier: [u8; 32])]
+pub struct VoteAnonymous<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+      mut,
+      seeds = [poll_id.to_le_bytes().as_ref()],
+      bump,
+      constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(
+      mut,
+      seeds = [
+        poll_id.to_le_bytes().as_ref(),
+        poll.candidate_seed_separator(),
+        poll.candidate_seed_name(&candidate_name).as_bytes()
+      ],
+      bump
+    )]
+    pub candidate: Account<'info, Candidate>,
+
+    // `init` fails if the nullifier was already used in this poll
+    #[account(
+      init,
+      payer = payer,
+      space = 8 + NullifierRecord::INIT_SPACE,
+      seeds = [b"nullifier", poll_id.to_le_bytes().as_ref(), nullifier.as_ref()],
+      bump
+    )]
+    pub nullifier_record: Account<'info, NullifierRecord>,
+
+    /// CHECK: the program config PDA, which may not exist yet. It is only
+    /// deserialized when initialized; see `ensure_not_paused`.
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(identity_hash: [u8; 32], wallet: Pubkey)]
+pub struct RegisterIdentity<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump,
+        has_one = admin @ Errors::NotAdmin,
+        constraint = config.initialized @ Errors::NotInitialized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    #[account(
+      init,
+      payer = admin,
+      space = 8 + IdentityRegistry::INIT_SPACE,
+      seeds = [b"identity", wallet.as_ref()],
+      bump
+    )]
+    pub identity: Account<'info, IdentityRegistry>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct VoteWithIdentity<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+      mut,
+      seeds = [poll_id.to_le_bytes().as_ref()],
+      bump,
+      constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(
+      mut,
+      seeds = [
+        poll_id.to_le_bytes().as_ref(),
+        poll.candidate_seed_separator(),
+        poll.candidate_seed_name(&candidate_name).as_bytes()
+      ],
+      bump
+    )]
+    pub candidate: Account<'info, Candidate>,
+
+    #[account(seeds = [b"identity", signer.key().as_ref()], bump)]
+    pub identity: Account<'info, IdentityRegistry>,
+
+    #[account(
+      init_if_needed,
+      payer = signer,
+      space = 8 + VoterRecord::INIT_SPACE,
+      seeds = [b"voter", poll_id.to_le_bytes().as_ref(), identity.identity_hash.as_ref()],
+      bump
+    )]
+    pub voter_record: Account<'info, VoterRecord>,
+
+    #[account(
+      init_if_needed,
+      payer = signer,
+      space = 8 + TopK::INIT_SPACE,
+      seeds = [b"top_k", poll_id.to_le_bytes().as_ref()],
+      bump
+    )]
+    pub top_k: Account<'info, TopK>,
+
+    #[account(
+      init_if_needed,
+      payer = signer,
+      space = 8 + VoterGlobalState::INIT_SPACE,
+      seeds = [b"voter_global", signer.key().as_ref()],
+      bump
+    )]
+    pub voter_global: Account<'info, VoterGlobalState>,
+
+    /// CHECK: the program config PDA, which may not exist yet. It is only
+    /// deserialized when initialized; see `ensure_not_paused`.
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VoteMany<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+      init_if_needed,
+      payer = signer,
+      space = 8 + VoterIndex::INIT_SPACE,
+      seeds = [b"voter_index", signer.key().as_ref()],
+      bump
+    )]
+    pub voter_index: Account<'info, VoterIndex>,
+
+    #[account(
+      init_if_needed,
+      payer = signer,
+      space = 8 + VoterGlobalState::INIT_SPACE,
+      seeds = [b"voter_global", signer.key().as_ref()],
+      bump
+    )]
+    pub voter_global: Account<'info, VoterGlobalState>,
+
+    /// CHECK: the program config PDA, which may not exist yet. It is only
+    /// deserialized when initialized; see `ensure_not_paused`.
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// One entry of a `vote_many` batch.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BatchVote {
+    pub poll_id: u64,
+    pub candidate_name: String,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct VoteAbstain<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+      mut,
+      seeds = [poll_id.to_le_bytes().as_ref()],
+      bump,
+      constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(
+      init_if_needed,
+      payer = signer,
+      space = 8 + VoterRecord::INIT_SPACE,
+      seeds = [b"voter", poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+      bump
+    )]
+    pub voter_record: Account<'info, VoterRecord>,
+
+    #[account(
+      init_if_needed,
+      payer = signer,
+      space = 8 + VoterGlobalState::INIT_SPACE,
+      seeds = [b"voter_global", signer.key().as_ref()],
+      bump
+    )]
+    pub voter_global: Account<'info, VoterGlobalState>,
+
+    /// CHECK: the program config PDA, which may not exist yet. It is only
+    /// deserialized when initialized; see `ensure_not_paused`.
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+
+    /// CHECK: the voter's `VoterAge`, read only by polls with
+    /// `min_account_age_slots`; see `ensure_account_age`.
+    #[account(seeds = [b"voter_age", signer.key().as_ref()], bump)]
+    pub voter_age: UncheckedAccount<'info>,
+
+    /// The poll named by `depends_on`; only needed on dependent polls. See
+    /// `ensure_prerequisite_met`.
+    pub prerequisite_poll: Option<Account<'info, Poll>>,
+
+    // read by `auto_finalize` when the ballot completes turnout
+    #[account(
+      init_if_needed,
+      payer = signer,
+      space = 8 + TopK::INIT_SPACE,
+      seeds = [b"top_k", poll_id.to_le_bytes().as_ref()],
+      bump
+    )]
+    pub top_k: Account<'info, TopK>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct SimulateVote<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+
+    #[account(
+      seeds = [
+        poll_id.to_le_bytes().as_ref(),
+        poll.candidate_seed_separator(),
+        poll.candidate_seed_name(&candidate_name).as_bytes()
+      ],
+      bump
+    )]
+    pub candidate: Account<'info, Candidate>,
+
+    /// CHECK: the signer's voter record PDA, which may not exist yet. It is only
+    /// deserialized when initialized.
+    #[account(
+      seeds = [b"voter", poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+      bump
+    )]
+    pub voter_record: UncheckedAccount<'info>,
+
+    /// CHECK: the program config PDA, which may not exist yet. It is only
+    /// deserialized when initialized; see `ensure_not_paused`.
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+
+    /// CHECK: the voter's `VoterAge`, as for `vote`.
+    #[account(seeds = [b"voter_age", signer.key().as_ref()], bump)]
+    pub voter_age: UncheckedAccount<'info>,
+
+    /// The poll named by `depends_on`, as for `vote`.
+    pub prerequisite_poll: Option<Account<'info, Poll>>,
+
+    /// The poll's `VoteLog`, as for `vote`.
+    #[account(has_one = poll @ Errors::VoteLogMissing)]
+    pub vote_log: Option<Account<'info, VoteLog>>,
+}
+
+#[account]
+#[derive(InitSpace, Default)]
+pub struct VoterRecord {
+    pub poll: Pubkey,
+    pub voter: Pubkey,
+    pub candidate: Pubkey,
+    pub voted_at: i64,
+    pub receipt_hash: [u8; 32],
+    pub votes_used: u64,
+    /// Set by `register_voter`; records created on the fly by `vote` aren't registered.
+    pub registered: bool,
+    /// Whether `candidate` has been counted. On `require_confirmation` polls a vote
+    /// stays pending until `confirm_vote`.
+    pub confirmed: bool,
+    /// Credits spent through `vote_quadratic`, out of `credits_per_voter`.
+    pub credits_used: u64,
+    /// Layout version, see [`VOTER_RECORD_VERSION`].
+    pub version: u8,
+    /// What the latest counted ballot was for.
+    pub choice: BallotChoice,
+    /// Poll round the ballot fields (`candidate` through `choice`) belong to; see
+    /// [`VoterRecord::start_round`].
+    pub round: u64,
+    /// Where the voter declared they vote from in `vote`; kept across rounds.
+    pub jurisdiction: Option<u32>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default, InitSpace)]
+pub enum BallotChoice {
+    /// No counted ballot yet.
+    #[default]
+    None,
+    Candidate,
+    Abstain,
+    Blank,
+}
+
+impl VoterRecord {
+    pub fn has_pending_vote(&self) -> bool {
+        !self.confirmed && self.candidate != Pubkey::default()
+    }
+
+    /// Whether any ballot, counted or pending, has been cast with this record.
+    pub fn has_voted(&self) -> bool {
+        self.votes_used > 0 || self.choice != BallotChoice::None || self.credits_used > 0 || self.has_pending_vote()
+    }
+
+    /// Clears a ballot cast in an earlier round so the voter can vote again in
+    /// `round`. Registration and the declared jurisdiction carry over.
+    pub fn start_round(&mut self, round: u64) {
+        if self.round == round {
+            return;
+        }
+        *self = VoterRecord {
+            poll: self.poll,
+            voter: self.voter,
+            registered: self.registered,
+            version: self.version,
+            jurisdiction: self.jurisdiction,
+            round,
+            ..Default::default()
+        };
+    }
+}
+
+/// Marks a nullifier as spent in a poll. The seeds carry a `b"nullifier"` prefix
+/// so a nullifier can't collide with a `[poll_id, name]` candidate PDA.
+#[account]
+#[derive(InitSpace)]
+pub struct NullifierRecord {
+    pub poll: Pubkey,
+    pub nullifier: [u8; 32],
+}
+
+/// Marks a gate token mint as spent in a poll by `vote_by_token_tier`.
+#[account]
+#[derive(InitSpace)]
+pub struct TokenBallot {
+    pub poll: Pubkey,
+    pub mint: Pubkey,
+}
+
+/// The receipt token `vote_with_receipt` minted to a voter in a poll.
+#[account]
+#[derive(InitSpace)]
+pub struct ReceiptRecord {
+    pub poll: Pubkey,
+    pub voter: Pubkey,
+    pub mint: Pubkey,
+}
+
+/// Per-voter breakdown of how many votes they placed on each candidate. Entries
+/// only ever grow alongside the matching `candidate_votes`.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct VoterAllocation {
+    #[max_len(MAX_ALLOCATION_ENTRIES)]
+    pub allocations: Vec<Allocation>,
+    /// Poll round `allocations` were made in.
+    pub round: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct Allocation {
+    pub candidate: Pubkey,
+    pub votes: u64,
+}
+
+impl VoterAllocation {
+    /// Drops allocations made in an earlier round; see [`VoterRecord::start_round`].
+    pub fn start_round(&mut self, round: u64) {
+        if self.round != round {
+            self.allocations.clear();
+            self.round = round;
+        }
+    }
+
+    pub fn add(&mut self, candidate: Pubkey, votes: u64) -> Result<()> {
+        if let Some(entry) = self.allocations.iter_mut().find(|entry| entry.candidate == candidate) {
+            entry.votes += votes;
+            return Ok(());
+        }
+        if self.allocations.len() >= MAX_ALLOCATION_ENTRIES {
+            return Err(Errors::AllocationFull.into());
+        }
+        self.allocations.push(Allocation { candidate, votes });
+        Ok(())
+    }
+}
+
+/// Slot a wallet was first registered as a voter in any poll. Solana doesn't
+/// expose account age, so this stands in for it on `min_account_age_slots` polls.
+#[account]
+#[derive(InitSpace)]
+pub struct VoterAge {
+    pub voter: Pubkey,
+    pub first_seen_slot: u64,
+}
+
+impl VoterAge {
+    pub fn old_enough(&self, min_age_slots: u64, slot: u64) -> bool {
+        slot.saturating_sub(self.first_seen_slot) >= min_age_slots
+    }
+}
+
+/// Every poll a voter has cast a counted vote in, oldest first, capped at
+/// `MAX_VOTER_INDEX_POLLS`.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct VoterIndex {
+    #[max_len(MAX_VOTER_INDEX_POLLS)]
+    pub poll_ids: Vec<u64>,
+}
+
+impl VoterIndex {
+    /// Adds `poll_id` unless it's already listed or the index is full.
+    pub fn record(&mut self, poll_id: u64) {
+        if self.poll_ids.len() < MAX_VOTER_INDEX_POLLS && !self.poll_ids.contains(&poll_id) {
+            self.poll_ids.push(poll_id);
+        }
+    }
+}
+
+/// How many polls a voter has joined across the whole program, checked against
+/// `ProgramConfig::max_polls_per_voter`. A poll counts once the voter's first
+/// ballot in it is counted; each round of an `advance_round` poll counts anew.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct VoterGlobalState {
+    pub voter: Pubkey,
+    pub polls_voted: u64,
+}
+
+impl VoterGlobalState {
+    /// Counts another poll for `voter`, failing with `ParticipationCapReached`
+    /// once they are at `max_polls` (0 for no cap).
+    pub fn join_poll(&mut self, voter: Pubkey, max_polls: u64) -> Result<()> {
+        if max_polls > 0 && self.polls_voted >= max_polls {
+            msg!("{} has already voted in {} polls", voter, self.polls_voted);
+            return Err(Errors::ParticipationCapReached.into());
+        }
+        self.voter = voter;
+        self.polls_voted += 1;
+        Ok(())
+    }
+}
+
+/// A finalized poll's results in one account, for SDKs that would rather fetch
+/// and deserialize than parse events. Written by `finalize_poll`, also for polls
+/// that finalized themselves, and kept up to date by `adjust_votes`; candidates
+/// are in pubkey order.
+#[account]
+#[derive(InitSpace)]
+pub struct PollResults {
+    pub poll_id: u64,
+    pub total_votes: u64,
+    pub unique_voters: u64,
+    pub winner: Option<Pubkey>,
+    #[max_len(MAX_RESULTS_CANDIDATES)]
+    pub candidates: Vec<ResultEntry>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug, InitSpace)]
+pub struct ResultEntry {
+    pub candidate: Pubkey,
+    pub votes: u64,
+}
+
+impl PollResults {
+    /// The results of finalized `poll`, whose candidates are `candidates`.
+    pub fn tally(poll: &Poll, candidates: &[(Pubkey, Candidate)]) -> PollResults {
+        let mut tally: Vec<ResultEntry> = candidates
+            .iter()
+            .map(|(key, candidate)| ResultEntry { candidate: *key, votes: candidate.candidate_votes })
+            .collect();
+        tally.sort_by_key(|entry| entry.candidate);
+        PollResults {
+            poll_id: poll.poll_id,
+            total_votes: poll.total_votes,
+            unique_voters: poll.unique_voters,
+            winner: poll.winner,
+            candidates: tally,
+        }
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct CheckEligibility<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+
+    /// CHECK: the signer's voter record PDA, which may not exist yet. It is only
+    /// deserialized when initialized.
+    #[account(
+      seeds = [b"voter", poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+      bump
+    )]
+    pub voter_record: UncheckedAccount<'info>,
+
+    /// CHECK: the signer's `VoterAge`, which may not exist; see `ensure_account_age`.
+    #[account(seeds = [b"voter_age", signer.key().as_ref()], bump)]
+    pub voter_age: UncheckedAccount<'info>,
+
+    /// CHECK: the signer's `IdentityRegistry`, which may not exist. Only its
+    /// owner is checked.
+    #[account(seeds = [b"identity", signer.key().as_ref()], bump)]
+    pub identity: UncheckedAccount<'info>,
+
+    /// CHECK: the signer's gate token; its owner is checked in the handler so a
+    /// wrong account shows up as a reason code rather than a failed instruction.
+    pub token_account: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: the gate token's mint, checked like `token_account`.
+    pub token_mint: Option<UncheckedAccount<'info>>,
+}
+
+/// A wallet's link to an identity verified off-chain, set by the program admin.
+#[account]
+#[derive(InitSpace)]
+pub struct IdentityRegistry {
+    pub wallet: Pubkey,
+    pub identity_hash: [u8; 32],
+}
+
+/// Compact stream of the candidate votes counted on a `vote_log` poll, for
+/// provers that rebuild the tally from a bounded log instead of every account.
+/// It holds the latest `VOTE_LOG_ENTRIES`: once full, each new entry overwrites
+/// the oldest, at `entries[total_entries % VOTE_LOG_ENTRIES]`, so a prover
+/// reading `total_entries` knows how many it has missed. See [`VoteLog::ordered`].
+#[account]
+#[derive(InitSpace, Default)]
+pub struct VoteLog {
+    pub poll: Pubkey,
+    /// Entries ever appended, overwritten ones included.
+    pub total_entries: u64,
+    #[max_len(VOTE_LOG_ENTRIES)]
+    pub entries: Vec<VoteLogEntry>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub struct VoteLogEntry {
+    /// The candidate's `candidate_index`.
+    pub candidate_index: u16,
+    /// Votes the ballot counted for it.
+    pub weight: u32,
+}
+
+impl VoteLog {
+    pub fn append(&mut self, entry: VoteLogEntry) {
+        if self.entries.len() < VOTE_LOG_ENTRIES {
+            self.entries.push(entry);
+        } else {
+            self.entries[(self.total_entries % VOTE_LOG_ENTRIES as u64) as usize] = entry;
+        }
+        self.total_entries += 1;
+    }
+
+    /// The retained entries, oldest first.
+    pub fn ordered(&self) -> Vec<VoteLogEntry> {
+        let oldest = if self.entries.len() < VOTE_LOG_ENTRIES {
+            0
+        } else {
+            (self.total_entries % VOTE_LOG_ENTRIES as u64) as usize
+        };
+        let (newer, older) = self.entries.split_at(oldest);
+        older.iter().chain(newer).copied().collect()
+    }
+}
+
+/// The latest `MAX_AUDIT_ENTRIES` authority actions on a poll, oldest first.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct AuditLog {
+    pub poll: Pubkey,
+    #[max_len(MAX_AUDIT_ENTRIES)]
+    pub entries: Vec<AuditEntry>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, Debug, InitSpace)]
+pub struct AuditEntry {
+    /// One of the `AUDIT_*` constants.
+    pub action_code: u8,
+    pub actor: Pubkey,
+    pub timestamp: i64,
+}
+
+impl AuditLog {
+    /// Appends an entry, dropping the oldest one once the log is full.
+    pub fn record(&mut self, action_code: u8, actor: Pubkey, timestamp: i64) {
+        if self.entries.len() == MAX_AUDIT_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.entries.push(AuditEntry { action_code, actor, timestamp });
+    }
+}
+
+/// A poll's `TOP_K` most voted candidates, kept sorted by votes (most first,
+/// ties by lowest pubkey) as votes come in. Unused slots are default entries.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct TopK {
+    pub poll: Pubkey,
+    pub entries: [TopKEntry; TOP_K],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, Debug, InitSpace)]
+pub struct TopKEntry {
+    pub candidate: Pubkey,
+    pub votes: u64,
+    /// High 64 bits of the tally on `high_precision` polls; see
+    /// [`TopKEntry::votes_wide`].
+    pub votes_hi: u64,
+}
+
+impl TopKEntry {
+    /// The full tally, `votes_hi` included.
+    pub fn votes_wide(&self) -> u128 {
+        ((self.votes_hi as u128) << 64) | self.votes as u128
+    }
+
+    fn ranks_above(&self, other: &TopKEntry) -> bool {
+        let (votes, other_votes) = (self.votes_wide(), other.votes_wide());
+        votes > other_votes || (votes == other_votes && self.candidate < other.candidate)
+    }
+}
+
+impl TopK {
+    /// Updates `candidate`'s tally to `votes`, entering it in place of the last
+    /// entry if it now outranks it. Tallies only grow, so an entry only moves up.
+    pub fn record(&mut self, candidate: Pubkey, votes: u128) {
+        let entry = TopKEntry { candidate, votes: votes as u64, votes_hi: (votes >> 64) as u64 };
+        let mut index = match self.entries.iter().position(|e| e.candidate == candidate) {
+            Some(index) => index,
+            None if entry.ranks_above(&self.entries[TOP_K - 1]) => TOP_K - 1,
+            None => return,
+        };
+        self.entries[index] = entry;
+
+        while index > 0 && entry.ranks_above(&self.entries[index - 1]) {
+            self.entries.swap(index, index - 1);
+            index -= 1;
+        }
+    }
+
+    /// Ranks `candidates` from scratch, for when a tally has gone down.
+    pub fn rebuild(&mut self, candidates: &[(Pubkey, Candidate)]) {
+        self.entries = Default::default();
+        for (key, candidate) in candidates {
+            self.record(*key, candidate.votes());
+        }
+    }
+
+    /// The winner and `valid_result` that `finalize_poll` would record, if the
+    /// leaderboard alone decides them. It can't when a slot hash tie-break is
+    /// needed, on polls with an `endorser` (it holds raw tallies, not endorsed
+    /// ones), or when every slot has votes but `min_contested_candidates` asks
+    /// for more candidates than it tracks.
+    pub fn settled_result(&self, poll: &Poll) -> Option<(Option<Pubkey>, bool)> {
+        if poll.settings.endorser.is_some() {
+            return None;
+        }
+        let contested = self.entries.iter().filter(|entry| entry.votes_wide() > 0).count();
+        let valid_result = contested as u64 >= poll.settings.min_contested_candidates;
+        if !valid_result && contested == TOP_K {
+            return None;
+        }
+
+        let leader = self.entries[0];
+        let leader_votes = leader.votes_wide();
+        if leader_votes == 0
+            || leader_votes < poll.settings.min_winning_votes as u128
+            || !poll.quorum_met()
+            || !poll.win_rule_met(leader_votes)
+        {
+            return Some((None, valid_result));
+        }
+        if self.entries[1].votes_wide() == leader_votes && poll.settings.tie_break != TIE_BREAK_LOWEST_PUBKEY {
+            return None;
+        }
+        Some((Some(leader.candidate), valid_result))
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct InitializeCandidate<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = !poll.archived @ Errors::PollArchived
+      )]
+    pub poll: Account<'info, Poll>,
+
+    // `init_if_needed` rather than `init`, so a duplicate name reaches the handler
+    // and fails with `DuplicateCandidate` instead of "account already in use"
+    #[account(
+        init_if_needed,
+        payer = signer,
+        space = Candidate::space(MAX_CANDIDATE_NAME_LEN, MAX_DISPLAY_NAME_LEN),
+        seeds = [
+            poll_id.to_le_bytes().as_ref(),
+            poll.candidate_seed_separator(),
+            poll.candidate_seed_name(&candidate_name).as_bytes()
+        ],
+        bump
+    )]
+    pub candidate: Account<'info, Candidate>,
+    /// CHECK: the program config PDA, which may not exist yet. It is only
+    /// deserialized when initialized; see `ensure_not_paused`.
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+
+    /// The poll's `DisplayNameSet`; only needed on polls with
+    /// `require_unique_display_names`, which created it.
+    #[account(mut, has_one = poll @ Errors::DisplayNameSetMissing)]
+    pub display_names: Option<Account<'info, DisplayNameSet>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Hashes of the display names taken in a `require_unique_display_names` poll,
+/// so `initialize_candidate` can reject a duplicate without scanning candidates.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct DisplayNameSet {
+    pub poll: Pubkey,
+    #[max_len(MAX_DISPLAY_NAMES)]
+    pub hashes: Vec<[u8; 32]>,
+}
+
+impl DisplayNameSet {
+    pub fn insert(&mut self, display_name: &str) -> Result<()> {
+        let hash = hashv(&[display_name.as_bytes()]).to_bytes();
+        if self.hashes.contains(&hash) {
+            msg!("Display name {} is already taken", display_name);
+            return Err(Errors::DuplicateDisplayName.into());
+        }
+        if self.hashes.len() >= MAX_DISPLAY_NAMES {
+            return Err(Errors::TooManyDisplayNames.into());
+        }
+        self.hashes.push(hash);
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace, Default)]
+pub struct Candidate {
+    #[max_len(MAX_CANDIDATE_NAME_LEN)]
+    pub candidate_name: String,
+    pub candidate_votes: u64,
+    pub poll: Pubkey,
+    pub created_at: u64,
+    /// Sum of the candidate's vote weights in basis points; equals
+    /// `candidate_votes * 10_000` when the poll has no decay.
+    pub weighted_votes: u64,
+    /// Time of the first and latest counted vote; both stay 0 until the first vote.
+    pub first_vote_at: i64,
+    pub last_vote_at: i64,
+    /// Name clients show; the PDA stays keyed by the shorter `candidate_name`.
+    #[max_len(MAX_DISPLAY_NAME_LEN)]
+    pub display_name: String,
+    /// Who manages the candidate: its creator until `transfer_candidate_authority`.
+    pub candidate_authority: Pubkey,
+    /// The candidate added to the poll before this one; see `Poll::candidate_head`.
+    pub next_candidate: Option<Pubkey>,
+    /// Party or group the candidate runs for; empty for independents.
+    #[max_len(MAX_PARTY_NAME_LEN)]
+    pub party: String,
+    /// Set by the poll authority with `verify_candidate` once the candidate's
+    /// identity is confirmed, so clients can flag impersonators.
+    pub verified: bool,
+    /// A slot from `reserve_candidate_slot` that `fill_candidate_slot` hasn't
+    /// described yet; it can't receive votes.
+    pub unfilled: bool,
+    /// High 64 bits of the tally on `high_precision` polls, with
+    /// `candidate_votes` as the low 64; see [`Candidate::votes`]. Always 0 elsewhere.
+    pub candidate_votes_hi: u64,
+    /// Votes from the rounds before the poll's current `round`; see `advance_round`.
+    pub total_votes_all_rounds: u64,
+    /// Set by `endorse_candidate`; 0 while not endorsed, which ranks like 10_000.
+    pub endorsement_multiplier_bps: u64,
+    /// Position among the poll's candidates in creation order, from 0. Candidates
+    /// are never removed, so it stays unique; `VoteLog` entries refer to it.
+    pub candidate_index: u64,
+}
+
+impl Candidate {
+    /// Account size (discriminator included) for a candidate whose seed and
+    /// display names are `name_len` and `display_name_len` bytes.
+    pub const fn space(name_len: usize, display_name_len: usize) -> usize {
+        8 + Candidate::INIT_SPACE - MAX_CANDIDATE_NAME_LEN - MAX_DISPLAY_NAME_LEN
+            + name_len
+            + display_name_len
+    }
+
+    /// The full tally, including `candidate_votes_hi`.
+    pub fn votes(&self) -> u128 {
+        wide_votes(self.candidate_votes, self.candidate_votes_hi)
+    }
+
+    /// The tally `tally_winner` ranks by: the votes scaled by the candidate's
+    /// endorsement multiplier, if any.
+    pub fn ranked_votes(&self) -> u128 {
+        match self.endorsement_multiplier_bps {
+            0 => self.votes(),
+            multiplier_bps => self.votes() * multiplier_bps as u128 / FULL_VOTE_WEIGHT_BPS as u128,
+        }
+    }
+
+    /// Counts `votes` cast at `voted_at`, worth `weight_bps` in the weighted tally.
+    /// Only `high_precision` polls may carry the tally past `u64::MAX`.
+    pub fn record_vote(&mut self, voted_at: i64, votes: u64, weight_bps: u64, high_precision: bool) -> Result<()> {
+        let total = self.votes().checked_add(votes as u128).ok_or(Errors::VoteOverflow)?;
+        set_wide_votes(&mut self.candidate_votes, &mut self.candidate_votes_hi, total, high_precision)?;
+        self.weighted_votes = self.weighted_votes.checked_add(weight_bps).ok_or(Errors::VoteOverflow)?;
+        if self.first_vote_at == 0 {
+            self.first_vote_at = voted_at;
+        }
+        self.last_vote_at = voted_at;
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct CandidateShareQuery<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+
+    #[account(
+        seeds = [
+            poll_id.to_le_bytes().as_ref(),
+            poll.candidate_seed_separator(),
+            poll.candidate_seed_name(&candidate_name).as_bytes()
+        ],
+        bump
+    )]
+    pub candidate: Account<'info, Candidate>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct RemainingVotes<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+
+    /// CHECK: the signer's voter record PDA, which may not exist yet. It is only
+    /// deserialized when initialized.
+    #[account(
+        seeds = [b"voter", poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+        bump
+    )]
+    pub voter_record: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct ReadCandidates<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct ComputeResultsCommitment<'info> {
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct VerifyResultProof<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct ResultsBreakdownQuery<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct ValidateCandidateName<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct PartyTotalsQuery<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct ZeroVoteCandidates<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct VictoryMargin<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct RankCandidates<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct SerializeResults<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct ExportCsv<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct IsVotable<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct TimeRemaining<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct GetPollStatus<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+/// Return data of `get_poll_status`, Borsh-encoded.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct PollStatus {
+    pub poll_id: u64,
+    /// One of the `POLL_STATUS_*` constants.
+    pub status: u8,
+    pub is_finalized: bool,
+    pub winner: Option<Pubkey>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct PollTurnout<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct PollSupplyTurnout<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct SnapshotSupply<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    /// CHECK: an SPL Token mint, parsed by hand in `mint_supply`.
+    #[account(owner = SPL_TOKEN_PROGRAM_ID @ Errors::InvalidMint)]
+    pub mint: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct VerifyPollIntegrity<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct RecomputeTotalVotes<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct UpdatePollSettings<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+
+    /// CHECK: the poll's `DisplayNameSet` PDA, checked and created by the
+    /// handler. Only needed when turning on `require_unique_display_names`.
+    #[account(mut)]
+    pub display_names: Option<UncheckedAccount<'info>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct ReschedulePoll<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct UpdateWinThreshold<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct EndorseCandidate<'info> {
+    pub endorser: Signer<'info>,
+
+    #[account(
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = poll.settings.endorser == Some(endorser.key()) @ Errors::NotEndorser,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(
+        mut,
+        seeds = [
+            poll_id.to_le_bytes().as_ref(),
+            poll.candidate_seed_separator(),
+            poll.candidate_seed_name(&candidate_name).as_bytes()
+        ],
+        bump
+    )]
+    pub candidate: Account<'info, Candidate>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct ApprovePoll<'info> {
+    pub approver: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = poll.settings.approver == Some(approver.key()) @ Errors::NotApprover,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+}
+
+#[derive(Accounts)]
+#[instruction(source_poll_id: u64, dest_poll_id: u64)]
+pub struct CloneCandidates<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [source_poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized
+    )]
+    pub source_poll: Account<'info, Poll>,
+
+    #[account(
+        mut,
+        seeds = [dest_poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !dest_poll.archived @ Errors::PollArchived
+    )]
+    pub dest_poll: Account<'info, Poll>,
+
+    /// CHECK: the program config PDA, which may not exist yet. It is only
+    /// deserialized when initialized; see `ensure_not_paused`.
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(source_poll_id: u64, new_poll_id: u64)]
+pub struct CreateRunoff<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [source_poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized
+    )]
+    pub source_poll: Account<'info, Poll>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = Poll::space(MAX_DESCRIPTION_LEN),
+        seeds = [new_poll_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + AuditLog::INIT_SPACE,
+        seeds = [b"audit_log", new_poll_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub audit_log: Account<'info, AuditLog>,
+
+    /// CHECK: the program config PDA, which may not exist yet. It is only
+    /// deserialized when initialized; see `ensure_not_paused`.
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(source_poll_id: u64, dest_poll_id: u64)]
+pub struct MergePolls<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [source_poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized
+    )]
+    pub source_poll: Account<'info, Poll>,
+
+    #[account(
+        mut,
+        seeds = [dest_poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !dest_poll.archived @ Errors::PollArchived
+    )]
+    pub dest_poll: Account<'info, Poll>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + TopK::INIT_SPACE,
+        seeds = [b"top_k", dest_poll_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub top_k: Account<'info, TopK>,
+
+    /// CHECK: the program config PDA, which may not exist yet. It is only
+    /// deserialized when initialized; see `ensure_not_paused`.
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct ReadMyBallot<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+
+    /// CHECK: the signer's voter record, which doesn't exist before their first
+    /// ballot; the owner check turns that case into `NoBallot`.
+    #[account(
+        seeds = [b"voter", poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+        bump,
+        owner = crate::ID @ Errors::NoBallot
+    )]
+    pub voter_record: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct MigrateVoterRecord<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    /// CHECK: the signer's voter record, possibly in an older, shorter layout
+    /// that `Account` can't deserialize. The handler reallocs it first and then
+    /// deserializes it, which checks the discriminator.
+    #[account(
+        mut,
+        seeds = [b"voter", poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+        bump,
+        owner = crate::ID
+    )]
+    pub voter_record: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReadParticipation<'info> {
+    pub signer: Signer<'info>,
+
+    /// CHECK: the signer's voter index, which doesn't exist before their first
+    /// counted vote. It is only deserialized when owned by this program.
+    #[account(seeds = [b"voter_index", signer.key().as_ref()], bump)]
+    pub voter_index: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct TransferCandidateAuthority<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(
+        mut,
+        seeds = [
+            poll_id.to_le_bytes().as_ref(),
+            poll.candidate_seed_separator(),
+            poll.candidate_seed_name(&candidate_name).as_bytes()
+        ],
+        bump,
+        constraint = signer.key() == candidate.candidate_authority
+            || signer.key() == poll.authority @ Errors::Unauthorized
+    )]
+    pub candidate: Account<'info, Candidate>,
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct VerifyCandidate<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(
+        mut,
+        seeds = [
+            poll_id.to_le_bytes().as_ref(),
+            poll.candidate_seed_separator(),
+            poll.candidate_seed_name(&candidate_name).as_bytes()
+        ],
+        bump
+    )]
+    pub candidate: Account<'info, Candidate>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct ReserveCandidateSlot<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = Candidate::space(MAX_CANDIDATE_NAME_LEN, MAX_DISPLAY_NAME_LEN),
+        seeds = [
+            poll_id.to_le_bytes().as_ref(),
+            poll.candidate_seed_separator(),
+            slot_seed_name(poll.reserved_slots).as_bytes()
+        ],
+        bump
+    )]
+    pub candidate: Account<'info, Candidate>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64, slot: u64)]
+pub struct FillCandidateSlot<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(
+        mut,
+        seeds = [
+            poll_id.to_le_bytes().as_ref(),
+            poll.candidate_seed_separator(),
+            slot_seed_name(slot).as_bytes()
+        ],
+        bump
+    )]
+    pub candidate: Account<'info, Candidate>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64, voter: Pubkey)]
+pub struct RegisterVoter<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + VoterRecord::INIT_SPACE,
+        seeds = [b"voter", poll_id.to_le_bytes().as_ref(), voter.as_ref()],
+        bump
+    )]
+    pub voter_record: Account<'info, VoterRecord>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + VoterAge::INIT_SPACE,
+        seeds = [b"voter_age", voter.as_ref()],
+        bump
+    )]
+    pub voter_age: Account<'info, VoterAge>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct FinalizePoll<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+
+    /// CHECK: address is pinned to the SlotHashes sysvar, which is parsed by hand
+    /// because it's too large to deserialize on-chain.
+    #[account(address = slot_hashes::ID)]
+    pub slot_hashes: Option<UncheckedAccount<'info>>,
+
+    // `init_if_needed` so a reopened poll overwrites its results when finalized again
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + PollResults::INIT_SPACE,
+        seeds = [b"results", poll_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub results: Account<'info, PollResults>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct CommitSortitionSeed<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct SortitionSelect<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct LockCandidates<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct SealCandidates<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived,
+        constraint = poll.sealed_candidate_hash == [0u8; 32] @ Errors::CandidatesAlreadySealed
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct VerifyCandidateSeal<'info> {
+    #[account(seeds = [poll_id.to_le_bytes().as_ref()], bump)]
+    pub poll: Account<'info, Poll>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct EnableVoteLog<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + VoteLog::INIT_SPACE,
+        seeds = [b"vote_log", poll_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub vote_log: Account<'info, VoteLog>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct ResumePoll<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct AdvanceRound<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + TopK::INIT_SPACE,
+        seeds = [b"top_k", poll_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub top_k: Account<'info, TopK>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct AdjustVotes<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+
+    // a poll finalized without votes has no leaderboard yet
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + TopK::INIT_SPACE,
+        seeds = [b"top_k", poll_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub top_k: Account<'info, TopK>,
+
+    // a poll that finalized itself has no results account yet
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + PollResults::INIT_SPACE,
+        seeds = [b"results", poll_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub results: Account<'info, PollResults>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct ReopenPoll<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized,
+        constraint = !poll.archived @ Errors::PollArchived
+    )]
+    pub poll: Account<'info, Poll>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64, voter: Pubkey)]
+pub struct ReclaimUnvotedRecord<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority @ Errors::Unauthorized
+    )]
+    pub poll: Account<'info, Poll>,
+
+    /// Only records from `register_voter` were paid for by the authority; ones
+    /// `vote` opened belong to the voter.
+    #[account(
+        mut,
+        seeds = [b"voter", poll_id.to_le_bytes().as_ref(), voter.as_ref()],
+        bump,
+        constraint = voter_record.registered @ Errors::VoterNotRegistered,
+        close = authority
+    )]
+    pub voter_record: Account<'info, VoterRecord>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct ArchivePoll<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: the poll, deserialized in `archive_poll` because it is rewritten
+    /// there as an `ArchivedPoll`.
+    #[account(mut, seeds = [poll_id.to_le_bytes().as_ref()], bump, owner = crate::ID)]
+    pub poll: UncheckedAccount<'info>,
+
+    #[account(mut, seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()], bump)]
+    pub audit_log: Account<'info, AuditLog>,
+}
+
+/// What `archive_poll` keeps of a poll, at the poll's address.
+#[account]
+#[derive(InitSpace)]
+pub struct ArchivedPoll {
+    pub poll_id: u64,
+    pub winner: Option<Pubkey>,
+    pub total_votes: u64,
+    pub is_finalized: bool,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + ProgramConfig::INIT_SPACE,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, ProgramConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetProgramPaused<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = admin @ Errors::NotAdmin,
+        constraint = config.initialized @ Errors::NotInitialized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetMaxPollsPerVoter<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = admin @ Errors::NotAdmin,
+        constraint = config.initialized @ Errors::NotInitialized
+    )]
+    pub config: Account<'info, ProgramConfig>,
+}
+
+/// Program-wide singleton holding the emergency-stop switch.
+#[account]
+#[derive(InitSpace)]
+pub struct ProgramConfig {
+    pub admin: Pubkey,
+    pub paused: bool,
+    /// Set by `initialize_config`; admin instructions refuse a config without it.
+    pub initialized: bool,
+    /// Polls a single voter may vote in, tracked in their `VoterGlobalState`.
+    /// 0 means unlimited.
+    pub max_polls_per_voter: u64,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct InitializePoll<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        init,
+        payer = signer,
+        space = Poll::space(MAX_DESCRIPTION_LEN),
+        seeds = [poll_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + AuditLog::INIT_SPACE,
+        seeds = [b"audit_log", poll_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub audit_log: Account<'info, AuditLog>,
+    /// CHECK: the program config PDA, which may not exist yet. It is only
+    /// deserialized when initialized; see `ensure_not_paused`.
+    #[account(seeds = [b"config"], bump)]
+    pub config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(InitSpace, Default)]
+pub struct Poll {
+    pub poll_id: u64,
+    #[max_len(MAX_DESCRIPTION_LEN)]
+    pub description: String,
+    pub poll_start: u64,
+    pub poll_end: u64,
+    pub candidate_amount: u64,
+    pub total_votes: u64,
+    pub authority: Pubkey,
+    pub is_finalized: bool,
+    pub winner: Option<Pubkey>,
+    pub no_winner: bool,
+    pub settings: PollSettings,
+    pub approved: bool,
+    /// Set on polls archived before `archive_poll` turned them into an
+    /// [`ArchivedPoll`]; such polls still reject mutating instructions.
+    pub archived: bool,
+    /// Sum of all vote weights in basis points, see `Candidate::weighted_votes`.
+    pub weighted_total: u64,
+    /// Votes cast in each `bucket_seconds` window since `poll_start`, for
+    /// turnout-over-time views.
+    pub vote_buckets: [u64; VOTE_BUCKETS],
+    /// Width of each `vote_buckets` window, fixed when voting opens. 0 on polls
+    /// created before it existed, which keep hourly buckets.
+    pub bucket_seconds: u64,
+    /// Ballots cast for no candidate through `vote_abstain`.
+    pub abstentions: u64,
+    /// Voters who have cast at least one counted vote or abstention.
+    pub unique_voters: u64,
+    /// Voters added with `register_voter`; the electorate `turnout` measures against.
+    pub registered_voter_count: u64,
+    /// Set by `lock_candidates` or `seal_candidates`; the ballot takes no more
+    /// candidates.
+    pub candidates_locked: bool,
+    /// When `finalize_poll` ran; 0 while the poll isn't finalized.
+    pub finalized_at: u64,
+    /// Most recently added candidate. Following each `next_candidate` from here
+    /// visits every candidate of the poll, newest first.
+    pub candidate_head: Option<Pubkey>,
+    /// Which candidate seed scheme this poll's candidates use; see [`CANDIDATE_SEED_VERSION`].
+    pub candidate_seed_version: u8,
+    /// Merkle root over the finalized results, set by `compute_results_commitment`.
+    /// All zeroes until then.
+    pub results_root: [u8; 32],
+    /// Set while `vote` is calling the poll's hook program.
+    pub processing: bool,
+    /// Supply of the poll's token mint, recorded by `snapshot_supply` before
+    /// voting opens. 0 until then.
+    pub snapshot_supply: u64,
+    /// Whether enough candidates received votes to meet
+    /// `min_contested_candidates`. Set whenever the winner is tallied; a walkover
+    /// result is still recorded but flagged here.
+    pub valid_result: bool,
+    /// Hash of the seed `sortition_select` must reveal, committed before voting
+    /// opens. All zeroes when the poll has no sortition.
+    pub sortition_commitment: [u8; 32],
+    /// Polls whose tallies `merge_polls` has added to this one.
+    #[max_len(MAX_MERGED_POLLS)]
+    pub merged_from: Vec<u64>,
+    /// `sha256(url)` of the off-chain endpoint results are published to; the URL
+    /// itself stays private. All zeroes when the poll has no callback.
+    pub result_callback_hash: [u8; 32],
+    /// Slots taken with `reserve_candidate_slot`; the next one is `#reserved_slots`.
+    pub reserved_slots: u64,
+    /// High 64 bits of `total_votes` on `high_precision` polls; see
+    /// [`Poll::total_votes_wide`].
+    pub total_votes_hi: u64,
+    /// Ballots cast blank through `vote_blank`; never part of `total_votes`.
+    pub blank_votes: u64,
+    /// Set when a slot takes more than `max_votes_per_slot` ballots. Voting stays
+    /// closed until the authority calls `resume_poll`.
+    pub paused: bool,
+    /// Slot `slot_votes` is counting ballots for.
+    pub velocity_slot: u64,
+    /// Ballots counted in `velocity_slot`.
+    pub slot_votes: u64,
+    /// Poll whose top two candidates `create_runoff` opened this poll for.
+    pub runoff_of: Option<u64>,
+    /// Current round, 0 until `advance_round` first runs.
+    pub round: u64,
+    /// `total_votes` of the rounds before `round`.
+    pub total_votes_all_rounds: u64,
+    /// Ballot commitment set by `seal_candidates`; all zeroes while unsealed.
+    pub sealed_candidate_hash: [u8; 32],
+    /// Set by `enable_vote_log`; every counted candidate vote is then appended
+    /// to the poll's `VoteLog`.
+    pub vote_log: bool,
+    /// Set when the poll opened as soon as it was created or rescheduled. Its
+    /// candidates can then be added up to `first_vote_at` instead of `poll_start`.
+    pub opened_at_creation: bool,
+    /// When the first candidate vote was counted; 0 before that.
+    pub first_vote_at: u64,
+}
+
+/// Optional per-poll rules, configured by the authority before voting opens.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct PollSettings {
+    /// Votes the leading candidate needs to be declared the winner. 0 disables the check.
+    pub min_winning_votes: u64,
+    /// Second signer who must approve the ballot before voting opens. Must differ
+    /// from the poll authority.
+    pub approver: Option<Pubkey>,
+    /// Lowercase candidate names before seeding and storing them. This changes PDA
+    /// derivation, so it can't be toggled once candidates exist.
+    pub case_insensitive_names: bool,
+    /// Votes each voter may cast across all candidates. 0 means unlimited.
+    pub votes_per_voter: u64,
+    /// Program whose `on_vote` instruction is invoked after every vote.
+    pub hook_program: Option<Pubkey>,
+    /// Only voters added with `register_voter` may vote.
+    pub require_registration: bool,
+    /// Basis points a vote's weight drops per hour after `poll_start`. 0 keeps every
+    /// vote at full weight. Only the weighted tallies decay; the winner is still
+    /// picked from the raw vote counts.
+    pub weight_decay_per_hour: u64,
+    /// Count abstentions in `total_votes`. Candidate shares then use a
+    /// denominator that includes abstentions; otherwise only candidate votes.
+    pub include_abstentions_in_total: bool,
+    /// Votes stay pending until the voter calls `confirm_vote`.
+    pub require_confirmation: bool,
+    /// Votes go through `vote_anonymous` with a nullifier instead of `vote`.
+    ///
+    /// Nothing on chain checks who may vote: a nullifier is any 32 bytes the
+    /// caller picks, so anyone can cast as many ballots as they make up
+    /// nullifiers. Only enable this where eligibility is enforced off chain,
+    /// e.g. by a relayer that checks a proof before submitting.
+    pub anonymous: bool,
+    /// Votes go through `vote_with_identity`, one budget per registered identity.
+    pub require_identity: bool,
+    /// How ties for first place are settled: `TIE_BREAK_LOWEST_PUBKEY` or
+    /// `TIE_BREAK_SLOT_HASH`.
+    pub tie_break: u8,
+    /// Seconds after finalization during which `adjust_votes` may still correct
+    /// the result. When set, `reopen_poll` is also limited to this window. The
+    /// poll can only be archived once the window has passed.
+    pub dispute_window_seconds: u64,
+    /// Total vote weight eligible to take part, the base `quorum_bps` applies to.
+    pub total_eligible_weight: u64,
+    /// Turnout needed for a winner, in basis points of `total_eligible_weight`.
+    /// 0 disables the check.
+    pub quorum_bps: u64,
+    /// Votes each `vote` adds to the candidate and poll tallies, e.g. 10 for a
+    /// department casting a block. Must be at least 1.
+    pub votes_per_cast: u64,
+    /// Log the candidate and its tally on every vote. Turning this off saves
+    /// compute on busy polls; the `VoteReceipt` event still records every vote.
+    pub verbose_logging: bool,
+    /// Only candidates marked with `verify_candidate` can receive votes.
+    pub require_verified_candidates: bool,
+    /// Candidates that must receive at least one vote for the result to count as
+    /// contested; see `Poll::valid_result`. 0 disables the check.
+    pub min_contested_candidates: u64,
+    /// Slots that must pass between a voter's first `register_voter` (see
+    /// `VoterAge`) and their `vote`. 0 disables the check.
+    pub min_account_age_slots: u64,
+    /// What `verify_poll_integrity` expects of the vote sum:
+    /// `COUNTING_SINGLE_CHOICE` or `COUNTING_APPROVAL`.
+    pub counting_mode: u8,
+    /// New candidate names may only use `[A-Za-z0-9 _-]`, keeping unicode
+    /// lookalikes off the ballot.
+    pub restrict_candidate_charset: bool,
+    /// No two candidates created by `initialize_candidate` may share a display
+    /// name; see `DisplayNameSet`. Can't be toggled once candidates exist.
+    pub require_unique_display_names: bool,
+    /// Credit budget of each voter on a quadratic poll, where votes go through
+    /// `vote_quadratic` and N votes for a candidate cost N². 0 disables it.
+    pub credits_per_voter: u64,
+    /// On `require_registration` polls, finalize on the `vote` that brings
+    /// turnout to every registered voter instead of waiting for `poll_end`.
+    /// Voters with budget left lose the rest of it.
+    pub auto_finalize_on_full_turnout: bool,
+    /// Let candidate tallies and `total_votes` grow past `u64::MAX` into their
+    /// `_hi` words. Winners are picked on the full tally; other reports read the
+    /// low 64 bits.
+    pub high_precision: bool,
+    /// Count `blank_votes` as turnout when checking `quorum_bps`.
+    pub blank_votes_count_toward_quorum: bool,
+    /// Ballots a single slot may take before the poll pauses itself; see
+    /// [`Poll::paused`]. 0 disables the check.
+    pub max_votes_per_slot: u64,
+    /// How the leader wins: `WIN_RULE_PLURALITY` or `WIN_RULE_MAJORITY`. Without
+    /// a majority the poll finalizes with `no_winner`.
+    pub win_rule: u8,
+    /// Poll that must have passed before this one takes votes: finalized with a
+    /// winner, or with `required_winner` if set. Votes must then pass it as
+    /// `prerequisite_poll`.
+    pub depends_on: Option<u64>,
+    /// Candidate the `depends_on` poll must have elected. Any winner will do when
+    /// unset.
+    pub required_winner: Option<Pubkey>,
+    /// Token-2022 mint, or group mint of a collection, whose holders vote
+    /// through `vote_by_token_tier`, weighted by the tier in their token's
+    /// metadata. Other ballots are rejected.
+    pub gate_mint: Option<Pubkey>,
+    /// Votes a `vote_by_token_tier` ballot counts as, indexed by the gate token's
+    /// tier. A tier weighted 0 can't vote.
+    pub tier_weights: [u64; TOKEN_TIERS],
+    /// Candidates with fewer votes are left out of `results_breakdown` and
+    /// `rank_candidates` and summed into their `other_votes`. 0 lists everyone.
+    pub results_min_votes: u64,
+    /// Signer allowed to weight candidates with `endorse_candidate`.
+    pub endorser: Option<Pubkey>,
+    /// Jurisdictions voters may declare in `vote`; empty allows any. Other
+    /// ballot modes can't declare one and are rejected when this is set.
+    #[max_len(MAX_JURISDICTIONS)]
+    pub allowed_jurisdictions: Vec<u32>,
+    /// Token-2022 group mint whose members `vote_with_receipt` mints to voters
+    /// as non-transferable "I voted" receipts. None disables receipts.
+    pub receipt_collection: Option<Pubkey>,
+}
+
+impl Default for PollSettings {
+    fn default() -> Self {
+        Self {
+            min_winning_votes: 0,
+            approver: None,
+            case_insensitive_names: false,
+            votes_per_voter: 0,
+            hook_program: None,
+            require_registration: false,
+            weight_decay_per_hour: 0,
+            include_abstentions_in_total: false,
+            require_confirmation: false,
+            anonymous: false,
+            require_identity: false,
+            tie_break: TIE_BREAK_LOWEST_PUBKEY,
+            dispute_window_seconds: 0,
+            total_eligible_weight: 0,
+            quorum_bps: 0,
+            votes_per_cast: 1,
+            verbose_logging: true,
+            require_verified_candidates: false,
+            min_contested_candidates: 0,
+            min_account_age_slots: 0,
+            counting_mode: COUNTING_SINGLE_CHOICE,
+            restrict_candidate_charset: false,
+            require_unique_display_names: false,
+            credits_per_voter: 0,
+            auto_finalize_on_full_turnout: false,
+            high_precision: false,
+            blank_votes_count_toward_quorum: false,
+            max_votes_per_slot: 0,
+            win_rule: WIN_RULE_PLURALITY,
+            depends_on: None,
+            required_winner: None,
+            gate_mint: None,
+            tier_weights: [0; TOKEN_TIERS],
+            results_min_votes: 0,
+            endorser: None,
+            allowed_jurisdictions: Vec::new(),
+            receipt_collection: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DisputeWindowState {
+    NotFinalized,
+    /// Finalized, but the result can still be adjusted.
+    Open,
+    /// Finalized for good; the poll may be archived.
+    Closed,
+}
+
+impl Poll {
+    /// Account size (discriminator included) for a poll whose description is `description_len` bytes.
+    pub const fn space(description_len: usize) -> usize {
+        8 + Poll::INIT_SPACE - MAX_DESCRIPTION_LEN + description_len
+    }
+
+    /// Name a candidate is stored and seeded under. With `case_insensitive_names`
+    /// the name is lowercased, so "Alice" and "alice" derive the same PDA. Unicode
+    /// lowercasing can change a name's byte length, so callers re-check the cap.
+    pub fn candidate_seed_name(&self, candidate_name: &str) -> String {
+        if self.settings.case_insensitive_names {
+            candidate_name.to_lowercase()
+        } else {
+            candidate_name.to_string()
+        }
+    }
+
+    /// Seed name for a new candidate called `candidate_name`, or the reason the
+    /// name can't be used. `validate_candidate_name` reports the same check.
+    pub fn new_candidate_seed_name(&self, candidate_name: &str) -> std::result::Result<String, Errors> {
+        if self.settings.restrict_candidate_charset
+            && !candidate_name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '_' | '-'))
+        {
+            return Err(Errors::InvalidCandidateChars);
+        }
+        if candidate_name.starts_with(SLOT_SEED_PREFIX) {
+            return Err(Errors::ReservedCandidateName);
+        }
+        let seed_name = self.candidate_seed_name(candidate_name);
+        if seed_name.len() > MAX_CANDIDATE_NAME_LEN {
+            return Err(Errors::CandidateNameTooLong);
+        }
+        Ok(seed_name)
+    }
+
+    /// First poll-wide reason a vote cast at `now` would be rejected, if any.
+    /// Per-voter and per-candidate checks (budget, registration, late candidates)
+    /// stay in `vote`.
+    pub fn voting_blocker(&self, now: u64) -> Option<Errors> {
+        if self.processing {
+            Some(Errors::ReentrancyDetected)
+        } else if self.archived {
+            Some(Errors::PollArchived)
+        } else if self.is_finalized {
+            Some(Errors::PollFinalized)
+        } else if self.paused {
+            Some(Errors::PollPaused)
+        } else if now < self.poll_start {
+            Some(Errors::PollNotStarted)
+        } else if now > self.poll_end {
+            Some(Errors::PollEnded)
+        } else if self.candidate_amount == 0 {
+            Some(Errors::NoCandidates)
+        } else if self.settings.approver.is_some() && !self.approved {
+            Some(Errors::PollNotApproved)
+        } else {
+            None
+        }
+    }
+
+    /// Where `now` falls relative to the voting window, as a `POLL_STATUS_*` code.
+    pub fn status(&self, now: u64) -> u8 {
+        if now < self.poll_start {
+            POLL_STATUS_UPCOMING
+        } else if now <= self.poll_end {
+            POLL_STATUS_ACTIVE
+        } else {
+            POLL_STATUS_ENDED
+        }
+    }
+
+    /// Whether a voter who declared `jurisdiction` may vote, i.e. the poll has no
+    /// `allowed_jurisdictions` or lists theirs.
+    pub fn jurisdiction_allowed(&self, jurisdiction: Option<u32>) -> bool {
+        let allowed = &self.settings.allowed_jurisdictions;
+        allowed.is_empty() || jurisdiction.is_some_and(|jurisdiction| allowed.contains(&jurisdiction))
+    }
+
+    /// Whether a leader with `top_votes` satisfies the poll's `win_rule`.
+    pub fn win_rule_met(&self, top_votes: u128) -> bool {
+        self.settings.win_rule != WIN_RULE_MAJORITY || top_votes.saturating_mul(2) > self.total_votes_wide()
+    }
+
+    /// Latest time a votable candidate can be added: `poll_start`, or the first
+    /// vote on a poll that opened at creation.
+    pub fn candidate_cutoff(&self) -> u64 {
+        match (self.opened_at_creation, self.first_vote_at) {
+            (false, _) => self.poll_start,
+            (true, 0) => u64::MAX,
+            (true, first_vote_at) => first_vote_at,
+        }
+    }
+
+    /// Reason `candidate` can't receive votes in this poll, if any.
+    pub fn candidate_blocker(&self, candidate: &Candidate) -> Option<Errors> {
+        if candidate.unfilled {
+            Some(Errors::CandidateSlotUnfilled)
+        } else if candidate.created_at > self.candidate_cutoff() {
+            Some(Errors::CandidateAddedAfterStart)
+        } else if self.settings.require_verified_candidates && !candidate.verified {
+            Some(Errors::CandidateNotVerified)
+        } else {
+            None
+        }
+    }
+
+    /// Counts a vote towards the poll's totals and its hourly turnout bucket, and
+    /// returns the vote's decayed weight in basis points.
+    pub fn record_vote(&mut self, voted_at: i64) -> Result<u64> {
+        self.record_votes(voted_at, self.settings.votes_per_cast)
+    }
+
+    /// `record_vote` for a ballot worth `votes` rather than `votes_per_cast`.
+    pub fn record_votes(&mut self, voted_at: i64, votes: u64) -> Result<u64> {
+        let weight_bps = vote_weight_bps(
+            self.poll_start,
+            voted_at,
+            self.settings.weight_decay_per_hour
+        )
+        .checked_mul(votes)
+        .ok_or(Errors::VoteOverflow)?;
+        let total = self.total_votes_wide().checked_add(votes as u128).ok_or(Errors::VoteOverflow)?;
+        self.set_total_votes(total)?;
+        if self.first_vote_at == 0 {
+            self.first_vote_at = voted_at as u64;
+        }
+        self.weighted_total = self.weighted_total.checked_add(weight_bps).ok_or(Errors::VoteOverflow)?;
+        self.vote_buckets[vote_bucket(self.poll_start, self.bucket_seconds, voted_at)] += votes;
+        Ok(weight_bps)
+    }
+
+    /// Counts a ballot cast in `slot` against `max_votes_per_slot`, starting the
+    /// count over once the slot has moved on. Returns whether this ballot paused
+    /// the poll.
+    pub fn track_slot_votes(&mut self, slot: u64) -> bool {
+        let max = self.settings.max_votes_per_slot;
+        if max == 0 {
+            return false;
+        }
+        if slot != self.velocity_slot {
+            self.velocity_slot = slot;
+            self.slot_votes = 0;
+        }
+        self.slot_votes = self.slot_votes.saturating_add(1);
+        if self.slot_votes > max && !self.paused {
+            self.paused = true;
+            return true;
+        }
+        false
+    }
+
+    /// Where the poll is relative to its post-finalization dispute window at `now`.
+    pub fn dispute_window_state(&self, now: u64) -> DisputeWindowState {
+        if !self.is_finalized {
+            DisputeWindowState::NotFinalized
+        } else if now < self.finalized_at.saturating_add(self.settings.dispute_window_seconds) {
+            DisputeWindowState::Open
+        } else {
+            DisputeWindowState::Closed
+        }
+    }
+
+    /// Whether `total_votes` reaches `quorum_bps` of `total_eligible_weight`.
+    pub fn quorum_met(&self) -> bool {
+        let blanks = if self.settings.blank_votes_count_toward_quorum { self.blank_votes } else { 0 };
+        (self.total_votes_wide() + blanks as u128).saturating_mul(FULL_VOTE_WEIGHT_BPS as u128)
+            >= self.settings.total_eligible_weight as u128 * self.settings.quorum_bps as u128
+    }
+
+    /// Seed between the poll id and the candidate name, empty for version 0 polls.
+    pub fn candidate_seed_separator(&self) -> &'static [u8] {
+        if self.candidate_seed_version == 0 {
+            &[]
+        } else {
+            CANDIDATE_SEED_SEPARATOR
+        }
+    }
+
+    /// PDA and bump of the candidate stored under `seed_name` in this poll.
+    pub fn candidate_address(&self, seed_name: &str) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                self.poll_id.to_le_bytes().as_ref(),
+                self.candidate_seed_separator(),
+                seed_name.as_bytes(),
+            ],
+            &crate::ID,
+        )
+    }
+
+    /// Records an abstention. With `include_abstentions_in_total` it also counts
+    /// towards `total_votes` and the turnout buckets, like a vote.
+    pub fn record_abstention(&mut self, voted_at: i64) -> Result<()> {
+        self.abstentions = self.abstentions.checked_add(1).ok_or(Errors::VoteOverflow)?;
+        if self.settings.include_abstentions_in_total {
+            let total = self.total_votes_wide().checked_add(1).ok_or(Errors::VoteOverflow)?;
+            self.set_total_votes(total)?;
+            self.vote_buckets[vote_bucket(self.poll_start, self.bucket_seconds, voted_at)] += 1;
+        }
+        Ok(())
+    }
+
+    /// Whether `url` is the callback the poll was created with. Relayers check
+    /// their configured URL with this before posting results.
+    pub fn callback_url_matches(&self, url: &str) -> bool {
+        self.result_callback_hash != [0; 32] && hashv(&[url.as_bytes()]).to_bytes() == self.result_callback_hash
+    }
+
+    /// `total_votes` including `total_votes_hi`.
+    pub fn total_votes_wide(&self) -> u128 {
+        wide_votes(self.total_votes, self.total_votes_hi)
+    }
+
+    /// Sets the full vote total; only `high_precision` polls may exceed `u64::MAX`.
+    pub fn set_total_votes(&mut self, total: u128) -> Result<()> {
+        set_wide_votes(&mut self.total_votes, &mut self.total_votes_hi, total, self.settings.high_precision)
+    }
+
+    /// Votes cast for candidates, i.e. `total_votes` without any abstentions it includes.
+    /// Fails with `VoteOverflow` if the total holds fewer votes than abstentions.
+    pub fn candidate_vote_total(&self) -> Result<u64> {
+        if self.settings.include_abstentions_in_total {
+            Ok(self.total_votes.checked_sub(self.abstentions).ok_or(Errors::VoteOverflow)?)
+        } else {
+            Ok(self.total_votes)
+        }
+    }
+}
+
+#[error_code]
+pub enum Errors {
+    #[msg("Poll end date is in the past")]
+    PollEndInThePast,
+
+    #[msg("Provided timestamp is not a valid Unix timestamp.")]
+    InvalidTimestamp,
+
+    #[msg("Poll end date is before poll start date")]
+    InvalidEndDate,
+
+    #[msg("Only the poll authority can perform this action")]
+    Unauthorized,
+
+    #[msg("Poll has not ended yet")]
+    PollNotEnded,
+
+    #[msg("Poll has already been finalized")]
+    PollAlreadyFinalized,
+
+    #[msg("Poll has not been finalized")]
+    PollNotFinalized,
+
+    #[msg("Poll is finalized and no longer accepts votes")]
+    PollFinalized,
+
+    #[msg("Provided candidates do not match the poll's candidate count")]
+    CandidateCountMismatch,
+
+    #[msg("Candidate does not belong to this poll")]
+    CandidatePollMismatch,
+
+    #[msg("Poll has already started")]
+    PollAlreadyStarted,
+
+    #[msg("Poll has not been approved yet")]
+    PollNotApproved,
+
+    #[msg("Only the poll approver can approve this poll")]
+    NotApprover,
+
+    #[msg("Poll approver must be different from the poll authority")]
+    ApproverIsAuthority,
+
+    #[msg("Voter allocation cannot track any more candidates")]
+    AllocationFull,
+
+    #[msg("Candidate votes do not add up to the poll's total votes")]
+    VoteSumMismatch,
+
+    #[msg("Candidate name is longer than 32 bytes")]
+    CandidateNameTooLong,
+
+    #[msg("This setting can't change once candidates have been added")]
+    CandidatesAlreadyAdded,
+
+    #[msg("Voter has no votes left in this poll")]
+    VoteBudgetExhausted,
+
+    #[msg("Poll is archived")]
+    PollArchived,
+
+    #[msg("Hook program is missing or does not match the poll's hook")]
+    InvalidHookProgram,
+
+    #[msg("Candidate was added after the poll started")]
+    CandidateAddedAfterStart,
+
+    #[msg("Unknown tie-break strategy")]
+    InvalidTieBreak,
+
+    #[msg("Tie-break needs the SlotHashes sysvar")]
+    MissingSlotHashes,
+
+    #[msg("Voter is not registered for this poll")]
+    VoterNotRegistered,
+
+    #[msg("Poll has not started yet")]
+    PollNotStarted,
+
+    #[msg("Poll has ended")]
+    PollEnded,
+
+    #[msg("Poll has no candidates")]
+    NoCandidates,
+
+    #[msg("Poll does not accept this kind of vote")]
+    VotingModeMismatch,
+
+    #[msg("A candidate has already reached the win threshold")]
+    ThresholdAlreadyReached,
+
+    #[msg("Win threshold can't be lower than a candidate's existing votes")]
+    ThresholdBelowExistingVotes,
+
+    #[msg("A candidate with this name already exists in the poll")]
+    DuplicateCandidate,
+
+    #[msg("Voter already has a pending vote in this poll")]
+    PendingVoteExists,
+
+    #[msg("No matching pending vote to confirm or cancel")]
+    NoPendingVote,
+
+    #[msg("Description is longer than 200 bytes")]
+    DescriptionTooLong,
+
+    #[msg("Description contains control characters")]
+    InvalidDescriptionChars,
+
+    #[msg("Signer can't be the zero address")]
+    InvalidSigner,
+
+    #[msg("Too many candidates for one snapshot")]
+    TooManyCandidates,
+
+    #[msg("Program is paused")]
+    ProgramPaused,
+
+    #[msg("Only the program admin can perform this action")]
+    NotAdmin,
+
+    #[msg("Poll results have not been committed")]
+    ResultsNotCommitted,
+
+    #[msg("Proof does not match the committed results")]
+    InvalidResultProof,
+
+    #[msg("Candidate display name is longer than 64 bytes")]
+    DisplayNameTooLong,
+
+    #[msg("Signer can't cover the rent for a voter record")]
+    InsufficientRent,
+
+    #[msg("Quorum must be at most 10000 bps of a non-zero eligible weight")]
+    InvalidQuorum,
+
+    #[msg("Poll results are still open to dispute")]
+    DisputeWindowOpen,
+
+    #[msg("Dispute window for this poll has closed")]
+    DisputeWindowClosed,
+
+    #[msg("Poll's candidates are locked")]
+    CandidatesLocked,
+
+    #[msg("Voter record belongs to a different poll")]
+    VoterPollMismatch,
+
+    #[msg("votes_per_cast must be at least 1")]
+    InvalidVotesPerCast,
+
+    #[msg("Vote tally overflowed")]
+    VoteOverflow,
+
+    #[msg("Too many votes in one batch")]
+    BatchTooLarge,
+
+    #[msg("Program config is not initialized")]
+    NotInitialized,
+
+    #[msg("Candidate is not verified")]
+    CandidateNotVerified,
+
+    #[msg("Poll was already merged into the destination")]
+    PollAlreadyMerged,
+
+    #[msg("Destination poll can't record more merged polls")]
+    TooManyMergedPolls,
+
+    #[msg("Poll id does not match the poll account")]
+    PollIdMismatch,
+
+    #[msg("Poll has no sortition seed commitment")]
+    SortitionNotCommitted,
+
+    #[msg("Revealed seed does not match the commitment")]
+    SortitionSeedMismatch,
+
+    #[msg("Every voter record of the poll must be provided exactly once")]
+    VoterCountMismatch,
+
+    #[msg("Account is not an initialized SPL Token mint")]
+    InvalidMint,
+
+    #[msg("Mint has no supply")]
+    ZeroSupply,
+
+    #[msg("Poll has no supply snapshot")]
+    SupplyNotSnapshotted,
+
+    #[msg("Poll is in the middle of a hook call")]
+    ReentrancyDetected,
+
+    #[msg("Candidate party name is longer than 32 bytes")]
+    PartyNameTooLong,
+
+    #[msg("Signer has not voted in this poll")]
+    NoBallot,
+
+    #[msg("Voter was first seen too recently to vote in this poll")]
+    AccountTooNew,
+
+    #[msg("Unknown counting mode")]
+    InvalidCountingMode,
+
+    #[msg("Candidate name contains characters outside the allowed set")]
+    InvalidCandidateChars,
+
+    #[msg("Another candidate already uses this display name")]
+    DuplicateDisplayName,
+
+    #[msg("The poll's display name set is full")]
+    TooManyDisplayNames,
+
+    #[msg("Not enough voting credits left for these votes")]
+    InsufficientCredits,
+
+    #[msg("Must cast at least one vote")]
+    NoVotesCast,
+
+    #[msg("Poll must open at least MIN_NOTICE_SECONDS after it is created")]
+    InsufficientNotice,
+
+    #[msg("Candidate slot has not been filled yet")]
+    CandidateSlotUnfilled,
+
+    #[msg("Candidate names starting with # are reserved for candidate slots")]
+    ReservedCandidateName,
+
+    #[msg("Voter record rent differs from the amount the client expected")]
+    RentMismatch,
+    #[msg("Poll is paused after a burst of votes")]
+    PollPaused,
+    #[msg("Poll is not paused")]
+    PollNotPaused,
+    #[msg("Win rule must be plurality or majority")]
+    InvalidWinRule,
+    #[msg("Only a majority poll finalized without a winner can have a runoff")]
+    RunoffNotNeeded,
+    #[msg("A runoff needs at least two candidates")]
+    RunoffNeedsTwoCandidates,
+    #[msg("The poll this one depends on has not passed")]
+    PrerequisiteNotMet,
+    #[msg("A poll can't depend on itself, and a required winner needs a prerequisite poll")]
+    InvalidPrerequisite,
+    #[msg("Voter has reached the program's limit of polls per voter")]
+    ParticipationCapReached,
+    #[msg("Token account doesn't hold the poll's gate mint for the signer")]
+    InvalidGateToken,
+    #[msg("Gate mint's token metadata has no valid tier")]
+    InvalidTokenTier,
+    #[msg("Signer has no registered identity")]
+    IdentityNotRegistered,
+    #[msg("Candidate set is already sealed")]
+    CandidatesAlreadySealed,
+    #[msg("Candidate set has not been sealed")]
+    CandidatesNotSealed,
+    #[msg("Candidates don't match the sealed ballot")]
+    CandidateSealMismatch,
+    #[msg("Clock sysvar is unavailable")]
+    ClockUnavailable,
+    #[msg("Signer is not the poll's endorser")]
+    NotEndorser,
+    #[msg("Endorsement multiplier must be between 10000 and 20000 bps")]
+    InvalidEndorsementMultiplier,
+    #[msg("Voter's declared jurisdiction isn't allowed in this poll")]
+    WrongJurisdiction,
+    #[msg("Too many allowed jurisdictions")]
+    TooManyJurisdictions,
+    #[msg("Voter record has cast a ballot and can't be reclaimed")]
+    RecordHasVoted,
+    #[msg("Poll logs its votes, pass its vote log")]
+    VoteLogMissing,
+    #[msg("Voter record has not cast a ballot")]
+    RecordHasNotVoted,
+    #[msg("Voter record's ballot is from an earlier round")]
+    StaleVoterRecord,
+    #[msg("Token-tier ballots must pass the token's ballot record")]
+    TokenBallotMissing,
+    #[msg("Unique display names need the poll's display name set")]
+    DisplayNameSetMissing,
+    #[msg("Receipt collection doesn't match the poll's receipt collection")]
+    ReceiptCollectionMismatch,
+    #[msg("Receipt mint must be an unminted, non-transferable mint of the poll's receipt authority")]
+    InvalidReceiptMint,
+    #[msg("Receipt token account doesn't hold the receipt mint for the signer")]
+    InvalidReceiptAccount,
+}
+
+#[event]
+pub struct VoteReceipt {
+    pub poll_id: u64,
+    pub candidate: Pubkey,
+    pub voter: Pubkey,
+    pub voted_at: i64,
+    pub receipt_hash: [u8; 32],
+}
+
+#[event]
+pub struct CandidateShare {
+    pub poll_id: u64,
+    pub candidate: Pubkey,
+    pub candidate_votes: u64,
+    pub total_votes: u64,
+    pub share_bps: u64,
+}
+
+#[event]
+pub struct VoteSimulation {
+    pub poll_id: u64,
+    pub candidate: Pubkey,
+    pub voter: Pubkey,
+    pub would_succeed: bool,
+    /// Error code `vote` would return, or 0 when `would_succeed`.
+    pub reason_code: u32,
+}
+
+#[event]
+pub struct Eligibility {
+    pub poll_id: u64,
+    pub voter: Pubkey,
+    pub eligible: bool,
+    /// Error code of the first gate the voter fails, or 0 when `eligible`.
+    pub reason_code: u32,
+}
+
+#[event]
+pub struct CandidateDisplayVotes {
+    pub poll_id: u64,
+    pub candidate: Pubkey,
+    pub weighted_votes_bps: u64,
+    pub display_votes: u64,
+}
+
+#[event]
+pub struct RemainingBudget {
+    pub voter: Pubkey,
+    pub poll_id: u64,
+    pub remaining: u64,
+}
+
+#[event]
+pub struct PollTiming {
+    pub poll_id: u64,
+    pub seconds_until_start: u64,
+    pub seconds_until_end: u64,
+    /// One of the `POLL_STATUS_*` constants.
+    pub status: u8,
+}
+
+#[event]
+pub struct Turnout {
+    pub poll_id: u64,
+    pub unique_voters: u64,
+    pub registered_voter_count: u64,
+    pub turnout_bps: u64,
+}
+
+#[event]
+pub struct SupplyTurnout {
+    pub poll_id: u64,
+    pub total_votes: u64,
+    pub snapshot_supply: u64,
+    pub turnout_bps: u64,
+}
+
+#[event]
+pub struct PollVotable {
+    pub poll_id: u64,
+    pub votable: bool,
+    /// Error code `vote` would return, or 0 when `votable`.
+    pub reason: u32,
+}
+
+#[event]
+pub struct CandidateSnapshot {
+    pub poll_id: u64,
+    pub candidates: Vec<CandidateVotes>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CandidateVotes {
+    pub name: String,
+    pub votes: u64,
+}
+
+#[event]
+pub struct CandidateRanking {
+    pub poll_id: u64,
+    pub ranking: Vec<CandidateVotes>,
+    /// Votes of the candidates left out under `results_min_votes`.
+    pub other_votes: u64,
+}
+
+/// Parallel arrays: `votes[i]` and `share_bps[i]` belong to `candidates[i]`.
+#[event]
+pub struct ResultsBreakdown {
+    pub poll_id: u64,
+    /// Sum of the candidates' votes, the denominator of `share_bps`. It includes
+    /// `other_votes`.
+    pub total_votes: u64,
+    /// Votes of the candidates left out under `results_min_votes`.
+    pub other_votes: u64,
+    pub candidates: Vec<Pubkey>,
+    pub votes: Vec<u64>,
+    pub share_bps: Vec<u64>,
+}
+
+/// `seed_name` is the name as the candidate would be stored, after the poll's
+/// case folding.
+#[event]
+pub struct NameValidation {
+    pub poll_id: u64,
+    pub valid: bool,
+    pub reason_code: u32,
+    pub seed_name: String,
+}
+
+/// A voter's latest ballot. `candidate` is `None` when they abstained or voted
+/// blank, and
+/// `weight_bps` is the decayed weight of a ballot cast at `voted_at`.
+#[event]
+pub struct MyBallot {
+    pub poll_id: u64,
+    pub voter: Pubkey,
+    pub candidate: Option<Pubkey>,
+    pub abstained: bool,
+    pub blank: bool,
+    pub confirmed: bool,
+    pub votes_used: u64,
+    pub weight_bps: u64,
+    pub voted_at: i64,
+}
+
+/// Parallel arrays: `votes[i]` is the total of `parties[i]`.
+#[event]
+pub struct PartyTotals {
+    pub poll_id: u64,
+    pub parties: Vec<String>,
+    pub votes: Vec<u64>,
+}
+
+/// Every candidate sharing the lead, in pubkey order, whichever of them the
+/// poll's `tie_break` then picks.
+#[event]
+pub struct TieDetected {
+    pub poll_id: u64,
+    pub tied_candidates: Vec<Pubkey>,
+    pub vote_count: u128,
+}
+
+#[event]
+pub struct AutoFinalized {
+    pub poll_id: u64,
+    pub winner: Option<Pubkey>,
+    pub unique_voters: u64,
+}
+
+#[event]
+pub struct RoundAdvanced {
+    pub poll_id: u64,
+    pub round: u64,
+    pub votes_last_round: u64,
+}
+
+#[event]
+pub struct SuspiciousVelocityPaused {
+    pub poll_id: u64,
+    pub slot: u64,
+    pub votes_in_slot: u64,
+}
+
+/// Capped at `MAX_VOTER_INDEX_POLLS`, like the index it's read from.
+#[event]
+pub struct ParticipationProof {
+    pub voter: Pubkey,
+    pub poll_count: u64,
+    pub poll_ids: Vec<u64>,
+}
+
+#[event]
+pub struct ZeroVoteReport {
+    pub poll_id: u64,
+    pub count: u64,
+    pub candidates: Vec<Pubkey>,
+}
+
+#[event]
+pub struct VictoryMarginReport {
+    pub poll_id: u64,
+    pub winner: Option<Pubkey>,
+    pub runner_up: Option<Pubkey>,
+    pub margin_votes: u128,
+    pub margin_bps: u64,
+}
+
+#[event]
+pub struct TotalRecomputed {
+    pub poll_id: u64,
+    pub old_total: u64,
+    pub new_total: u64,
+}
+
+#[event]
+pub struct SerializedResults {
+    pub poll_id: u64,
+    /// See [`encode_results`] for the layout.
+    pub results: Vec<u8>,
+    /// `sha256(results)`.
+    pub hash: [u8; 32],
+}
+
+#[event]
+pub struct PollReopened {
+    pub poll_id: u64,
+    pub authority: Pubkey,
+    pub new_poll_end: u64,
+}
+
+#[event]
+pub struct PollRescheduled {
+    pub poll_id: u64,
+    pub authority: Pubkey,
+    pub poll_start: u64,
+    pub poll_end: u64,
+}
+
+#[event]
+pub struct SortitionSelected {
+    pub poll_id: u64,
+    pub voter: Pubkey,
+    pub candidate: Option<Pubkey>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serialized_len<T: AccountSerialize>(account: &T) -> usize {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        data.len()
+    }
+
+    #[test]
+    fn poll_space_matches_serialized_size() {
+        for len in [0, 1, 42, MAX_DESCRIPTION_LEN] {
+            // optional fields are populated so the poll serializes at its largest
+            let poll = Poll {
+                description: "x".repeat(len),
+                winner: Some(Pubkey::new_unique()),
+                candidate_head: Some(Pubkey::new_unique()),
+                merged_from: vec![0; MAX_MERGED_POLLS],
+                runoff_of: Some(1),
+                settings: PollSettings {
+                    approver: Some(Pubkey::new_unique()),
+                    hook_program: Some(Pubkey::new_unique()),
+                    depends_on: Some(1),
+                    required_winner: Some(Pubkey::new_unique()),
+                    gate_mint: Some(Pubkey::new_unique()),
+                    endorser: Some(Pubkey::new_unique()),
+                    allowed_jurisdictions: vec![0; MAX_JURISDICTIONS],
+                    receipt_collection: Some(Pubkey::new_unique()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            assert_eq!(Poll::space(len), serialized_len(&poll));
+        }
+    }
+
+    #[test]
+    fn candidate_space_matches_serialized_size() {
+        for (len, display_len) in [(0, 0), (1, 12), (5, 0), (MAX_CANDIDATE_NAME_LEN, MAX_DISPLAY_NAME_LEN)] {
+            let candidate = Candidate {
+                candidate_name: "x".repeat(len),
+                display_name: "y".repeat(display_len),
+                // party isn't part of `space`'s arguments; only its maximum is reserved
+                party: "p".repeat(MAX_PARTY_NAME_LEN),
+                next_candidate: Some(Pubkey::new_unique()),
+                ..Default::default()
+            };
+            assert_eq!(Candidate::space(len, display_len), serialized_len(&candidate));
+        }
+    }
+
+    #[test]
+    fn candidate_seed_separator_keeps_candidates_off_the_poll_address() {
+        let poll_id = 7u64;
+        let (poll_address, _) =
+            Pubkey::find_program_address(&[poll_id.to_le_bytes().as_ref()], &crate::ID);
+
+        let legacy = Poll { poll_id, ..Default::default() };
+        assert_eq!(legacy.candidate_address("").0, poll_address);
+
+        let poll = Poll {
+            poll_id,
+            candidate_seed_version: CANDIDATE_SEED_VERSION,
+            ..Default::default()
+        };
+        assert_ne!(poll.candidate_address("").0, poll_address);
+        assert_ne!(poll.candidate_address("Alice").0, legacy.candidate_address("Alice").0);
+    }
+
+    #[test]
+    fn abstentions_count_in_total_only_when_configured() {
+        for include in [false, true] {
+            let mut poll = Poll {
+                settings: PollSettings {
+                    include_abstentions_in_total: include,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            poll.record_vote(0).unwrap();
+            poll.record_abstention(0).unwrap();
+
+            assert_eq!(poll.abstentions, 1);
+            assert_eq!(poll.total_votes, if include { 2 } else { 1 });
+            assert_eq!(poll.candidate_vote_total().unwrap(), 1);
+            assert_eq!(poll.vote_buckets.iter().sum::<u64>(), poll.total_votes);
+        }
+
+        let mut poll = Poll { abstentions: u64::MAX, ..Default::default() };
+        assert_eq!(poll.record_abstention(0).unwrap_err(), Errors::VoteOverflow.into());
+        poll.settings.include_abstentions_in_total = true;
+        assert_eq!(poll.candidate_vote_total().unwrap_err(), Errors::VoteOverflow.into());
+    }
+
+    #[test]
+    fn vote_bucket_counts_hours_from_start() {
+        let start = 1_700_000_000;
+        assert_eq!(vote_bucket(start, 3600, start as i64), 0);
+        assert_eq!(vote_bucket(start, 3600, start as i64 + 3599), 0);
+        assert_eq!(vote_bucket(start, 3600, start as i64 + 3600), 1);
+        assert_eq!(vote_bucket(start, 3600, start as i64 + 5 * 3600 + 1), 5);
+        assert_eq!(vote_bucket(start, 3600, start as i64 + 100 * 3600), VOTE_BUCKETS - 1);
+        // early or negative timestamps fall into the first bucket
+        assert_eq!(vote_bucket(start, 3600, start as i64 - 60), 0);
+        assert_eq!(vote_bucket(start, 3600, -1), 0);
+        // polls from before `bucket_seconds` keep hourly buckets
+        assert_eq!(vote_bucket(start, 0, start as i64 + 3600), 1);
+    }
+
+    #[test]
+    fn bucket_seconds_scale_with_poll_length() {
+        let start = 1_700_000_000;
+        assert_eq!(bucket_seconds(start, start + 12 * 3600), 3600);
+        assert_eq!(bucket_seconds(start, start + 24 * 3600), 3600);
+        assert_eq!(bucket_seconds(start, start + 48 * 3600), 7200);
+        assert_eq!(bucket_seconds(start, start + 48 * 3600 + 1), 7201);
+
+        let width = bucket_seconds(start, start + 48 * 3600);
+        assert_eq!(vote_bucket(start, width, (start + 5 * 3600) as i64), 2);
+        assert_eq!(vote_bucket(start, width, (start + 48 * 3600) as i64), VOTE_BUCKETS - 1);
+    }
+
+    #[test]
+    fn voting_blocker_reports_the_first_failing_check() {
+        let mut poll = Poll {
+            poll_start: 100,
+            poll_end: 200,
+            candidate_amount: 1,
+            ..Default::default()
+        };
+        assert!(poll.voting_blocker(150).is_none());
+        assert!(matches!(poll.voting_blocker(99), Some(Errors::PollNotStarted)));
+        assert!(matches!(poll.voting_blocker(201), Some(Errors::PollEnded)));
+
+        poll.settings.approver = Some(Pubkey::new_unique());
+        assert!(matches!(poll.voting_blocker(150), Some(Errors::PollNotApproved)));
+
+        poll.candidate_amount = 0;
+        assert!(matches!(poll.voting_blocker(150), Some(Errors::NoCandidates)));
+
+        poll.paused = true;
+        assert!(matches!(poll.voting_blocker(150), Some(Errors::PollPaused)));
+
+        poll.is_finalized = true;
+        assert!(matches!(poll.voting_blocker(150), Some(Errors::PollFinalized)));
+
+        poll.processing = true;
+        assert!(matches!(poll.voting_blocker(150), Some(Errors::ReentrancyDetected)));
+    }
+
+    #[test]
+    fn track_slot_votes_pauses_once_a_slot_passes_the_limit() {
+        let mut poll = Poll::default();
+        assert!(!poll.track_slot_votes(5));
+        assert_eq!(poll.slot_votes, 0);
+
+        poll.settings.max_votes_per_slot = 2;
+        assert!(!poll.track_slot_votes(5));
+        assert!(!poll.track_slot_votes(5));
+        // a new slot starts the count over
+        assert!(!poll.track_slot_votes(6));
+        assert_eq!(poll.slot_votes, 1);
+        assert!(!poll.track_slot_votes(6));
+        assert!(poll.track_slot_votes(6));
+        assert!(poll.paused);
+        assert!(!poll.track_slot_votes(6));
+    }
+
+    #[test]
+    fn candidate_blocker_checks_verification_only_when_required() {
+        let mut poll = Poll { poll_start: 100, ..Default::default() };
+        let mut candidate = Candidate { created_at: 50, ..Default::default() };
+        assert!(poll.candidate_blocker(&candidate).is_none());
+
+        poll.settings.require_verified_candidates = true;
+        assert!(matches!(poll.candidate_blocker(&candidate), Some(Errors::CandidateNotVerified)));
+        candidate.verified = true;
+        assert!(poll.candidate_blocker(&candidate).is_none());
+
+        candidate.created_at = 101;
+        assert!(matches!(poll.candidate_blocker(&candidate), Some(Errors::CandidateAddedAfterStart)));
+
+        // a poll that opened at creation takes candidates until its first vote
+        poll.opened_at_creation = true;
+        assert!(poll.candidate_blocker(&candidate).is_none());
+        poll.record_vote(105).unwrap();
+        assert!(poll.candidate_blocker(&candidate).is_none());
+        candidate.created_at = 106;
+        assert!(matches!(poll.candidate_blocker(&candidate), Some(Errors::CandidateAddedAfterStart)));
+    }
+
+    #[test]
+    fn sortition_index_is_deterministic_and_in_range() {
+        let seed = [7; 32];
+        let index = sortition_index(&seed, 1, 3);
+        assert!(index < 3);
+        assert_eq!(sortition_index(&seed, 1, 3), index);
+        assert_eq!(sortition_index(&seed, 1, 1), 0);
+        assert!((0..50).map(|poll_id| sortition_index(&seed, poll_id, 3)).any(|i| i != index));
+    }
+
+    #[test]
+    fn encode_results_is_in_index_order_and_little_endian() {
+        let low = Pubkey::new_from_array([1; 32]);
+        let high = Pubkey::new_from_array([2; 32]);
+        let results = encode_results(7, &[(0, high, 258), (1, low, 1)]);
+
+        let mut expected = vec![7, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0];
+        expected.extend_from_slice(&[2; 32]);
+        expected.extend_from_slice(&[2, 1, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(&[1; 32]);
+        expected.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(results, expected);
+        assert_eq!(encode_results(7, &[(1, low, 1), (0, high, 258)]), results);
+    }
+
+    #[test]
+    fn descriptions_allow_newlines_but_no_other_control_chars() {
+        assert!(is_renderable_description("Favourite colour?"));
+        assert!(is_renderable_description("Line one\nLine two — ünïcode"));
+        assert!(!is_renderable_description("null\0byte"));
+        assert!(!is_renderable_description("tab\there"));
+        assert!(!is_renderable_description("\u{1b}[31mred"));
+    }
+
+    #[test]
+    fn join_poll_stops_at_the_participation_cap() {
+        let voter = Pubkey::new_unique();
+        let mut state = VoterGlobalState::default();
+        state.join_poll(voter, 2).unwrap();
+        state.join_poll(voter, 2).unwrap();
+        assert_eq!(state.join_poll(voter, 2).unwrap_err(), Errors::ParticipationCapReached.into());
+        assert_eq!((state.voter, state.polls_voted), (voter, 2));
+
+        state.join_poll(voter, 0).unwrap();
+        assert_eq!(state.polls_voted, 3);
+    }
+
+    #[test]
+    fn voter_index_skips_repeats_and_stops_at_cap() {
+        let mut index = VoterIndex::default();
+        index.record(1);
+        index.record(1);
+        assert_eq!(index.poll_ids, vec![1]);
+
+        for poll_id in 2..=(MAX_VOTER_INDEX_POLLS as u64 + 5) {
+            index.record(poll_id);
+        }
+        assert_eq!(index.poll_ids.len(), MAX_VOTER_INDEX_POLLS);
+        assert_eq!(index.poll_ids.last(), Some(&(MAX_VOTER_INDEX_POLLS as u64)));
+    }
+
+    #[test]
+    fn top_k_keeps_the_most_voted_candidates_in_order() {
+        let candidates: Vec<Pubkey> = (0..TOP_K + 1).map(|_| Pubkey::new_unique()).collect();
+        let mut top_k = TopK::default();
+        for (votes, candidate) in candidates.iter().enumerate() {
+            top_k.record(*candidate, votes as u128 + 1);
+        }
+        // the first candidate, with one vote, fell off the board
+        let ranked: Vec<Pubkey> = top_k.entries.iter().map(|e| e.candidate).collect();
+        let expected: Vec<Pubkey> = candidates[1..].iter().rev().copied().collect();
+        assert_eq!(ranked, expected);
+
+        // a listed candidate gaining votes moves up instead of being listed twice
+        top_k.record(candidates[2], 10);
+        assert_eq!(top_k.entries[0], TopKEntry { candidate: candidates[2], votes: 10, votes_hi: 0 });
+        assert_eq!(top_k.entries.iter().filter(|e| e.candidate == candidates[2]).count(), 1);
+        assert_eq!(top_k.entries[1].candidate, candidates[5]);
+    }
+
+    #[test]
+    fn top_k_settles_a_result_unless_the_full_ballot_is_needed() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut poll = Poll::default();
+        let mut top_k = TopK::default();
+        assert_eq!(top_k.settled_result(&poll), Some((None, true)));
+
+        top_k.record(a, 2);
+        top_k.record(b, 1);
+        assert_eq!(top_k.settled_result(&poll), Some((Some(a), true)));
+
+        top_k.record(b, 2);
+        assert_eq!(top_k.settled_result(&poll), Some((Some(a.min(b)), true)));
+        poll.settings.tie_break = TIE_BREAK_SLOT_HASH;
+        assert_eq!(top_k.settled_result(&poll), None);
+
+        poll.settings.tie_break = TIE_BREAK_LOWEST_PUBKEY;
+        poll.settings.min_contested_candidates = 3;
+        assert_eq!(top_k.settled_result(&poll), Some((Some(a.min(b)), false)));
+        for votes in 1..TOP_K as u128 - 1 {
+            top_k.record(Pubkey::new_unique(), votes);
+        }
+        poll.settings.min_contested_candidates = TOP_K as u64 + 1;
+        assert_eq!(top_k.settled_result(&poll), None);
+    }
+
+    #[test]
+    fn top_k_ranks_high_precision_tallies_on_both_words() {
+        let (low, high) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut top_k = TopK::default();
+        top_k.record(low, u64::MAX as u128);
+        top_k.record(high, u64::MAX as u128 + 1);
+        assert_eq!(top_k.entries[0].candidate, high);
+        assert_eq!(top_k.entries[0].votes_wide(), u64::MAX as u128 + 1);
+        assert_eq!(top_k.entries[1].candidate, low);
+
+        let poll = Poll::default();
+        assert_eq!(top_k.settled_result(&poll), Some((Some(high), true)));
+    }
+
+    #[test]
+    fn dispute_window_closes_after_its_length() {
+        let mut poll = Poll {
+            settings: PollSettings {
+                dispute_window_seconds: 60,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(poll.dispute_window_state(100), DisputeWindowState::NotFinalized);
+
+        poll.is_finalized = true;
+        poll.finalized_at = 100;
+        assert_eq!(poll.dispute_window_state(159), DisputeWindowState::Open);
+        assert_eq!(poll.dispute_window_state(160), DisputeWindowState::Closed);
+
+        poll.settings.dispute_window_seconds = 0;
+        assert_eq!(poll.dispute_window_state(100), DisputeWindowState::Closed);
+    }
+
+    #[test]
+    fn quorum_is_a_share_of_eligible_weight() {
+        let mut poll = Poll {
+            settings: PollSettings {
+                total_eligible_weight: 3,
+                quorum_bps: 6_667,
+                ..Default::default()
+            },
+            total_votes: 2,
+            ..Default::default()
+        };
+        // 2 of 3 is 6666.6 bps
+        assert!(!poll.quorum_met());
+
+        poll.settings.quorum_bps = 6_666;
+        assert!(poll.quorum_met());
+
+        poll.settings.total_eligible_weight = u64::MAX;
+        poll.settings.quorum_bps = FULL_VOTE_WEIGHT_BPS;
+        poll.total_votes = u64::MAX;
+        assert!(poll.quorum_met());
+
+        // blank votes only count when configured
+        poll.total_votes = 0;
+        poll.blank_votes = u64::MAX;
+        assert!(!poll.quorum_met());
+        poll.settings.blank_votes_count_toward_quorum = true;
+        assert!(poll.quorum_met());
+
+        assert!(Poll::default().quorum_met());
+    }
+
+    #[test]
+    fn audit_log_drops_the_oldest_entry_when_full() {
+        let actor = Pubkey::new_unique();
+        let mut log = AuditLog::default();
+        for timestamp in 0..(MAX_AUDIT_ENTRIES as i64 + 2) {
+            log.record(AUDIT_UPDATE_SETTINGS, actor, timestamp);
+        }
+        assert_eq!(log.entries.len(), MAX_AUDIT_ENTRIES);
+        assert_eq!(log.entries[0].timestamp, 2);
+        assert_eq!(log.entries.last().unwrap().timestamp, MAX_AUDIT_ENTRIES as i64 + 1);
+    }
+
+    #[test]
+    fn voter_record_must_match_its_poll() {
+        let poll = Pubkey::new_unique();
+        assert!(ensure_record_matches_poll(&VoterRecord::default(), &poll).is_ok());
+
+        let mut record = VoterRecord { poll, ..Default::default() };
+        assert!(ensure_record_matches_poll(&record, &poll).is_ok());
+
+        record.poll = Pubkey::new_unique();
+        assert_eq!(
+            ensure_record_matches_poll(&record, &poll).unwrap_err(),
+            Errors::VoterPollMismatch.into()
+        );
+    }
+
+    #[test]
+    fn a_new_round_clears_the_ballot_but_keeps_registration() {
+        let mut record = VoterRecord {
+            poll: Pubkey::new_unique(),
+            candidate: Pubkey::new_unique(),
+            votes_used: 3,
+            credits_used: 9,
+            registered: true,
+            confirmed: true,
+            version: VOTER_RECORD_VERSION,
+            choice: BallotChoice::Candidate,
+            ..Default::default()
+        };
+        let same_round = record.clone();
+        record.start_round(0);
+        assert_eq!((record.votes_used, record.candidate), (same_round.votes_used, same_round.candidate));
+
+        record.start_round(1);
+        assert_eq!(record.round, 1);
+        assert_eq!((record.votes_used, record.credits_used), (0, 0));
+        assert_eq!(record.candidate, Pubkey::default());
+        assert_eq!(record.choice, BallotChoice::None);
+        assert!(record.registered && !record.confirmed);
+        assert_eq!((record.poll, record.version), (same_round.poll, VOTER_RECORD_VERSION));
+
+        let mut allocation = VoterAllocation::default();
+        allocation.add(Pubkey::new_unique(), 2).unwrap();
+        allocation.start_round(0);
+        assert_eq!(allocation.allocations.len(), 1);
+        allocation.start_round(1);
+        assert!(allocation.allocations.is_empty());
+    }
+
+    #[test]
+    fn zero_address_is_not_a_valid_signer() {
+        assert!(require_valid_signer(&Pubkey::new_unique()).is_ok());
+        assert_eq!(
+            require_valid_signer(&Pubkey::default()).unwrap_err(),
+            Errors::InvalidSigner.into()
+        );
+    }
+
+    #[test]
+    fn vote_weight_decays_per_hour_down_to_the_floor() {
+        let start = 1_700_000_000;
+        assert_eq!(vote_weight_bps(start, start as i64 + 7200, 0), FULL_VOTE_WEIGHT_BPS);
+        assert_eq!(vote_weight_bps(start, start as i64 + 3599, 1_000), 10_000);
+        assert_eq!(vote_weight_bps(start, start as i64 + 3 * 3600, 1_000), 7_000);
+        assert_eq!(vote_weight_bps(start, start as i64 + 50 * 3600, 1_000), MIN_VOTE_WEIGHT_BPS);
+        assert_eq!(vote_weight_bps(start, i64::MAX, u64::MAX), MIN_VOTE_WEIGHT_BPS);
+    }
+
+    #[test]
+    fn display_votes_round_half_up() {
+        assert_eq!(display_votes(0), 0);
+        assert_eq!(display_votes(4_999), 0);
+        assert_eq!(display_votes(5_000), 1);
+        assert_eq!(display_votes(14_999), 1);
+        assert_eq!(display_votes(17_000), 2);
+        assert_eq!(display_votes(u64::MAX), u64::MAX / 10_000);
+    }
+
+    #[test]
+    fn results_csv_sorts_rows_by_candidate_index() {
+        let (low, high) = (Pubkey::new_from_array([1; 32]), Pubkey::new_from_array([2; 32]));
+        let lines = results_csv(&[(2, low, 3), (0, high, u64::MAX as u128 + 1)]);
+        assert_eq!(
+            lines,
+            vec![
+                "candidate,index,votes".to_string(),
+                format!("{},0,18446744073709551616", high),
+                format!("{},2,3", low),
+            ]
+        );
+    }
+
+    #[test]
+    fn results_root_proves_each_candidate() {
+        // indices run against pubkey order, so the leaves follow the indices
+        let tally: Vec<(u64, Pubkey, u64)> = (0..3u8)
+            .map(|i| (i as u64, Pubkey::new_from_array([3 - i; 32]), i as u64 * 10))
+            .collect();
+        let root = results_root(&tally);
+        let leaves: Vec<[u8; 32]> =
+            tally.iter().map(|(_, key, votes)| results_leaf(key, *votes)).collect();
+        let left = merkle_parent(&leaves[0], &leaves[1]);
+
+        // leaves 0 and 1 pair up; leaf 2 is carried up and joins their parent
+        assert_eq!(root, merkle_parent(&left, &leaves[2]));
+        assert!(verify_merkle_proof(leaves[0], &[leaves[1], leaves[2]], &root));
+        assert!(verify_merkle_proof(leaves[2], &[left], &root));
+        assert!(!verify_merkle_proof(results_leaf(&tally[2].1, 21), &[left], &root));
+
+        let mut reversed = tally.clone();
+        reversed.reverse();
+        assert_eq!(results_root(&reversed), root);
+    }
+
+    #[test]
+    fn now_maps_a_missing_clock_to_clock_unavailable() {
+        // off-chain there is no sysvar syscall, so `Clock::get` always fails here
+        assert!(Clock::get().is_err());
+        assert_eq!(now().unwrap_err(), Errors::ClockUnavailable.into());
+        assert_eq!(clock().unwrap_err(), Errors::ClockUnavailable.into());
+    }
+
+    #[test]
+    fn candidate_set_hash_commits_to_display_names_not_order() {
+        let candidate = |i: u8, display_name: &str| {
+            let candidate = Candidate { display_name: display_name.to_string(), ..Default::default() };
+            (Pubkey::new_from_array([i; 32]), candidate)
+        };
+        let ballot = vec![candidate(1, "Alice"), candidate(2, "Bob")];
+        let sealed = candidate_set_hash(&ballot);
+
+        assert_eq!(candidate_set_hash(&[ballot[1].clone(), ballot[0].clone()]), sealed);
+        assert_ne!(candidate_set_hash(&[candidate(1, "Alice"), candidate(2, "Bobby")]), sealed);
+        assert_ne!(candidate_set_hash(&ballot[..1]), sealed);
+    }
+
+    fn candidate_with_votes(candidate_votes: u64) -> (Pubkey, Candidate) {
+        let candidate = Candidate {
+            candidate_votes,
+            ..Default::default()
+        };
+        (Pubkey::new_unique(), candidate)
+    }
+
+    #[test]
+    fn gate_mint_polls_only_take_token_tier_ballots() {
+        let mut poll = Poll {
+            poll_start: 100,
+            poll_end: 200,
+            candidate_amount: 1,
+            ..Default::default()
+        };
+        let record = VoterRecord::default();
+        assert!(ballot_blocker(&poll, None, &record, 150, BallotMode::Standard).is_none());
+        assert!(matches!(
+            ballot_blocker(&poll, None, &record, 150, BallotMode::TokenTier),
+            Some(Errors::VotingModeMismatch)
+        ));
+
+        poll.settings.gate_mint = Some(Pubkey::new_unique());
+        assert!(matches!(
+            ballot_blocker(&poll, None, &record, 150, BallotMode::Standard),
+            Some(Errors::VotingModeMismatch)
+        ));
+        assert!(ballot_blocker(&poll, None, &record, 150, BallotMode::TokenTier).is_none());
+        poll.settings.votes_per_voter = 1;
+        let spent = VoterRecord { votes_used: 1, ..Default::default() };
+        assert!(matches!(
+            ballot_blocker(&poll, None, &spent, 150, BallotMode::TokenTier),
+            Some(Errors::VoteBudgetExhausted)
+        ));
+    }
+
+    #[test]
+    fn vote_log_overwrites_its_oldest_entries_once_full() {
+        let entry = |i: u32| VoteLogEntry { candidate_index: (i % 3) as u16, weight: i };
+        let mut log = VoteLog::default();
+        for i in 0..3 {
+            log.append(entry(i));
+        }
+        assert_eq!(log.ordered(), vec![entry(0), entry(1), entry(2)]);
+
+        let total = VOTE_LOG_ENTRIES as u32 + 5;
+        for i in 3..total {
+            log.append(entry(i));
+        }
+        assert_eq!(log.total_entries, total as u64);
+        assert_eq!(log.entries.len(), VOTE_LOG_ENTRIES);
+        let ordered = log.ordered();
+        assert_eq!(ordered.first(), Some(&entry(5)));
+        assert_eq!(ordered.last(), Some(&entry(total - 1)));
+        assert!(ordered.windows(2).all(|pair| pair[0].weight + 1 == pair[1].weight));
+    }
+
+    #[test]
+    fn vote_log_space_fits_a_full_buffer() {
+        let log = VoteLog {
+            entries: vec![VoteLogEntry { candidate_index: u16::MAX, weight: u32::MAX }; VOTE_LOG_ENTRIES],
+            ..Default::default()
+        };
+        assert_eq!(serialized_len(&log), 8 + VoteLog::INIT_SPACE);
+    }
+
+    #[test]
+    fn only_untouched_records_have_not_voted() {
+        let registered = VoterRecord { registered: true, ..Default::default() };
+        assert!(!registered.has_voted());
+
+        let pending = VoterRecord { candidate: Pubkey::new_unique(), ..registered.clone() };
+        assert!(pending.has_voted());
+        let abstained = VoterRecord { votes_used: 1, choice: BallotChoice::Abstain, ..registered.clone() };
+        assert!(abstained.has_voted());
+        let quadratic = VoterRecord { credits_used: 4, ..registered };
+        assert!(quadratic.has_voted());
+    }
+
+    #[test]
+    fn ballots_need_an_allowed_declared_jurisdiction() {
+        let mut poll = Poll {
+            poll_start: 100,
+            poll_end: 200,
+            candidate_amount: 1,
+            ..Default::default()
+        };
+        let mut record = VoterRecord::default();
+        assert!(ballot_blocker(&poll, None, &record, 150, BallotMode::Standard).is_none());
+
+        poll.settings.allowed_jurisdictions = vec![7, 9];
+        assert!(matches!(ballot_blocker(&poll, None, &record, 150, BallotMode::Standard), Some(Errors::WrongJurisdiction)));
+        record.jurisdiction = Some(8);
+        assert!(matches!(ballot_blocker(&poll, None, &record, 150, BallotMode::Standard), Some(Errors::WrongJurisdiction)));
+        record.jurisdiction = Some(9);
+        assert!(ballot_blocker(&poll, None, &record, 150, BallotMode::Standard).is_none());
+    }
+
+    #[test]
+    fn endorsed_candidates_rank_by_scaled_votes() {
+        let leader = candidate_with_votes(10);
+        let mut endorsed = candidate_with_votes(8);
+        endorsed.1.endorsement_multiplier_bps = 15_000;
+        let mut poll = Poll::default();
+
+        assert_eq!(endorsed.1.ranked_votes(), 12);
+        assert_eq!(endorsed.1.votes(), 8);
+        let candidates = [leader, endorsed.clone()];
+        assert_eq!(tally_winner(&poll, &candidates, None).unwrap(), Some(endorsed.0));
+
+        // a threshold still counts the 8 votes actually cast
+        poll.settings.min_winning_votes = 9;
+        assert_eq!(tally_winner(&poll, &candidates, None).unwrap(), None);
+    }
+
+    #[test]
+    fn minor_candidates_are_summed_into_other_votes() {
+        let candidates = vec![candidate_with_votes(9), candidate_with_votes(1), candidate_with_votes(2)];
+        let mut poll = Poll::default();
+
+        let (shown, other_votes) = split_minor_candidates(&poll, candidates.clone());
+        assert_eq!((shown.len(), other_votes), (3, 0));
+
+        poll.settings.results_min_votes = 3;
+        let (shown, other_votes) = split_minor_candidates(&poll, candidates);
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].1.candidate_votes, 9);
+        assert_eq!(other_votes, 3);
+    }
+
+    #[test]
+    fn slot_hash_tie_break_is_reproducible() {
+        let poll = Poll {
+            settings: PollSettings {
+                tie_break: TIE_BREAK_SLOT_HASH,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let candidates = vec![
+            candidate_with_votes(3),
+            candidate_with_votes(3),
+            candidate_with_votes(1),
+            candidate_with_votes(3),
+        ];
+        let mut tied: Vec<Pubkey> =
+            candidates.iter().filter(|(_, c)| c.candidate_votes == 3).map(|(key, _)| *key).collect();
+        tied.sort();
+
+        // 5 % 3 tied candidates picks the third one in pubkey order
+        let mut slot_hash = [0u8; 32];
+        slot_hash[0] = 5;
+        let winner = tally_winner(&poll, &candidates, Some(&slot_hash)).unwrap();
+        assert_eq!(winner, Some(tied[2]));
+
+        // account order doesn't matter
+        let mut reversed = candidates.clone();
+        reversed.reverse();
+        assert_eq!(tally_winner(&poll, &reversed, Some(&slot_hash)).unwrap(), winner);
+
+        slot_hash[0] = 7;
+        assert_eq!(tally_winner(&poll, &candidates, Some(&slot_hash)).unwrap(), Some(tied[1]));
+
+        assert_eq!(
+            tally_winner(&poll, &candidates, None).unwrap_err(),
+            Errors::MissingSlotHashes.into()
+        );
+    }
+
+    #[test]
+    fn contested_counts_candidates_with_votes() {
+        let mut poll = Poll::default();
+        let candidates = vec![candidate_with_votes(3), candidate_with_votes(0)];
+        assert!(is_contested(&poll, &candidates));
+
+        poll.settings.min_contested_candidates = 2;
+        assert!(!is_contested(&poll, &candidates));
+        assert!(is_contested(&poll, &[candidate_with_votes(3), candidate_with_votes(1)]));
+    }
+
+    #[test]
+    fn mint_supply_reads_initialized_mints_only() {
+        let mut data = [0u8; MINT_ACCOUNT_LEN];
+        data[36..44].copy_from_slice(&1_000u64.to_le_bytes());
+        assert_eq!(mint_supply(&data), None);
+
+        data[45] = 1;
+        assert_eq!(mint_supply(&data), Some(1_000));
+        assert_eq!(mint_supply(&data[..MINT_ACCOUNT_LEN - 1]), None);
+    }
+
+    /// A Token-2022 mint whose embedded metadata has `additional` entries.
+    fn tiered_mint(mint: &Pubkey, additional: &[(&str, &str)]) -> Vec<u8> {
+        let mut metadata = [Pubkey::default().to_bytes(), mint.to_bytes()].concat();
+        for field in ["Gate", "GATE", "https://gate.example"] {
+            field.to_string().serialize(&mut metadata).unwrap();
+        }
+        let pairs: Vec<(String, String)> = additional
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        pairs.serialize(&mut metadata).unwrap();
+
+        let mut data = vec![0u8; TOKEN_ACCOUNT_LEN + 1];
+        data[45] = 1;
+        data[TOKEN_ACCOUNT_LEN] = 1;
+        // an unrelated extension first, so the walk has to skip it
+        data.extend_from_slice(&[3, 0, 2, 0, 0xaa, 0xbb]);
+        data.extend_from_slice(&TOKEN_METADATA_EXTENSION.to_le_bytes());
+        data.extend_from_slice(&(metadata.len() as u16).to_le_bytes());
+        data.extend_from_slice(&metadata);
+        data
+    }
+
+    #[test]
+    fn token_metadata_field_reads_additional_metadata() {
+        let mint = Pubkey::new_unique();
+        let data = tiered_mint(&mint, &[("color", "red"), ("tier", "2")]);
+        assert_eq!(token_metadata_field(&data, &mint, "tier").as_deref(), Some("2"));
+        assert_eq!(token_metadata_field(&data, &mint, "rank"), None);
+        // metadata describing another mint doesn't count
+        assert_eq!(token_metadata_field(&data, &Pubkey::new_unique(), "tier"), None);
+
+        let mut uninitialized = data.clone();
+        uninitialized[45] = 0;
+        assert_eq!(token_metadata_field(&uninitialized, &mint, "tier"), None);
+        assert_eq!(token_metadata_field(&data[..data.len() - 1], &mint, "tier"), None);
+    }
+
+    #[test]
+    fn token_group_reads_the_group_member_extension() {
+        let (mint, group) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = tiered_mint(&mint, &[("tier", "1")]);
+        assert_eq!(token_group(&data, &mint), None);
+
+        data.extend_from_slice(&TOKEN_GROUP_MEMBER_EXTENSION.to_le_bytes());
+        data.extend_from_slice(&72u16.to_le_bytes());
+        data.extend_from_slice(mint.as_ref());
+        data.extend_from_slice(group.as_ref());
+        data.extend_from_slice(&7u64.to_le_bytes());
+        assert_eq!(token_group(&data, &mint), Some(group));
+        assert_eq!(token_group(&data, &group), None);
+        assert_eq!(token_metadata_field(&data, &mint, "tier").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn token_2022_account_reads_initialized_accounts_only() {
+        let (mint, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = vec![0u8; TOKEN_ACCOUNT_LEN];
+        data[..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(owner.as_ref());
+        data[64..72].copy_from_slice(&5u64.to_le_bytes());
+        assert_eq!(token_2022_account(&data), None);
+
+        data[108] = 1;
+        assert_eq!(token_2022_account(&data), Some((mint, owner, 5)));
+        data.push(2);
+        assert_eq!(token_2022_account(&data), Some((mint, owner, 5)));
+        // a mint padded to the same length is not a token account
+        *data.last_mut().unwrap() = 1;
+        assert_eq!(token_2022_account(&data), None);
+    }
+
+    #[test]
+    fn is_receipt_mint_wants_a_fresh_soulbound_mint_of_the_authority() {
+        let authority = Pubkey::new_unique();
+        let mut data = vec![0u8; TOKEN_ACCOUNT_LEN + 1];
+        data[..4].copy_from_slice(&1u32.to_le_bytes());
+        data[4..36].copy_from_slice(authority.as_ref());
+        data[45] = 1;
+        data[TOKEN_ACCOUNT_LEN] = 1;
+        assert!(!is_receipt_mint(&data, &authority));
+
+        data.extend_from_slice(&NON_TRANSFERABLE_EXTENSION.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        assert!(is_receipt_mint(&data, &authority));
+        assert!(!is_receipt_mint(&data, &Pubkey::new_unique()));
+
+        // already minted, divisible, or with its mint authority revoked
+        for (offset, value) in [(36, 1), (44, 6), (0, 0)] {
+            let mut other = data.clone();
+            other[offset] = value;
+            assert!(!is_receipt_mint(&other, &authority));
+        }
+        assert!(!is_receipt_mint(&data[..MINT_ACCOUNT_LEN], &authority));
+    }
+
+    #[test]
+    fn voter_age_counts_slots_since_first_seen() {
+        let age = VoterAge { voter: Pubkey::new_unique(), first_seen_slot: 100 };
+        assert!(age.old_enough(0, 100));
+        assert!(!age.old_enough(10, 109));
+        assert!(age.old_enough(10, 110));
+        assert!(!age.old_enough(10, 50));
+    }
+
+    #[test]
+    fn new_candidate_names_are_capped_after_case_folding() {
+        let mut poll = Poll::default();
+        assert_eq!(poll.new_candidate_seed_name("Alice").unwrap(), "Alice");
+        assert_eq!(poll.new_candidate_seed_name("").unwrap(), "");
+        assert!(poll.new_candidate_seed_name(&"x".repeat(MAX_CANDIDATE_NAME_LEN)).is_ok());
+        assert!(matches!(
+            poll.new_candidate_seed_name(&"x".repeat(MAX_CANDIDATE_NAME_LEN + 1)),
+            Err(Errors::CandidateNameTooLong)
+        ));
+
+        // "İ" is 2 bytes but lowercases to 3
+        poll.settings.case_insensitive_names = true;
+        let name = "İ".repeat(MAX_CANDIDATE_NAME_LEN / 2);
+        assert!(matches!(poll.new_candidate_seed_name(&name), Err(Errors::CandidateNameTooLong)));
+
+        assert!(matches!(poll.new_candidate_seed_name(&slot_seed_name(0)), Err(Errors::ReservedCandidateName)));
+        assert!(poll.new_candidate_seed_name("Slot #1").is_ok());
+    }
+
+    #[test]
+    fn callback_url_must_match_the_stored_hash() {
+        let mut poll = Poll::default();
+        assert!(!poll.callback_url_matches(""));
+
+        poll.result_callback_hash = hashv(&[b"https://results.example/poll/1"]).to_bytes();
+        assert!(poll.callback_url_matches("https://results.example/poll/1"));
+        assert!(!poll.callback_url_matches("https://results.example/poll/2"));
+    }
+
+    #[test]
+    fn high_precision_tallies_carry_past_u64_max() {
+        let mut candidate = Candidate { candidate_votes: u64::MAX, ..Default::default() };
+        assert_eq!(candidate.record_vote(1, 1, 0, false).unwrap_err(), Errors::VoteOverflow.into());
+        assert_eq!(candidate.votes(), u64::MAX as u128);
+
+        candidate.record_vote(1, 2, 0, true).unwrap();
+        assert_eq!(candidate.votes(), u64::MAX as u128 + 2);
+        assert_eq!((candidate.candidate_votes, candidate.candidate_votes_hi), (1, 1));
+
+        let mut poll = Poll { total_votes: u64::MAX, ..Default::default() };
+        assert!(poll.record_votes(0, 1).is_err());
+        poll.settings.high_precision = true;
+        poll.record_votes(0, 1).unwrap();
+        assert_eq!(poll.total_votes_wide(), u64::MAX as u128 + 1);
+
+        // the carried tally outranks a bigger low word
+        let leader = (Pubkey::new_unique(), candidate);
+        let runner_up = (Pubkey::new_unique(), Candidate { candidate_votes: u64::MAX, ..Default::default() });
+        assert_eq!(tally_winner(&poll, &[runner_up, leader.clone()], None).unwrap(), Some(leader.0));
+    }
+
+    #[test]
+    fn dependent_polls_need_their_prerequisite_to_pass() {
+        let winner = Pubkey::new_unique();
+        let mut poll = Poll { poll_id: 2, ..Default::default() };
+        let mut prerequisite = Poll { poll_id: 1, ..Default::default() };
+        assert!(ensure_prerequisite_met(&poll, None).is_ok());
+
+        poll.settings.depends_on = Some(1);
+        let not_met: Error = Errors::PrerequisiteNotMet.into();
+        assert_eq!(ensure_prerequisite_met(&poll, None).unwrap_err(), not_met);
+        assert_eq!(ensure_prerequisite_met(&poll, Some(&prerequisite)).unwrap_err(), not_met);
+
+        prerequisite.is_finalized = true;
+        prerequisite.winner = Some(winner);
+        assert!(ensure_prerequisite_met(&poll, Some(&prerequisite)).is_ok());
+        let other = Poll { poll_id: 3, ..prerequisite.clone() };
+        assert_eq!(ensure_prerequisite_met(&poll, Some(&other)).unwrap_err(), not_met);
+
+        poll.settings.required_winner = Some(Pubkey::new_unique());
+        assert_eq!(ensure_prerequisite_met(&poll, Some(&prerequisite)).unwrap_err(), not_met);
+        poll.settings.required_winner = Some(winner);
+        assert!(ensure_prerequisite_met(&poll, Some(&prerequisite)).is_ok());
+    }
+
+    #[test]
+    fn margin_of_victory_compares_the_top_two() {
+        let candidate = |votes| (Pubkey::new_unique(), Candidate { candidate_votes: votes, ..Default::default() });
+        let (leader, second, third) = (candidate(6), candidate(3), candidate(1));
+        let candidates = [third.clone(), leader.clone(), second.clone()];
+        assert_eq!(margin_of_victory(&candidates, 10), (Some(leader.0), Some(second.0), 3, 3_000));
+
+        assert_eq!(margin_of_victory(std::slice::from_ref(&leader), 6), (Some(leader.0), None, 0, 0));
+        assert_eq!(margin_of_victory(&[], 0), (None, None, 0, 0));
+
+        let rival = (Pubkey::new_unique(), leader.1.clone());
+        let (low, high) = if leader.0 < rival.0 { (leader.0, rival.0) } else { (rival.0, leader.0) };
+        assert_eq!(margin_of_victory(&[rival, leader], 12), (Some(low), Some(high), 0, 0));
+    }
+
+    #[test]
+    fn majority_rule_needs_more_than_half_of_the_votes() {
+        let leader = (Pubkey::new_unique(), Candidate { candidate_votes: 4, ..Default::default() });
+        let others = [
+            (Pubkey::new_unique(), Candidate { candidate_votes: 3, ..Default::default() }),
+            (Pubkey::new_unique(), Candidate { candidate_votes: 3, ..Default::default() }),
+        ];
+        let candidates = [leader.clone(), others[0].clone(), others[1].clone()];
+        let mut poll = Poll { total_votes: 10, ..Default::default() };
+        assert_eq!(tally_winner(&poll, &candidates, None).unwrap(), Some(leader.0));
+
+        poll.settings.win_rule = WIN_RULE_MAJORITY;
+        assert_eq!(tally_winner(&poll, &candidates, None).unwrap(), None);
+        // exactly half is still not a majority
+        assert!(!poll.win_rule_met(5));
+        assert!(poll.win_rule_met(6));
+    }
+
+    #[test]
+    fn polls_need_min_notice_before_opening() {
+        let now = 1_700_000_000;
+        assert!(has_notice(now + MIN_NOTICE_SECONDS, now));
+        assert!(has_notice(now + 2 * MIN_NOTICE_SECONDS, now));
+        assert!(!has_notice(now + MIN_NOTICE_SECONDS - 1, now));
+        assert!(!has_notice(now, now));
+    }
+
+    #[test]
+    fn quadratic_cost_charges_the_difference_of_squares() {
+        assert_eq!(quadratic_cost(0, 1), Some(1));
+        assert_eq!(quadratic_cost(0, 3), Some(9));
+        assert_eq!(quadratic_cost(3, 4), Some(40));
+        assert_eq!(quadratic_cost(0, 1 << 32), None);
+        assert_eq!(quadratic_cost(u64::MAX, 1), None);
+    }
+
+    #[test]
+    fn display_name_set_rejects_duplicates_and_overflow() {
+        let mut set = DisplayNameSet::default();
+        assert!(set.insert("Alice").is_ok());
+        assert!(set.insert("alice").is_ok());
+        assert_eq!(set.insert("Alice").unwrap_err(), Errors::DuplicateDisplayName.into());
+
+        for i in set.hashes.len()..MAX_DISPLAY_NAMES {
+            set.insert(&format!("Candidate {}", i)).unwrap();
+        }
+        assert_eq!(set.insert("One too many").unwrap_err(), Errors::TooManyDisplayNames.into());
+    }
+
+    #[test]
+    fn restricted_charset_rejects_lookalikes() {
+        let mut poll = Poll::default();
+        assert!(poll.new_candidate_seed_name("Аlice").is_ok());
+
+        poll.settings.restrict_candidate_charset = true;
+        assert_eq!(poll.new_candidate_seed_name("Alice Smith-Jones_2").unwrap(), "Alice Smith-Jones_2");
+        // Cyrillic "А"
+        assert!(matches!(poll.new_candidate_seed_name("Аlice"), Err(Errors::InvalidCandidateChars)));
+        assert!(matches!(poll.new_candidate_seed_name("Bob 🗳"), Err(Errors::InvalidCandidateChars)));
+        assert!(matches!(poll.new_candidate_seed_name("a.b"), Err(Errors::InvalidCandidateChars)));
+    }
+
+    #[test]
+    fn slot_hash_is_not_needed_without_a_tie() {
+        let poll = Poll {
+            settings: PollSettings {
+                tie_break: TIE_BREAK_SLOT_HASH,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let leader = candidate_with_votes(2);
+        let candidates = vec![candidate_with_votes(1), leader.clone()];
+        assert_eq!(tally_winner(&poll, &candidates, None).unwrap(), Some(leader.0));
+    }
 }
@@ -1,10 +1,14 @@
 import * as anchor from "@coral-xyz/anchor";
-import { Keypair, PublicKey } from "@solana/web3.js";
+import { createHash } from "crypto";
+import { Keypair, PublicKey, SYSVAR_SLOT_HASHES_PUBKEY } from "@solana/web3.js";
 import { BankrunProvider, startAnchor } from "anchor-bankrun";
+import { Clock } from "solana-bankrun";
 import { Voting } from "../target/types/voting";
+import { VoteHook } from "../target/types/vote_hook";
 
 const IDL = require("../target/idl/voting.json");
 const PROGRAM_ID = new PublicKey(IDL.address);
+const HOOK_IDL = require("../target/idl/vote_hook.json");
 
 describe("Voting", () => {
   let context;
@@ -20,6 +24,227 @@ describe("Voting", () => {
     );
   });
 
+  const pollAddress = (pollId: number) =>
+    PublicKey.findProgramAddressSync(
+      [new anchor.BN(pollId).toArrayLike(Buffer, "le", 8)],
+      votingProgram.programId,
+    )[0];
+
+  const candidateAddress = (pollId: number, name: string) =>
+    PublicKey.findProgramAddressSync(
+      [
+        new anchor.BN(pollId).toArrayLike(Buffer, "le", 8),
+        Buffer.from("::"),
+        Buffer.from(name),
+      ],
+      votingProgram.programId,
+    )[0];
+
+  const candidateAccounts = (pollId: number, names: string[]) =>
+    names.map((name) => ({
+      pubkey: candidateAddress(pollId, name),
+      isSigner: false,
+      isWritable: false,
+    }));
+
+  const chainNow = async () =>
+    Number((await context.banksClient.getClock()).unixTimestamp);
+
+  // Moves the chain clock and advances `slots` slots, so that otherwise identical
+  // transactions get a fresh blockhash instead of being deduplicated.
+  const warpTo = async (unixTimestamp: number, slots = 1) => {
+    const clock = await context.banksClient.getClock();
+    context.warpToSlot(clock.slot + BigInt(slots));
+    context.setClock(
+      new Clock(
+        clock.slot + BigInt(slots),
+        clock.epochStartTimestamp,
+        clock.epoch,
+        clock.leaderScheduleEpoch,
+        BigInt(unixTimestamp),
+      ),
+    );
+  };
+
+  // Candidate PDAs depend on the poll's name handling, so tests derive them and
+  // pass them explicitly instead of relying on anchor's account resolution.
+  const initializeCandidate = (
+    pollId: number,
+    name: string,
+    seedName = name,
+    displayName = "",
+    party = "",
+  ) =>
+    votingProgram.methods.initializeCandidate(name, new anchor.BN(pollId), displayName, party)
+      .accountsPartial({ candidate: candidateAddress(pollId, seedName) });
+
+  const vote = (pollId: number, name: string, seedName = name) =>
+    votingProgram.methods.vote(name, new anchor.BN(pollId), null, null)
+      .accountsPartial({ candidate: candidateAddress(pollId, seedName) });
+
+  const voteAs = (pollId: number, name: string, voter: Keypair) =>
+    votingProgram.methods.vote(name, new anchor.BN(pollId), null, null)
+      .accountsPartial({ candidate: candidateAddress(pollId, name), signer: voter.publicKey })
+      .signers([voter]);
+
+  const voterRecordAddress = (pollId: number, voter: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("voter"), new anchor.BN(pollId).toArrayLike(Buffer, "le", 8), voter.toBuffer()],
+      votingProgram.programId,
+    )[0];
+
+  // The five writable accounts `vote_many` takes per entry.
+  const batchAccounts = (pollId: number, name: string, voter: PublicKey) =>
+    [
+      pollAddress(pollId),
+      candidateAddress(pollId, name),
+      voterRecordAddress(pollId, voter),
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("allocation"), new anchor.BN(pollId).toArrayLike(Buffer, "le", 8), voter.toBuffer()],
+        votingProgram.programId,
+      )[0],
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("top_k"), new anchor.BN(pollId).toArrayLike(Buffer, "le", 8)],
+        votingProgram.programId,
+      )[0],
+    ].map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }));
+
+  // Gives a fresh keypair enough SOL to pay for its own voter accounts.
+  const fund = (address: PublicKey) =>
+    context.setAccount(address, {
+      lamports: 10 * anchor.web3.LAMPORTS_PER_SOL,
+      data: Buffer.alloc(0),
+      owner: anchor.web3.SystemProgram.programId,
+      executable: false,
+    });
+
+  const createPoll = async (pollId: number, pollStart: number, pollEnd: number, names: string[]) => {
+    await votingProgram.methods.initializePoll(
+      new anchor.BN(pollId),
+      `Poll ${pollId}`,
+      new anchor.BN(pollStart),
+      new anchor.BN(pollEnd),
+      noCallback,
+    ).rpc();
+    for (const name of names) {
+      await initializeCandidate(pollId, name).rpc();
+    }
+  };
+
+  // Votes `times` times from the provider wallet, advancing a slot before each
+  // vote so repeated identical transactions aren't deduplicated.
+  const castVotes = async (pollId: number, name: string, times = 1) => {
+    for (let i = 0; i < times; i++) {
+      await warpTo(await chainNow());
+      await vote(pollId, name).rpc();
+    }
+  };
+
+  // result_callback_hash for polls without a callback
+  const noCallback = new Array(32).fill(0);
+
+  const defaultSettings = () => ({
+    minWinningVotes: new anchor.BN(0),
+    approver: null as PublicKey | null,
+    caseInsensitiveNames: false,
+    votesPerVoter: new anchor.BN(0),
+    hookProgram: null as PublicKey | null,
+    requireRegistration: false,
+    weightDecayPerHour: new anchor.BN(0),
+    includeAbstentionsInTotal: false,
+    requireConfirmation: false,
+    anonymous: false,
+    requireIdentity: false,
+    tieBreak: 0,
+    disputeWindowSeconds: new anchor.BN(0),
+    totalEligibleWeight: new anchor.BN(0),
+    quorumBps: new anchor.BN(0),
+    votesPerCast: new anchor.BN(1),
+    verboseLogging: true,
+    requireVerifiedCandidates: false,
+    minContestedCandidates: new anchor.BN(0),
+    minAccountAgeSlots: new anchor.BN(0),
+    countingMode: 0,
+    restrictCandidateCharset: false,
+    requireUniqueDisplayNames: false,
+    creditsPerVoter: new anchor.BN(0),
+    autoFinalizeOnFullTurnout: false,
+    highPrecision: false,
+    blankVotesCountTowardQuorum: false,
+    maxVotesPerSlot: new anchor.BN(0),
+    winRule: 0,
+    dependsOn: null as anchor.BN | null,
+    requiredWinner: null as PublicKey | null,
+    gateMint: null as PublicKey | null,
+    tierWeights: [0, 0, 0, 0].map((weight) => new anchor.BN(weight)),
+    resultsMinVotes: new anchor.BN(0),
+    endorser: null as PublicKey | null,
+    allowedJurisdictions: [] as number[],
+    receiptCollection: null as PublicKey | null,
+  });
+
+  const updateSettings = (pollId: number, overrides: Partial<ReturnType<typeof defaultSettings>>) =>
+    votingProgram.methods.updatePollSettings(
+      new anchor.BN(pollId),
+      { ...defaultSettings(), ...overrides },
+    ).rpc();
+
+  // Sends the transaction built by `builder` and returns its log messages.
+  const transactionLogs = async (builder: any, signers: Keypair[] = []) => {
+    const tx = await builder.transaction();
+    tx.recentBlockhash = (await context.banksClient.getLatestBlockhash())[0];
+    tx.feePayer = provider.wallet.publicKey;
+    if (signers.length > 0) {
+      tx.partialSign(...signers);
+    }
+    const meta = await context.banksClient.processTransaction(
+      await provider.wallet.signTransaction(tx),
+    );
+    return meta.logMessages;
+  };
+
+  // Sends the transaction built by `builder` and returns the events it emitted.
+  const emittedEvents = async (builder: any, signers: Keypair[] = []) => {
+    const parser = new anchor.EventParser(
+      votingProgram.programId,
+      new anchor.BorshCoder(IDL),
+    );
+    return Array.from(parser.parseLogs(await transactionLogs(builder, signers)));
+  };
+
+  const emittedEvent = async (builder: any, name: string, signers: Keypair[] = []) => {
+    const events = await emittedEvents(builder, signers);
+    const event = events.find((e) => e.name.toLowerCase() === name.toLowerCase());
+    expect(event).toBeDefined();
+    return event!.data as any;
+  };
+
+  // Rewrites an account's data in place, e.g. to simulate on-chain corruption.
+  const patchAccount = async (
+    address: PublicKey,
+    accountName: string,
+    patch: (decoded: any) => void,
+  ) => {
+    const account = await context.banksClient.getAccount(address);
+    const decoded = votingProgram.coder.accounts.decode(accountName, Buffer.from(account.data));
+    patch(decoded);
+    const encoded = await votingProgram.coder.accounts.encode(accountName, decoded);
+    const data = Buffer.alloc(account.data.length);
+    encoded.copy(data);
+    context.setAccount(address, { ...account, data });
+  };
+
+  const expectError = async (promise: Promise<unknown>, message: string) => {
+    try {
+      await promise;
+    } catch (err: any) {
+      const logs: string[] = err.logs || [];
+      expect(logs.some((log) => log.includes(message))).toBe(true);
+      return;
+    }
+    throw new Error(`Expected transaction to fail with "${message}"`);
+  };
+
   it("initializes a poll", async () => {
     let poll_start = Math.floor(Date.now() / 1000) + 60;
     let poll_end = poll_start + 60 * 60 * 24;
@@ -29,6 +254,7 @@ describe("Voting", () => {
       "What is your favorite color?",
       new anchor.BN(poll_start),
       new anchor.BN(poll_end),
+      noCallback,
     ).rpc();
 
     const [pollAddress] = PublicKey.findProgramAddressSync(
@@ -52,6 +278,7 @@ describe("Voting", () => {
         "Invalid timestamp test",                      
         new anchor.BN(100),                            
         new anchor.BN(1739370789),                     
+        noCallback,
       ).rpc();
       // if the transaction does not throw, the test fails
       fail("Expected initializePoll to throw due to invalid timestamp");
@@ -69,14 +296,18 @@ describe("Voting", () => {
     await votingProgram.methods.initializeCandidate(
       "Pink",
       new anchor.BN(1),
-    ).rpc();
+      "",
+      "",
+    ).accountsPartial({ candidate: candidateAddress(1, "Pink") }).rpc();
     await votingProgram.methods.initializeCandidate(
       "Blue",
       new anchor.BN(1),
-    ).rpc();
+      "",
+      "",
+    ).accountsPartial({ candidate: candidateAddress(1, "Blue") }).rpc();
 
     const [pinkAddress] = PublicKey.findProgramAddressSync(
-      [new anchor.BN(1).toArrayLike(Buffer, "le", 8), Buffer.from("Pink")],
+      [new anchor.BN(1).toArrayLike(Buffer, "le", 8), Buffer.from("::"), Buffer.from("Pink")],
       votingProgram.programId,
     );
     const pinkCandidate = await votingProgram.account.candidate.fetch(pinkAddress);
@@ -85,7 +316,7 @@ describe("Voting", () => {
     expect(pinkCandidate.candidateName).toBe("Pink");
 
     const [blueAddress] = PublicKey.findProgramAddressSync(
-      [new anchor.BN(1).toArrayLike(Buffer, "le", 8), Buffer.from("Blue")],
+      [new anchor.BN(1).toArrayLike(Buffer, "le", 8), Buffer.from("::"), Buffer.from("Blue")],
       votingProgram.programId,
     );
     const blueCandidate = await votingProgram.account.candidate.fetch(blueAddress);
@@ -95,21 +326,29 @@ describe("Voting", () => {
   });
 
   it("vote candidates", async () => {
+    const { pollStart } = await votingProgram.account.poll.fetch(pollAddress(1));
+    await warpTo(Math.max(await chainNow(), pollStart.toNumber()));
     await votingProgram.methods.vote(
       "Pink",
       new anchor.BN(1),
-    ).rpc();
+      null,
+      null,
+    ).accountsPartial({ candidate: candidateAddress(1, "Pink") }).rpc();
     await votingProgram.methods.vote(
       "Blue",
       new anchor.BN(1),
-    ).rpc();
+      null,
+      null,
+    ).accountsPartial({ candidate: candidateAddress(1, "Blue") }).rpc();
     await votingProgram.methods.vote(
       "Pink",
       new anchor.BN(1),
-    ).rpc();
+      null,
+      null,
+    ).accountsPartial({ candidate: candidateAddress(1, "Pink") }).rpc();
 
     const [pinkAddress] = PublicKey.findProgramAddressSync(
-      [new anchor.BN(1).toArrayLike(Buffer, "le", 8), Buffer.from("Pink")],
+      [new anchor.BN(1).toArrayLike(Buffer, "le", 8), Buffer.from("::"), Buffer.from("Pink")],
       votingProgram.programId,
     );
     const pinkCandidate = await votingProgram.account.candidate.fetch(pinkAddress);
@@ -118,7 +357,7 @@ describe("Voting", () => {
     expect(pinkCandidate.candidateName).toBe("Pink");
 
     const [blueAddress] = PublicKey.findProgramAddressSync(
-      [new anchor.BN(1).toArrayLike(Buffer, "le", 8), Buffer.from("Blue")],
+      [new anchor.BN(1).toArrayLike(Buffer, "le", 8), Buffer.from("::"), Buffer.from("Blue")],
       votingProgram.programId,
     );
     const blueCandidate = await votingProgram.account.candidate.fetch(blueAddress);
@@ -126,4 +365,3651 @@ describe("Voting", () => {
     expect(blueCandidate.candidateVotes.toNumber()).toBe(1);
     expect(blueCandidate.candidateName).toBe("Blue");
   });
+
+  it("stores a verifiable vote receipt", async () => {
+    const voter = provider.wallet.publicKey;
+    const pollIdBytes = new anchor.BN(1).toArrayLike(Buffer, "le", 8);
+
+    const [pinkAddress] = PublicKey.findProgramAddressSync(
+      [pollIdBytes, Buffer.from("Pink")],
+      votingProgram.programId,
+    );
+    const [voterRecordAddress] = PublicKey.findProgramAddressSync(
+      [Buffer.from("voter"), pollIdBytes, voter.toBuffer()],
+      votingProgram.programId,
+    );
+
+    // the last vote cast by the provider wallet was for Pink
+    const voterRecord = await votingProgram.account.voterRecord.fetch(voterRecordAddress);
+    expect(voterRecord.voter.toBase58()).toBe(voter.toBase58());
+    expect(voterRecord.candidate.toBase58()).toBe(pinkAddress.toBase58());
+
+    const expectedHash = createHash("sha256")
+      .update(pollIdBytes)
+      .update(pinkAddress.toBuffer())
+      .update(voter.toBuffer())
+      .update(voterRecord.votedAt.toTwos(64).toArrayLike(Buffer, "le", 8))
+      .digest();
+    expect(Buffer.from(voterRecord.receiptHash)).toEqual(expectedHash);
+  });
+
+  it("reopens a finalized poll and accepts votes again", async () => {
+    const pollId = 3;
+    const now = await chainNow();
+
+    await votingProgram.methods.initializePoll(
+      new anchor.BN(pollId),
+      "Reopen test",
+      new anchor.BN(now + 10),
+      new anchor.BN(now + 100),
+      noCallback,
+    ).rpc();
+    await votingProgram.methods.initializeCandidate(
+      "Red",
+      new anchor.BN(pollId),
+      "",
+      "",
+    ).accountsPartial({ candidate: candidateAddress(pollId, "Red") }).rpc();
+    await warpTo(now + 20);
+    await votingProgram.methods.vote(
+      "Red",
+      new anchor.BN(pollId),
+      null,
+      null,
+    ).accountsPartial({ candidate: candidateAddress(pollId, "Red") }).rpc();
+
+    await warpTo(now + 101);
+    await votingProgram.methods.finalizePoll(new anchor.BN(pollId))
+      .remainingAccounts(candidateAccounts(pollId, ["Red"]))
+      .rpc();
+
+    let poll = await votingProgram.account.poll.fetch(pollAddress(pollId));
+    expect(poll.isFinalized).toBe(true);
+    expect(poll.winner.toBase58()).toBe(candidateAddress(pollId, "Red").toBase58());
+
+    await expectError(
+      vote(pollId, "Red").rpc(),
+      "Poll is finalized and no longer accepts votes",
+    );
+
+    await votingProgram.methods.reopenPoll(
+      new anchor.BN(pollId),
+      new anchor.BN(now + 1000),
+    ).rpc();
+
+    poll = await votingProgram.account.poll.fetch(pollAddress(pollId));
+    expect(poll.isFinalized).toBe(false);
+    expect(poll.winner).toBeNull();
+    expect(poll.pollEnd.toNumber()).toBe(now + 1000);
+
+    await warpTo(await chainNow());
+    await vote(pollId, "Red").rpc();
+    const red = await votingProgram.account.candidate.fetch(candidateAddress(pollId, "Red"));
+    expect(red.candidateVotes.toNumber()).toBe(2);
+  });
+
+  it("rejects reopening by a non-authority", async () => {
+    const outsider = Keypair.generate();
+    await expectError(
+      votingProgram.methods.reopenPoll(new anchor.BN(3), new anchor.BN(3_000_000_000))
+        .accountsPartial({ authority: outsider.publicKey })
+        .signers([outsider])
+        .rpc(),
+      "Only the poll authority can perform this action",
+    );
+  });
+
+  it("declares a winner only when the leader clears min_winning_votes", async () => {
+    const now = await chainNow();
+    await createPoll(4, now + 10, now + 100, ["Ash", "Elm"]);
+    await createPoll(5, now + 10, now + 100, ["Ash", "Elm"]);
+    await updateSettings(4, { minWinningVotes: new anchor.BN(3) });
+    await updateSettings(5, { minWinningVotes: new anchor.BN(4) });
+
+    await warpTo(now + 20);
+    await expectError(
+      updateSettings(4, { minWinningVotes: new anchor.BN(1) }),
+      "Poll has already started",
+    );
+
+    for (const pollId of [4, 5]) {
+      await castVotes(pollId, "Ash", 3);
+      await castVotes(pollId, "Elm", 1);
+    }
+
+    await warpTo(now + 101);
+    for (const pollId of [4, 5]) {
+      await votingProgram.methods.finalizePoll(new anchor.BN(pollId))
+        .remainingAccounts(candidateAccounts(pollId, ["Ash", "Elm"]))
+        .rpc();
+    }
+
+    const cleared = await votingProgram.account.poll.fetch(pollAddress(4));
+    expect(cleared.winner.toBase58()).toBe(candidateAddress(4, "Ash").toBase58());
+    expect(cleared.noWinner).toBe(false);
+
+    const missed = await votingProgram.account.poll.fetch(pollAddress(5));
+    expect(missed.winner).toBeNull();
+    expect(missed.noWinner).toBe(true);
+  });
+
+  it("logs the offending values when initialize_poll fails", async () => {
+    const now = await chainNow();
+
+    await expectError(
+      votingProgram.methods.initializePoll(
+        new anchor.BN(6), "Bad start", new anchor.BN(100), new anchor.BN(now + 100),
+        noCallback,
+      ).rpc(),
+      "Invalid poll_start 100 for poll 6",
+    );
+    await expectError(
+      votingProgram.methods.initializePoll(
+        new anchor.BN(6), "Ended", new anchor.BN(now - 100), new anchor.BN(now - 10),
+        noCallback,
+      ).rpc(),
+      `poll_end ${now - 10} is not after the current time ${now}`,
+    );
+    await expectError(
+      votingProgram.methods.initializePoll(
+        new anchor.BN(6), "Backwards", new anchor.BN(now + 200), new anchor.BN(now + 100),
+        noCallback,
+      ).rpc(),
+      `poll_start ${now + 200} is not before poll_end ${now + 100}`,
+    );
+  });
+
+  it("logs the poll and voter when voting on a finalized poll", async () => {
+    // poll 4 was finalized above
+    await expectError(
+      vote(4, "Ash").rpc(),
+      `Poll 4 is finalized, rejecting vote from ${provider.wallet.publicKey.toBase58()}`,
+    );
+  });
+
+  it("reports a candidate's vote share in basis points", async () => {
+    const now = await chainNow();
+    await createPoll(7, now + 10, now + 1000, ["Oak", "Fir"]);
+    await warpTo(now + 20);
+
+    let share = await emittedEvent(
+      votingProgram.methods.candidateShare("Oak", new anchor.BN(7))
+        .accountsPartial({ candidate: candidateAddress(7, "Oak") }),
+      "CandidateShare",
+    );
+    expect(share.shareBps.toNumber()).toBe(0);
+
+    await castVotes(7, "Oak", 1);
+    await castVotes(7, "Fir", 3);
+
+    share = await emittedEvent(
+      votingProgram.methods.candidateShare("Oak", new anchor.BN(7))
+        .accountsPartial({ candidate: candidateAddress(7, "Oak") }),
+      "CandidateShare",
+    );
+    expect(share.candidateVotes.toNumber()).toBe(1);
+    expect(share.totalVotes.toNumber()).toBe(4);
+    expect(share.shareBps.toNumber()).toBe(2500);
+  });
+
+  it("blocks voting until the approver approves the poll", async () => {
+    const approver = Keypair.generate();
+    const outsider = Keypair.generate();
+    const now = await chainNow();
+    await createPoll(8, now + 10, now + 1000, ["Yes", "No"]);
+
+    await expectError(
+      updateSettings(8, { approver: provider.wallet.publicKey }),
+      "Poll approver must be different from the poll authority",
+    );
+    await updateSettings(8, { approver: approver.publicKey });
+    await warpTo(now + 20);
+
+    await expectError(
+      vote(8, "Yes").rpc(),
+      "Poll has not been approved yet",
+    );
+
+    await expectError(
+      votingProgram.methods.approvePoll(new anchor.BN(8))
+        .accounts({ approver: outsider.publicKey })
+        .signers([outsider])
+        .rpc(),
+      "Only the poll approver can approve this poll",
+    );
+
+    await votingProgram.methods.approvePoll(new anchor.BN(8))
+      .accounts({ approver: approver.publicKey })
+      .signers([approver])
+      .rpc();
+    const poll = await votingProgram.account.poll.fetch(pollAddress(8));
+    expect(poll.approved).toBe(true);
+
+    await castVotes(8, "Yes");
+    const yes = await votingProgram.account.candidate.fetch(candidateAddress(8, "Yes"));
+    expect(yes.candidateVotes.toNumber()).toBe(1);
+  });
+
+  it("tracks each voter's allocation across candidates", async () => {
+    const now = await chainNow();
+    await createPoll(9, now + 10, now + 1000, ["Left", "Right"]);
+    await warpTo(now + 20);
+    await castVotes(9, "Left", 2);
+    await castVotes(9, "Right", 1);
+
+    const [allocationAddress] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("allocation"),
+        new anchor.BN(9).toArrayLike(Buffer, "le", 8),
+        provider.wallet.publicKey.toBuffer(),
+      ],
+      votingProgram.programId,
+    );
+    const allocation = await votingProgram.account.voterAllocation.fetch(allocationAddress);
+    const breakdown = allocation.allocations.map((entry) => [
+      entry.candidate.toBase58(),
+      entry.votes.toNumber(),
+    ]);
+    expect(breakdown).toEqual([
+      [candidateAddress(9, "Left").toBase58(), 2],
+      [candidateAddress(9, "Right").toBase58(), 1],
+    ]);
+  });
+
+  it("verifies poll integrity and pinpoints corruption", async () => {
+    const now = await chainNow();
+    await createPoll(10, now + 10, now + 1000, ["Cat", "Dog"]);
+    await warpTo(now + 20);
+    await castVotes(10, "Cat", 2);
+    await castVotes(10, "Dog", 1);
+
+    await votingProgram.methods.verifyPollIntegrity(new anchor.BN(10))
+      .remainingAccounts(candidateAccounts(10, ["Cat", "Dog"]))
+      .rpc();
+
+    await expectError(
+      votingProgram.methods.verifyPollIntegrity(new anchor.BN(10))
+        .remainingAccounts(candidateAccounts(10, ["Cat"]))
+        .rpc(),
+      "Provided candidates do not match the poll's candidate count",
+    );
+
+    await patchAccount(candidateAddress(10, "Dog"), "candidate", (dog) => {
+      dog.candidateVotes = new anchor.BN(5);
+    });
+    await warpTo(await chainNow());
+    await expectError(
+      votingProgram.methods.verifyPollIntegrity(new anchor.BN(10))
+        .remainingAccounts(candidateAccounts(10, ["Cat", "Dog"]))
+        .rpc(),
+      "Candidate votes do not add up to the poll's total votes",
+    );
+
+    await patchAccount(candidateAddress(10, "Dog"), "candidate", (dog) => {
+      dog.candidateVotes = new anchor.BN(1);
+      dog.poll = pollAddress(9);
+    });
+    await warpTo(await chainNow());
+    await expectError(
+      votingProgram.methods.verifyPollIntegrity(new anchor.BN(10))
+        .remainingAccounts(candidateAccounts(10, ["Cat", "Dog"]))
+        .rpc(),
+      "Candidate does not belong to this poll",
+    );
+  });
+
+  it("treats candidate names case-insensitively when configured", async () => {
+    const now = await chainNow();
+    await createPoll(11, now + 10, now + 1000, []);
+    await updateSettings(11, { caseInsensitiveNames: true });
+
+    await initializeCandidate(11, "Alice", "alice").rpc();
+    const alice = await votingProgram.account.candidate.fetch(candidateAddress(11, "alice"));
+    expect(alice.candidateName).toBe("alice");
+
+    await expectError(
+      initializeCandidate(11, "alice", "alice").rpc(),
+      "already in use",
+    );
+    await expectError(
+      updateSettings(11, { caseInsensitiveNames: false }),
+      "This setting can't change once candidates have been added",
+    );
+
+    await warpTo(now + 20);
+    await vote(11, "ALICE", "alice").rpc();
+    const voted = await votingProgram.account.candidate.fetch(candidateAddress(11, "alice"));
+    expect(voted.candidateVotes.toNumber()).toBe(1);
+  });
+
+  it("reports the caller's remaining vote budget", async () => {
+    const now = await chainNow();
+    await createPoll(12, now + 10, now + 1000, ["North", "South"]);
+    await updateSettings(12, { votesPerVoter: new anchor.BN(3) });
+    await warpTo(now + 20);
+
+    let budget = await emittedEvent(
+      votingProgram.methods.remainingVotes(new anchor.BN(12)),
+      "RemainingBudget",
+    );
+    expect(budget.remaining.toNumber()).toBe(3);
+
+    await castVotes(12, "North", 2);
+    budget = await emittedEvent(
+      votingProgram.methods.remainingVotes(new anchor.BN(12)),
+      "RemainingBudget",
+    );
+    expect(budget.voter.toBase58()).toBe(provider.wallet.publicKey.toBase58());
+    expect(budget.remaining.toNumber()).toBe(1);
+
+    await castVotes(12, "South", 1);
+    await warpTo(await chainNow());
+    await expectError(vote(12, "North").rpc(), "Voter has no votes left in this poll");
+  });
+
+  it("archives a finalized poll, shrinking it and rejecting votes", async () => {
+    const pollId = 13;
+    const now = await chainNow();
+    await createPoll(pollId, now + 10, now + 100, ["Gold"]);
+    await warpTo(now + 20);
+    await castVotes(pollId, "Gold");
+
+    await expectError(
+      votingProgram.methods.archivePoll(new anchor.BN(pollId)).rpc(),
+      "Poll has not been finalized",
+    );
+
+    await warpTo(now + 101);
+    await votingProgram.methods.finalizePoll(new anchor.BN(pollId))
+      .remainingAccounts(candidateAccounts(pollId, ["Gold"]))
+      .rpc();
+
+    const before = await context.banksClient.getAccount(pollAddress(pollId));
+    const authorityBefore = await context.banksClient.getBalance(provider.wallet.publicKey);
+    await votingProgram.methods.archivePoll(new anchor.BN(pollId)).rpc();
+    const after = await context.banksClient.getAccount(pollAddress(pollId));
+    const authorityAfter = await context.banksClient.getBalance(provider.wallet.publicKey);
+
+    // discriminator, poll_id, winner, total_votes and is_finalized
+    expect(after.data.length).toBe(8 + 8 + 33 + 8 + 1);
+    const rent = await context.banksClient.getRent();
+    const freed = BigInt(before.lamports) - BigInt(after.lamports);
+    expect(BigInt(after.lamports)).toBe(rent.minimumBalance(BigInt(after.data.length)));
+    expect(freed).toBe(
+      rent.minimumBalance(BigInt(before.data.length)) - rent.minimumBalance(BigInt(after.data.length)),
+    );
+    // the authority gets the freed rent, less the transaction fee
+    expect(authorityAfter - authorityBefore).toBeGreaterThan(freed - BigInt(10_000));
+
+    const archived = await votingProgram.account.archivedPoll.fetch(pollAddress(pollId));
+    expect(archived.pollId.toNumber()).toBe(pollId);
+    expect(archived.isFinalized).toBe(true);
+    expect(archived.totalVotes.toNumber()).toBe(1);
+    expect(archived.winner!.toBase58()).toBe(candidateAddress(pollId, "Gold").toBase58());
+
+    await expectError(vote(pollId, "Gold").rpc(), "AccountDiscriminatorMismatch");
+    await expectError(votingProgram.methods.archivePoll(new anchor.BN(pollId)).rpc(), "Poll is archived");
+  });
+
+  it("calls the configured hook program on every vote", async () => {
+    const hookProgram = new anchor.Program<VoteHook>(HOOK_IDL, provider);
+    const [hookState] = PublicKey.findProgramAddressSync(
+      [Buffer.from("hook_state")],
+      hookProgram.programId,
+    );
+    await hookProgram.methods.initialize().rpc();
+
+    const now = await chainNow();
+    await createPoll(14, now + 10, now + 1000, ["Hooked"]);
+    await expectError(
+      updateSettings(14, { hookProgram: votingProgram.programId }),
+      "Hook program is missing or does not match the poll's hook",
+    );
+    await updateSettings(14, { hookProgram: hookProgram.programId });
+    await warpTo(now + 20);
+
+    await expectError(
+      vote(14, "Hooked")
+        .remainingAccounts([
+          { pubkey: votingProgram.programId, isSigner: false, isWritable: false },
+          { pubkey: hookState, isSigner: false, isWritable: true },
+        ])
+        .rpc(),
+      "Hook program is missing or does not match the poll's hook",
+    );
+
+    await vote(14, "Hooked")
+      .remainingAccounts([
+        { pubkey: hookProgram.programId, isSigner: false, isWritable: false },
+        { pubkey: hookState, isSigner: false, isWritable: true },
+      ])
+      .rpc();
+
+    const state = await hookProgram.account.hookState.fetch(hookState);
+    expect(state.calls.toNumber()).toBe(1);
+    expect(state.lastPollId.toNumber()).toBe(14);
+    expect(state.lastCandidate.toBase58()).toBe(candidateAddress(14, "Hooked").toBase58());
+    expect(state.lastVoter.toBase58()).toBe(provider.wallet.publicKey.toBase58());
+  });
+
+  it("rejects votes for candidates added after the poll started", async () => {
+    const now = await chainNow();
+    await createPoll(15, now + 10, now + 1000, ["Early"]);
+    await warpTo(now + 20);
+    await initializeCandidate(15, "Late").rpc();
+
+    const late = await votingProgram.account.candidate.fetch(candidateAddress(15, "Late"));
+    expect(late.createdAt.toNumber()).toBe(now + 20);
+
+    await vote(15, "Early").rpc();
+    await expectError(
+      vote(15, "Late").rpc(),
+      "Candidate was added after the poll started",
+    );
+  });
+
+  it("settles ties with the latest slot hash", async () => {
+    const now = await chainNow();
+    await createPoll(16, now + 10, now + 100, ["Fir", "Oak", "Yew"]);
+    await expectError(updateSettings(16, { tieBreak: 7 }), "Unknown tie-break strategy");
+    await updateSettings(16, { tieBreak: 3 });
+    await warpTo(now + 20);
+    for (const name of ["Fir", "Oak", "Yew"]) {
+      await vote(16, name).rpc();
+    }
+    await warpTo(now + 200);
+
+    const finalize = () =>
+      votingProgram.methods.finalizePoll(new anchor.BN(16))
+        .remainingAccounts(candidateAccounts(16, ["Fir", "Oak", "Yew"]));
+    await expectError(
+      finalize().accountsPartial({ slotHashes: null }).rpc(),
+      "Tie-break needs the SlotHashes sysvar",
+    );
+
+    const slotHashes = await context.banksClient.getAccount(SYSVAR_SLOT_HASHES_PUBKEY);
+    const latestHash = Buffer.from(slotHashes.data.slice(16, 48));
+    await finalize().accountsPartial({ slotHashes: SYSVAR_SLOT_HASHES_PUBKEY }).rpc();
+
+    const tied = ["Fir", "Oak", "Yew"]
+      .map((name) => candidateAddress(16, name))
+      .sort((a, b) => Buffer.compare(a.toBuffer(), b.toBuffer()));
+    const index = Number(latestHash.readBigUInt64LE(0) % BigInt(tied.length));
+    const poll = await votingProgram.account.poll.fetch(pollAddress(16));
+    expect(poll.winner.toBase58()).toBe(tied[index].toBase58());
+  });
+
+  it("buckets votes by hour since poll_start", async () => {
+    const now = await chainNow();
+    const start = now + 10;
+    await createPoll(17, start, start + 48 * 3600, ["Hour"]);
+
+    await warpTo(start + 60);
+    await vote(17, "Hour").rpc();
+    await warpTo(start + 2 * 3600 + 5);
+    await vote(17, "Hour").rpc();
+    await warpTo(start + 2 * 3600 + 30);
+    await vote(17, "Hour").rpc();
+    await warpTo(start + 30 * 3600);
+    await vote(17, "Hour").rpc();
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(17));
+    const buckets = poll.voteBuckets.map((count) => count.toNumber());
+    expect(buckets).toHaveLength(24);
+    expect(buckets[0]).toBe(1);
+    expect(buckets[2]).toBe(2);
+    expect(buckets[23]).toBe(1);
+    expect(buckets.reduce((sum, count) => sum + count, 0)).toBe(poll.totalVotes.toNumber());
+  });
+
+  it("only accepts registered voters when registration is required", async () => {
+    const now = await chainNow();
+    await createPoll(18, now + 10, now + 1000, ["Listed"]);
+    await updateSettings(18, { requireRegistration: true });
+
+    const registered = Keypair.generate();
+    const stranger = Keypair.generate();
+    for (const voter of [registered, stranger]) {
+      fund(voter.publicKey);
+    }
+    await votingProgram.methods.registerVoter(new anchor.BN(18), registered.publicKey).rpc();
+    await warpTo(now + 20);
+
+    await expectError(
+      voteAs(18, "Listed", stranger).rpc(),
+      "Voter is not registered for this poll",
+    );
+    await voteAs(18, "Listed", registered).rpc();
+
+    const record = await votingProgram.account.voterRecord.fetch(voterRecordAddress(18, registered.publicKey));
+    expect(record.registered).toBe(true);
+    expect(record.votesUsed.toNumber()).toBe(1);
+  });
+
+  it("lets anyone vote when registration is not required", async () => {
+    const now = await chainNow();
+    await createPoll(19, now + 10, now + 1000, ["Open"]);
+    await warpTo(now + 20);
+
+    await vote(19, "Open").rpc();
+    const record = await votingProgram.account.voterRecord.fetch(
+      voterRecordAddress(19, provider.wallet.publicKey),
+    );
+    expect(record.registered).toBe(false);
+    expect(record.votesUsed.toNumber()).toBe(1);
+  });
+
+  it("opens a poll with a past start immediately", async () => {
+    const now = await chainNow();
+    await createPoll(20, now - 3600, now + 1000, ["Now"]);
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(20));
+    expect(poll.pollStart.toNumber()).toBe(now);
+
+    await vote(20, "Now").rpc();
+    const candidate = await votingProgram.account.candidate.fetch(candidateAddress(20, "Now"));
+    expect(candidate.candidateVotes.toNumber()).toBe(1);
+  });
+
+  it("rejects votes outside the poll window", async () => {
+    const now = await chainNow();
+    await createPoll(21, now + 10, now + 100, ["Window"]);
+    await expectError(vote(21, "Window").rpc(), "Poll has not started yet");
+
+    await warpTo(now + 101);
+    await expectError(vote(21, "Window").rpc(), "Poll has ended");
+  });
+
+  it("reports whether a poll is votable and why not", async () => {
+    const errorCode = (name: string) => IDL.errors.find((e: any) => e.name === name).code;
+    const votable = (pollId: number) =>
+      emittedEvent(votingProgram.methods.isVotable(new anchor.BN(pollId)), "PollVotable");
+
+    const now = await chainNow();
+    const approver = Keypair.generate();
+    await createPoll(22, now + 10, now + 100, []);
+
+    let status = await votable(22);
+    expect(status.votable).toBe(false);
+    expect(status.reason).toBe(errorCode("PollNotStarted"));
+
+    await updateSettings(22, { approver: approver.publicKey });
+    await warpTo(now + 20);
+    status = await votable(22);
+    expect(status.reason).toBe(errorCode("NoCandidates"));
+
+    await initializeCandidate(22, "Maybe").rpc();
+    status = await votable(22);
+    expect(status.reason).toBe(errorCode("PollNotApproved"));
+
+    await votingProgram.methods.approvePoll(new anchor.BN(22))
+      .accounts({ approver: approver.publicKey })
+      .signers([approver])
+      .rpc();
+    status = await votable(22);
+    expect(status.votable).toBe(true);
+    expect(status.reason).toBe(0);
+
+    await warpTo(now + 101);
+    status = await votable(22);
+    expect(status.votable).toBe(false);
+    expect(status.reason).toBe(errorCode("PollEnded"));
+  });
+
+  it("accepts anonymous votes once per nullifier", async () => {
+    const now = await chainNow();
+    await createPoll(23, now + 10, now + 1000, ["Hidden"]);
+    await updateSettings(23, { anonymous: true });
+    await warpTo(now + 20);
+
+    const voteAnonymous = (nullifier: Buffer) =>
+      votingProgram.methods.voteAnonymous("Hidden", new anchor.BN(23), [...nullifier])
+        .accountsPartial({ candidate: candidateAddress(23, "Hidden") });
+    const nullifier = createHash("sha256").update("voter secret").digest();
+
+    await expectError(vote(23, "Hidden").rpc(), "Poll does not accept this kind of vote");
+    await voteAnonymous(nullifier).rpc();
+    await warpTo(now + 21);
+    await expectError(voteAnonymous(nullifier).rpc(), "already in use");
+    await voteAnonymous(createHash("sha256").update("another secret").digest()).rpc();
+
+    const candidate = await votingProgram.account.candidate.fetch(candidateAddress(23, "Hidden"));
+    expect(candidate.candidateVotes.toNumber()).toBe(2);
+    const [recordAddress] = PublicKey.findProgramAddressSync(
+      [Buffer.from("nullifier"), new anchor.BN(23).toArrayLike(Buffer, "le", 8), nullifier],
+      votingProgram.programId,
+    );
+    const record = await votingProgram.account.nullifierRecord.fetch(recordAddress);
+    expect(Buffer.from(record.nullifier)).toEqual(nullifier);
+  });
+
+  it("adjusts the win threshold until a candidate reaches it", async () => {
+    const now = await chainNow();
+    const names = ["Swift", "Steady"];
+    await createPoll(24, now + 10, now + 1000, names);
+    await updateSettings(24, { minWinningVotes: new anchor.BN(3) });
+    await warpTo(now + 20);
+    await castVotes(24, "Swift", 2);
+
+    const updateThreshold = (threshold: number) =>
+      votingProgram.methods.updateWinThreshold(new anchor.BN(24), new anchor.BN(threshold))
+        .remainingAccounts(candidateAccounts(24, names))
+        .rpc();
+
+    await expectError(updateThreshold(1), "Win threshold can't be lower than a candidate's existing votes");
+    await updateThreshold(5);
+    let poll = await votingProgram.account.poll.fetch(pollAddress(24));
+    expect(poll.settings.minWinningVotes.toNumber()).toBe(5);
+
+    await warpTo(await chainNow());
+    await updateThreshold(2);
+    poll = await votingProgram.account.poll.fetch(pollAddress(24));
+    expect(poll.settings.minWinningVotes.toNumber()).toBe(2);
+
+    await warpTo(await chainNow());
+    await expectError(updateThreshold(4), "A candidate has already reached the win threshold");
+  });
+
+  it("serializes results in the canonical bridge format", async () => {
+    const now = await chainNow();
+    const names = ["Port", "Bay"];
+    await createPoll(25, now + 10, now + 1000, names);
+    await warpTo(now + 20);
+    await castVotes(25, "Port", 2);
+    await castVotes(25, "Bay", 1);
+
+    const event = await emittedEvent(
+      votingProgram.methods.serializeResults(new anchor.BN(25))
+        .remainingAccounts(candidateAccounts(25, [...names].reverse())),
+      "SerializedResults",
+    );
+
+    // in candidate index order, i.e. the order they were added
+    const tally = [
+      { address: candidateAddress(25, "Port"), votes: 2 },
+      { address: candidateAddress(25, "Bay"), votes: 1 },
+    ];
+    const expected = Buffer.concat([
+      new anchor.BN(25).toArrayLike(Buffer, "le", 8),
+      new anchor.BN(2).toArrayLike(Buffer, "le", 4),
+      ...tally.flatMap(({ address, votes }) => [
+        address.toBuffer(),
+        new anchor.BN(votes).toArrayLike(Buffer, "le", 8),
+      ]),
+    ]);
+
+    expect(Buffer.from(event.results)).toEqual(expected);
+    expect(Buffer.from(event.hash)).toEqual(createHash("sha256").update(expected).digest());
+  });
+
+  it("rejects a duplicate candidate name with a clear error", async () => {
+    const now = await chainNow();
+    await createPoll(26, now + 10, now + 1000, ["Twin"]);
+    await warpTo(await chainNow());
+
+    await expectError(
+      initializeCandidate(26, "Twin").rpc(),
+      "A candidate with this name already exists in the poll",
+    );
+    const poll = await votingProgram.account.poll.fetch(pollAddress(26));
+    expect(poll.candidateAmount.toNumber()).toBe(1);
+  });
+
+  describe("with require_confirmation", () => {
+    const confirmVote = (pollId: number, name: string) =>
+      votingProgram.methods.confirmVote(name, new anchor.BN(pollId))
+        .accountsPartial({ candidate: candidateAddress(pollId, name) });
+    const votesFor = async (pollId: number, name: string) =>
+      (await votingProgram.account.candidate.fetch(candidateAddress(pollId, name))).candidateVotes.toNumber();
+    const record = () =>
+      votingProgram.account.voterRecord.fetch(voterRecordAddress(27, provider.wallet.publicKey));
+
+    it("counts a vote only once it is confirmed", async () => {
+      const now = await chainNow();
+      await createPoll(27, now + 10, now + 1000, ["Sure", "Unsure"]);
+      await updateSettings(27, { requireConfirmation: true });
+      await warpTo(now + 20);
+
+      await vote(27, "Sure").rpc();
+      expect(await votesFor(27, "Sure")).toBe(0);
+      expect((await record()).confirmed).toBe(false);
+
+      await warpTo(await chainNow());
+      await expectError(vote(27, "Sure").rpc(), "Voter already has a pending vote in this poll");
+      await expectError(confirmVote(27, "Unsure").rpc(), "No matching pending vote to confirm or cancel");
+
+      await confirmVote(27, "Sure").rpc();
+      expect(await votesFor(27, "Sure")).toBe(1);
+      const confirmed = await record();
+      expect(confirmed.confirmed).toBe(true);
+      expect(confirmed.votesUsed.toNumber()).toBe(1);
+    });
+
+    it("backs out of a canceled pending vote", async () => {
+      await warpTo(await chainNow());
+      await vote(27, "Unsure").rpc();
+      await votingProgram.methods.cancelPendingVote(new anchor.BN(27)).rpc();
+
+      await warpTo(await chainNow());
+      await expectError(confirmVote(27, "Unsure").rpc(), "No matching pending vote to confirm or cancel");
+      await expectError(
+        votingProgram.methods.cancelPendingVote(new anchor.BN(27)).rpc(),
+        "No matching pending vote to confirm or cancel",
+      );
+      expect(await votesFor(27, "Unsure")).toBe(0);
+      expect((await record()).votesUsed.toNumber()).toBe(1);
+    });
+  });
+
+  it("validates description characters", async () => {
+    const now = await chainNow();
+    const initialize = (pollId: number, description: string) =>
+      votingProgram.methods.initializePoll(
+        new anchor.BN(pollId),
+        description,
+        new anchor.BN(now + 10),
+        new anchor.BN(now + 1000),
+        noCallback,
+      ).rpc();
+
+    await initialize(28, "Which colour should the logo be?");
+    await initialize(29, "Pick one:\nRed or blue");
+    await expectError(initialize(30, "Hidden\u0000text"), "Description contains control characters");
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(29));
+    expect(poll.description).toBe("Pick one:\nRed or blue");
+  });
+
+  it("records a candidate's first and last vote times", async () => {
+    const now = await chainNow();
+    await createPoll(31, now + 10, now + 1000, ["Rising", "Idle"]);
+    await warpTo(now + 20);
+    await vote(31, "Rising").rpc();
+    await warpTo(now + 500);
+    await vote(31, "Rising").rpc();
+
+    const rising = await votingProgram.account.candidate.fetch(candidateAddress(31, "Rising"));
+    expect(rising.firstVoteAt.toNumber()).toBe(now + 20);
+    expect(rising.lastVoteAt.toNumber()).toBe(now + 500);
+
+    const idle = await votingProgram.account.candidate.fetch(candidateAddress(31, "Idle"));
+    expect(idle.firstVoteAt.toNumber()).toBe(0);
+    expect(idle.lastVoteAt.toNumber()).toBe(0);
+  });
+
+  it("indexes every poll a voter takes part in", async () => {
+    const voter = Keypair.generate();
+    fund(voter.publicKey);
+    const now = await chainNow();
+    await createPoll(32, now + 10, now + 1000, ["First"]);
+    await createPoll(33, now + 10, now + 1000, ["Second"]);
+    await warpTo(now + 20);
+
+    await voteAs(32, "First", voter).rpc();
+    await voteAs(33, "Second", voter).rpc();
+    await warpTo(now + 21);
+    await voteAs(33, "Second", voter).rpc();
+
+    const [indexAddress] = PublicKey.findProgramAddressSync(
+      [Buffer.from("voter_index"), voter.publicKey.toBuffer()],
+      votingProgram.programId,
+    );
+    const index = await votingProgram.account.voterIndex.fetch(indexAddress);
+    expect(index.pollIds.map((id) => id.toNumber())).toEqual([32, 33]);
+  });
+
+  it("reads a page of candidates in one snapshot", async () => {
+    const now = await chainNow();
+    const names = ["Ada", "Bea", "Cy", "Dot"];
+    await createPoll(34, now + 10, now + 1000, names);
+    await warpTo(now + 20);
+    await castVotes(34, "Ada", 2);
+    await castVotes(34, "Cy", 1);
+
+    const snapshot = await emittedEvent(
+      votingProgram.methods.readCandidates(new anchor.BN(34))
+        .remainingAccounts(candidateAccounts(34, ["Ada", "Bea", "Cy"])),
+      "CandidateSnapshot",
+    );
+    expect(snapshot.pollId.toNumber()).toBe(34);
+    expect(snapshot.candidates.map((c: any) => [c.name, c.votes.toNumber()])).toEqual([
+      ["Ada", 2],
+      ["Bea", 0],
+      ["Cy", 1],
+    ]);
+
+    await expectError(
+      votingProgram.methods.readCandidates(new anchor.BN(34))
+        .remainingAccounts(candidateAccounts(33, ["Second"]))
+        .rpc(),
+      "Candidate does not belong to this poll",
+    );
+  });
+
+  it("decays vote weight with time since poll start", async () => {
+    const now = await chainNow();
+    const start = now + 10;
+    await createPoll(35, start, start + 24 * 3600, ["Early", "Late"]);
+    await updateSettings(35, { weightDecayPerHour: new anchor.BN(1000) });
+
+    await warpTo(start + 60);
+    await vote(35, "Early").rpc();
+    await warpTo(start + 3 * 3600 + 60);
+    await vote(35, "Late").rpc();
+
+    const early = await votingProgram.account.candidate.fetch(candidateAddress(35, "Early"));
+    const late = await votingProgram.account.candidate.fetch(candidateAddress(35, "Late"));
+    expect(early.weightedVotes.toNumber()).toBe(10000);
+    expect(late.weightedVotes.toNumber()).toBe(7000);
+    expect(late.candidateVotes.toNumber()).toBe(1);
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(35));
+    expect(poll.weightedTotal.toNumber()).toBe(17000);
+  });
+
+  it("rejects polls, candidates and votes while the program is paused", async () => {
+    const now = await chainNow();
+    await createPoll(36, now + 10, now + 1000, ["Calm"]);
+    await expectError(
+      votingProgram.methods.setProgramPaused(true).rpc(),
+      "AccountNotInitialized",
+    );
+    await votingProgram.methods.initializeConfig().rpc();
+    await warpTo(await chainNow());
+    await expectError(votingProgram.methods.initializeConfig().rpc(), "already in use");
+    const config = await votingProgram.account.programConfig.fetch(
+      PublicKey.findProgramAddressSync([Buffer.from("config")], votingProgram.programId)[0],
+    );
+    expect(config.initialized).toBe(true);
+
+    const outsider = Keypair.generate();
+    await expectError(
+      votingProgram.methods.setProgramPaused(true)
+        .accounts({ admin: outsider.publicKey })
+        .signers([outsider])
+        .rpc(),
+      "Only the program admin can perform this action",
+    );
+
+    await votingProgram.methods.setProgramPaused(true).rpc();
+    await warpTo(now + 20);
+    await expectError(
+      votingProgram.methods.initializePoll(
+        new anchor.BN(37),
+        "Paused",
+        new anchor.BN(now + 30),
+        new anchor.BN(now + 1000),
+        noCallback,
+      ).rpc(),
+      "Program is paused",
+    );
+    await expectError(initializeCandidate(36, "Storm").rpc(), "Program is paused");
+    await expectError(vote(36, "Calm").rpc(), "Program is paused");
+
+    await votingProgram.methods.setProgramPaused(false).rpc();
+    await vote(36, "Calm").rpc();
+    const calm = await votingProgram.account.candidate.fetch(candidateAddress(36, "Calm"));
+    expect(calm.candidateVotes.toNumber()).toBe(1);
+  });
+
+  it("rounds weighted tallies to whole display votes", async () => {
+    const now = await chainNow();
+    const start = now + 10;
+    await createPoll(38, start, start + 24 * 3600, ["Up", "Down"]);
+    await updateSettings(38, { weightDecayPerHour: new anchor.BN(2500) });
+
+    await warpTo(start + 60);
+    await vote(38, "Up").rpc();
+    await vote(38, "Down").rpc();
+    await warpTo(start + 3600 + 60);
+    await vote(38, "Up").rpc();
+    await warpTo(start + 3 * 3600 + 60);
+    await vote(38, "Down").rpc();
+
+    const display = (name: string) =>
+      emittedEvent(
+        votingProgram.methods.candidateDisplayVotes(name, new anchor.BN(38))
+          .accountsPartial({ candidate: candidateAddress(38, name) }),
+        "CandidateDisplayVotes",
+      );
+
+    // 10000 + 7500 bps = 1.75 votes
+    const up = await display("Up");
+    expect(up.weightedVotesBps.toNumber()).toBe(17500);
+    expect(up.displayVotes.toNumber()).toBe(2);
+
+    // 10000 + 2500 bps = 1.25 votes
+    const down = await display("Down");
+    expect(down.weightedVotesBps.toNumber()).toBe(12500);
+    expect(down.displayVotes.toNumber()).toBe(1);
+  });
+
+  it("only accepts new candidates until the poll ends", async () => {
+    const now = await chainNow();
+    await createPoll(39, now + 10, now + 100, ["Opener"]);
+    await warpTo(now + 50);
+    await initializeCandidate(39, "Midway").rpc();
+
+    await warpTo(now + 101);
+    await expectError(initializeCandidate(39, "Too Late").rpc(), "Poll has ended");
+    const poll = await votingProgram.account.poll.fetch(pollAddress(39));
+    expect(poll.candidateAmount.toNumber()).toBe(2);
+  });
+
+  it("clones a poll's candidates into a new poll with zeroed tallies", async () => {
+    const now = await chainNow();
+    const names = ["Incumbent", "Challenger"];
+    await createPoll(40, now + 10, now + 100, names);
+    await warpTo(now + 20);
+    await castVotes(40, "Incumbent", 2);
+    await createPoll(41, now + 200, now + 1000, []);
+
+    await votingProgram.methods.cloneCandidates(new anchor.BN(40), new anchor.BN(41))
+      .remainingAccounts(
+        names.flatMap((name) => [
+          { pubkey: candidateAddress(40, name), isSigner: false, isWritable: false },
+          { pubkey: candidateAddress(41, name), isSigner: false, isWritable: true },
+        ]),
+      )
+      .rpc();
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(41));
+    expect(poll.candidateAmount.toNumber()).toBe(2);
+    for (const name of names) {
+      const clone = await votingProgram.account.candidate.fetch(candidateAddress(41, name));
+      expect(clone.candidateName).toBe(name);
+      expect(clone.candidateVotes.toNumber()).toBe(0);
+      expect(clone.poll.toBase58()).toBe(pollAddress(41).toBase58());
+    }
+  });
+
+  it("commits finalized results to a Merkle root and verifies inclusion", async () => {
+    const now = await chainNow();
+    const names = ["Alpha", "Beta", "Gamma"];
+    await createPoll(42, now + 10, now + 100, names);
+    await warpTo(now + 20);
+    await castVotes(42, "Alpha", 3);
+    await castVotes(42, "Gamma", 1);
+
+    const commit = () =>
+      votingProgram.methods.computeResultsCommitment(new anchor.BN(42))
+        .remainingAccounts(candidateAccounts(42, names))
+        .rpc();
+    await expectError(commit(), "Poll has not been finalized");
+
+    await warpTo(now + 101);
+    await votingProgram.methods.finalizePoll(new anchor.BN(42))
+      .remainingAccounts(candidateAccounts(42, names))
+      .rpc();
+    await commit();
+
+    const sha256 = (...parts: Buffer[]) => createHash("sha256").update(Buffer.concat(parts)).digest();
+    const parent = (a: Buffer, b: Buffer) =>
+      Buffer.compare(a, b) <= 0 ? sha256(Buffer.from([1]), a, b) : sha256(Buffer.from([1]), b, a);
+    const votes: Record<string, number> = { Alpha: 3, Beta: 0, Gamma: 1 };
+    // leaves in candidate index order, i.e. the order the candidates were added
+    const leaves = names
+      .map((name) => ({ address: candidateAddress(42, name), votes: votes[name] }))
+      .map(({ address, votes }) => ({
+        address,
+        votes,
+        hash: sha256(Buffer.from([0]), address.toBuffer(), new anchor.BN(votes).toArrayLike(Buffer, "le", 8)),
+      }));
+    const root = parent(parent(leaves[0].hash, leaves[1].hash), leaves[2].hash);
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(42));
+    expect(Buffer.from(poll.resultsRoot)).toEqual(root);
+
+    const verify = (votes: number) =>
+      votingProgram.methods.verifyResultProof(
+        new anchor.BN(42),
+        leaves[0].address,
+        new anchor.BN(votes),
+        [[...leaves[1].hash], [...leaves[2].hash]],
+      ).rpc();
+    await verify(leaves[0].votes);
+    await expectError(verify(leaves[0].votes + 1), "Proof does not match the committed results");
+  });
+
+  it("ranks only a complete candidate set", async () => {
+    const now = await chainNow();
+    const names = ["Bronze", "Gold", "Silver"];
+    await createPoll(43, now + 10, now + 1000, names);
+    await warpTo(now + 20);
+    await castVotes(43, "Gold", 3);
+    await castVotes(43, "Silver", 2);
+
+    const rank = (included: string[]) =>
+      votingProgram.methods.rankCandidates(new anchor.BN(43))
+        .remainingAccounts(candidateAccounts(43, included));
+
+    await expectError(
+      rank(["Gold", "Silver"]).rpc(),
+      "Provided candidates do not match the poll's candidate count",
+    );
+
+    const { ranking } = await emittedEvent(rank(names), "CandidateRanking");
+    expect(ranking.map((c: any) => [c.name, c.votes.toNumber()])).toEqual([
+      ["Gold", 3],
+      ["Silver", 2],
+      ["Bronze", 0],
+    ]);
+  });
+
+  it("stores a long display name next to a short seed name", async () => {
+    const now = await chainNow();
+    await createPoll(44, now + 10, now + 1000, []);
+    const displayName = "Dr. Evangeline Montgomery-Okonkwo (Independent)";
+    await initializeCandidate(44, "evangeline", "evangeline", displayName).rpc();
+    await initializeCandidate(44, "plain").rpc();
+    await expectError(
+      initializeCandidate(44, "verbose", "verbose", "x".repeat(65)).rpc(),
+      "Candidate display name is longer than 64 bytes",
+    );
+
+    const candidate = await votingProgram.account.candidate.fetch(candidateAddress(44, "evangeline"));
+    expect(candidate.candidateName).toBe("evangeline");
+    expect(candidate.displayName).toBe(displayName);
+
+    const plain = await votingProgram.account.candidate.fetch(candidateAddress(44, "plain"));
+    expect(plain.displayName).toBe("plain");
+  });
+
+  it("simulates votes without changing any account", async () => {
+    const errorCode = (name: string) => IDL.errors.find((e: any) => e.name === name).code;
+    const simulate = (name: string) =>
+      emittedEvent(
+        votingProgram.methods.simulateVote(name, new anchor.BN(45))
+          .accountsPartial({ candidate: candidateAddress(45, name) }),
+        "VoteSimulation",
+      );
+
+    const now = await chainNow();
+    await createPoll(45, now + 10, now + 1000, ["Ready"]);
+    await updateSettings(45, { votesPerVoter: new anchor.BN(1) });
+
+    let outcome = await simulate("Ready");
+    expect(outcome.wouldSucceed).toBe(false);
+    expect(outcome.reasonCode).toBe(errorCode("PollNotStarted"));
+
+    await warpTo(now + 20);
+    await initializeCandidate(45, "Latecomer").rpc();
+    outcome = await simulate("Latecomer");
+    expect(outcome.reasonCode).toBe(errorCode("CandidateAddedAfterStart"));
+
+    outcome = await simulate("Ready");
+    expect(outcome.wouldSucceed).toBe(true);
+    expect(outcome.reasonCode).toBe(0);
+    const record = await context.banksClient.getAccount(voterRecordAddress(45, provider.wallet.publicKey));
+    expect(record).toBeNull();
+    const ready = await votingProgram.account.candidate.fetch(candidateAddress(45, "Ready"));
+    expect(ready.candidateVotes.toNumber()).toBe(0);
+
+    await vote(45, "Ready").rpc();
+    await warpTo(now + 21);
+    outcome = await simulate("Ready");
+    expect(outcome.wouldSucceed).toBe(false);
+    expect(outcome.reasonCode).toBe(errorCode("VoteBudgetExhausted"));
+  });
+
+  it("counts abstentions in total_votes only when configured", async () => {
+    const now = await chainNow();
+    await createPoll(46, now + 10, now + 1000, ["Choice"]);
+    await createPoll(47, now + 10, now + 1000, ["Choice"]);
+    await updateSettings(47, { includeAbstentionsInTotal: true });
+    await warpTo(now + 20);
+
+    for (const pollId of [46, 47]) {
+      await vote(pollId, "Choice").rpc();
+      await votingProgram.methods.voteAbstain(new anchor.BN(pollId)).rpc();
+      await votingProgram.methods.verifyPollIntegrity(new anchor.BN(pollId))
+        .remainingAccounts(candidateAccounts(pollId, ["Choice"]))
+        .rpc();
+    }
+
+    const excluded = await votingProgram.account.poll.fetch(pollAddress(46));
+    expect(excluded.totalVotes.toNumber()).toBe(1);
+    expect(excluded.abstentions.toNumber()).toBe(1);
+    expect(excluded.uniqueVoters.toNumber()).toBe(1);
+
+    const included = await votingProgram.account.poll.fetch(pollAddress(47));
+    expect(included.totalVotes.toNumber()).toBe(2);
+    expect(included.abstentions.toNumber()).toBe(1);
+
+    const share = await emittedEvent(
+      votingProgram.methods.candidateShare("Choice", new anchor.BN(47))
+        .accountsPartial({ candidate: candidateAddress(47, "Choice") }),
+      "CandidateShare",
+    );
+    expect(share.shareBps.toNumber()).toBe(5000);
+  });
+
+  it("derives candidate addresses away from the poll address", async () => {
+    const now = await chainNow();
+    await createPoll(48, now + 10, now + 1000, []);
+
+    const legacyAddress = PublicKey.findProgramAddressSync(
+      [new anchor.BN(48).toArrayLike(Buffer, "le", 8), Buffer.from("")],
+      votingProgram.programId,
+    )[0];
+    expect(legacyAddress.equals(pollAddress(48))).toBe(true);
+    expect(candidateAddress(48, "").equals(pollAddress(48))).toBe(false);
+
+    await initializeCandidate(48, "").rpc();
+    const candidate = await votingProgram.account.candidate.fetch(candidateAddress(48, ""));
+    expect(candidate.poll.equals(pollAddress(48))).toBe(true);
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(48));
+    expect(poll.candidateSeedVersion).toBe(1);
+    expect(poll.candidateAmount.toNumber()).toBe(1);
+  });
+
+  it("transfers candidate authority", async () => {
+    const now = await chainNow();
+    await createPoll(49, now + 10, now + 1000, ["Maple"]);
+    const organizer = Keypair.generate();
+    const outsider = Keypair.generate();
+    await fund(organizer.publicKey);
+    await fund(outsider.publicKey);
+
+    let candidate = await votingProgram.account.candidate.fetch(candidateAddress(49, "Maple"));
+    expect(candidate.candidateAuthority.equals(provider.wallet.publicKey)).toBe(true);
+
+    await votingProgram.methods
+      .transferCandidateAuthority("Maple", new anchor.BN(49), organizer.publicKey)
+      .accountsPartial({ candidate: candidateAddress(49, "Maple") })
+      .rpc();
+    candidate = await votingProgram.account.candidate.fetch(candidateAddress(49, "Maple"));
+    expect(candidate.candidateAuthority.equals(organizer.publicKey)).toBe(true);
+
+    await expectError(
+      votingProgram.methods
+        .transferCandidateAuthority("Maple", new anchor.BN(49), outsider.publicKey)
+        .accountsPartial({ candidate: candidateAddress(49, "Maple"), signer: outsider.publicKey })
+        .signers([outsider])
+        .rpc(),
+      "Unauthorized",
+    );
+
+    await votingProgram.methods
+      .transferCandidateAuthority("Maple", new anchor.BN(49), outsider.publicKey)
+      .accountsPartial({ candidate: candidateAddress(49, "Maple"), signer: organizer.publicKey })
+      .signers([organizer])
+      .rpc();
+    candidate = await votingProgram.account.candidate.fetch(candidateAddress(49, "Maple"));
+    expect(candidate.candidateAuthority.equals(outsider.publicKey)).toBe(true);
+  });
+
+  it("keeps a live top-5 leaderboard", async () => {
+    const now = await chainNow();
+    const names = ["A", "B", "C", "D", "E", "F"];
+    await createPoll(50, now + 10, now + 1000, names);
+    await warpTo(now + 20);
+
+    // F gets one vote, then A..E get 2..6 votes
+    await castVotes(50, "F", 1);
+    for (const [i, name] of names.slice(0, 5).entries()) {
+      await castVotes(50, name, i + 2);
+    }
+
+    const [topKAddress] = PublicKey.findProgramAddressSync(
+      [Buffer.from("top_k"), new anchor.BN(50).toArrayLike(Buffer, "le", 8)],
+      votingProgram.programId,
+    );
+    let topK = await votingProgram.account.topK.fetch(topKAddress);
+    expect(topK.entries.map((e) => e.candidate.toBase58())).toEqual(
+      ["E", "D", "C", "B", "A"].map((name) => candidateAddress(50, name).toBase58()),
+    );
+    expect(topK.entries.map((e) => e.votes.toNumber())).toEqual([6, 5, 4, 3, 2]);
+
+    // B overtakes everyone without appearing twice
+    await castVotes(50, "B", 4);
+    topK = await votingProgram.account.topK.fetch(topKAddress);
+    expect(topK.entries.map((e) => e.candidate.toBase58())).toEqual(
+      ["B", "E", "D", "C", "A"].map((name) => candidateAddress(50, name).toBase58()),
+    );
+    expect(topK.entries[0].votes.toNumber()).toBe(7);
+  });
+
+  it("reports a voter who can't afford their voter record", async () => {
+    const now = await chainNow();
+    await createPoll(51, now + 10, now + 1000, ["Cedar"]);
+    await warpTo(now + 20);
+
+    const voter = Keypair.generate();
+    const rent = await context.banksClient.getRent();
+    // 8-byte discriminator + VoterRecord::INIT_SPACE
+    const recordRent = rent.minimumBalance(BigInt(8 + 146));
+    context.setAccount(voter.publicKey, {
+      lamports: Number(recordRent) - 1,
+      data: Buffer.alloc(0),
+      owner: anchor.web3.SystemProgram.programId,
+      executable: false,
+    });
+
+    const outcome = await emittedEvent(
+      votingProgram.methods.simulateVote("Cedar", new anchor.BN(51))
+        .accountsPartial({ candidate: candidateAddress(51, "Cedar"), signer: voter.publicKey }),
+      "VoteSimulation",
+      [voter],
+    );
+    expect(outcome.wouldSucceed).toBe(false);
+    const code = IDL.errors.find((e: any) => e.name === "InsufficientRent").code;
+    expect(outcome.reasonCode).toBe(code);
+
+    await fund(voter.publicKey);
+    const funded = await emittedEvent(
+      votingProgram.methods.simulateVote("Cedar", new anchor.BN(51))
+        .accountsPartial({ candidate: candidateAddress(51, "Cedar"), signer: voter.publicKey }),
+      "VoteSimulation",
+      [voter],
+    );
+    expect(funded.wouldSucceed).toBe(true);
+  });
+
+  it("records authority actions in the poll's audit log", async () => {
+    const now = await chainNow();
+    await createPoll(52, now + 10, now + 20, ["Elm"]);
+    await updateSettings(52, { minWinningVotes: new anchor.BN(1) });
+    await warpTo(now + 30);
+    await votingProgram.methods.finalizePoll(new anchor.BN(52))
+      .remainingAccounts(candidateAccounts(52, ["Elm"]))
+      .rpc();
+
+    const [auditLogAddress] = PublicKey.findProgramAddressSync(
+      [Buffer.from("audit_log"), new anchor.BN(52).toArrayLike(Buffer, "le", 8)],
+      votingProgram.programId,
+    );
+    const auditLog = await votingProgram.account.auditLog.fetch(auditLogAddress);
+    expect(auditLog.poll.equals(pollAddress(52))).toBe(true);
+    // AUDIT_UPDATE_SETTINGS, then AUDIT_FINALIZE
+    expect(auditLog.entries.map((e) => e.actionCode)).toEqual([0, 4]);
+    expect(auditLog.entries.every((e) => e.actor.equals(provider.wallet.publicKey))).toBe(true);
+    expect(auditLog.entries[1].timestamp.toNumber()).toBe(now + 30);
+  });
+
+  it("links a poll's candidates into a list", async () => {
+    const now = await chainNow();
+    await createPoll(53, now + 10, now + 1000, ["First", "Second", "Third"]);
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(53));
+    const names: string[] = [];
+    let next: PublicKey | null = poll.candidateHead;
+    while (next) {
+      const candidate = await votingProgram.account.candidate.fetch(next);
+      names.push(candidate.candidateName);
+      next = candidate.nextCandidate;
+    }
+    expect(names).toEqual(["Third", "Second", "First"]);
+    expect(poll.candidateHead!.equals(candidateAddress(53, "Third"))).toBe(true);
+  });
+
+  it("needs a share of the eligible weight to pick a winner", async () => {
+    const now = await chainNow();
+    for (const pollId of [54, 55]) {
+      await createPoll(pollId, now + 10, now + 100, ["Yes"]);
+      await updateSettings(pollId, {
+        totalEligibleWeight: new anchor.BN(4),
+        quorumBps: new anchor.BN(5000),
+      });
+    }
+    await warpTo(now + 20);
+    await castVotes(54, "Yes", 2);
+    await castVotes(55, "Yes", 1);
+    await warpTo(now + 200);
+
+    for (const pollId of [54, 55]) {
+      await votingProgram.methods.finalizePoll(new anchor.BN(pollId))
+        .remainingAccounts(candidateAccounts(pollId, ["Yes"]))
+        .rpc();
+    }
+
+    const met = await votingProgram.account.poll.fetch(pollAddress(54));
+    expect(met.winner!.equals(candidateAddress(54, "Yes"))).toBe(true);
+
+    const missed = await votingProgram.account.poll.fetch(pollAddress(55));
+    expect(missed.winner).toBeNull();
+    expect(missed.noWinner).toBe(true);
+
+    const later = await chainNow();
+    await createPoll(56, later + 10, later + 100, []);
+    await expectError(
+      updateSettings(56, { totalEligibleWeight: new anchor.BN(0), quorumBps: new anchor.BN(1) }),
+      "InvalidQuorum",
+    );
+  });
+
+  it("reschedules a poll only before it starts", async () => {
+    const now = await chainNow();
+    await createPoll(57, now + 100, now + 200, ["Birch"]);
+
+    const rescheduled = await emittedEvent(
+      votingProgram.methods.reschedulePoll(
+        new anchor.BN(57), new anchor.BN(now + 50), new anchor.BN(now + 500),
+      ),
+      "PollRescheduled",
+    );
+    expect(rescheduled.pollStart.toNumber()).toBe(now + 50);
+    expect(rescheduled.pollEnd.toNumber()).toBe(now + 500);
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(57));
+    expect(poll.pollStart.toNumber()).toBe(now + 50);
+    expect(poll.pollEnd.toNumber()).toBe(now + 500);
+
+    await expectError(
+      votingProgram.methods.reschedulePoll(
+        new anchor.BN(57), new anchor.BN(now + 400), new anchor.BN(now + 300),
+      ).rpc(),
+      "InvalidEndDate",
+    );
+
+    await warpTo(now + 60);
+    await expectError(
+      votingProgram.methods.reschedulePoll(
+        new anchor.BN(57), new anchor.BN(now + 100), new anchor.BN(now + 600),
+      ).rpc(),
+      "PollAlreadyStarted",
+    );
+  });
+
+  it("lets wallets linked to one identity vote only once between them", async () => {
+    const now = await chainNow();
+    await createPoll(58, now + 10, now + 1000, ["Linden"]);
+    await updateSettings(58, { requireIdentity: true, votesPerVoter: new anchor.BN(1) });
+
+    const identityHash = Array.from(Buffer.alloc(32, 7));
+    const wallets = [Keypair.generate(), Keypair.generate()];
+    for (const wallet of wallets) {
+      await fund(wallet.publicKey);
+      await votingProgram.methods.registerIdentity(identityHash, wallet.publicKey).rpc();
+    }
+    await warpTo(now + 20);
+
+    const voteWithIdentity = (wallet: Keypair) =>
+      votingProgram.methods.voteWithIdentity("Linden", new anchor.BN(58))
+        .accountsPartial({ candidate: candidateAddress(58, "Linden"), signer: wallet.publicKey })
+        .signers([wallet])
+        .rpc();
+
+    await expectError(voteAs(58, "Linden", wallets[0]).rpc(), "VotingModeMismatch");
+    await voteWithIdentity(wallets[0]);
+    await expectError(voteWithIdentity(wallets[1]), "VoteBudgetExhausted");
+
+    const candidate = await votingProgram.account.candidate.fetch(candidateAddress(58, "Linden"));
+    expect(candidate.candidateVotes.toNumber()).toBe(1);
+    const [recordAddress] = PublicKey.findProgramAddressSync(
+      [Buffer.from("voter"), new anchor.BN(58).toArrayLike(Buffer, "le", 8), Buffer.from(identityHash)],
+      votingProgram.programId,
+    );
+    const record = await votingProgram.account.voterRecord.fetch(recordAddress);
+    expect(record.voter.equals(wallets[0].publicKey)).toBe(true);
+  });
+
+  it("allows adjusting votes only during the dispute window", async () => {
+    const now = await chainNow();
+    await createPoll(59, now + 10, now + 100, ["North", "South"]);
+    await updateSettings(59, { disputeWindowSeconds: new anchor.BN(600) });
+    await warpTo(now + 20);
+    await castVotes(59, "North", 2);
+    await castVotes(59, "South", 1);
+    await warpTo(now + 200);
+    await votingProgram.methods.finalizePoll(new anchor.BN(59))
+      .remainingAccounts(candidateAccounts(59, ["North", "South"]))
+      .rpc();
+
+    const adjust = (votes: number) =>
+      votingProgram.methods.adjustVotes(new anchor.BN(59), candidateAddress(59, "South"), new anchor.BN(votes))
+        .remainingAccounts(
+          candidateAccounts(59, ["North", "South"]).map((meta) => ({ ...meta, isWritable: true })),
+        )
+        .rpc();
+
+    await expectError(votingProgram.methods.archivePoll(new anchor.BN(59)).rpc(), "DisputeWindowOpen");
+
+    await adjust(5);
+    const poll = await votingProgram.account.poll.fetch(pollAddress(59));
+    expect(poll.totalVotes.toNumber()).toBe(7);
+    expect(poll.winner!.equals(candidateAddress(59, "South"))).toBe(true);
+    const south = await votingProgram.account.candidate.fetch(candidateAddress(59, "South"));
+    expect(south.candidateVotes.toNumber()).toBe(5);
+
+    await warpTo(now + 800);
+    await expectError(adjust(0), "DisputeWindowClosed");
+    await expectError(
+      votingProgram.methods.reopenPoll(new anchor.BN(59), new anchor.BN(now + 5000)).rpc(),
+      "DisputeWindowClosed",
+    );
+    await votingProgram.methods.archivePoll(new anchor.BN(59)).rpc();
+  });
+
+  it("reports turnout against the registered electorate", async () => {
+    const now = await chainNow();
+    await createPoll(60, now + 10, now + 1000, ["Aspen"]);
+    await updateSettings(60, { requireRegistration: true });
+
+    const turnout = () =>
+      emittedEvent(votingProgram.methods.turnout(new anchor.BN(60)), "Turnout");
+    expect((await turnout()).turnoutBps.toNumber()).toBe(0);
+
+    const voters = [Keypair.generate(), Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    for (const voter of voters) {
+      await fund(voter.publicKey);
+      await votingProgram.methods.registerVoter(new anchor.BN(60), voter.publicKey).rpc();
+    }
+    await warpTo(now + 20);
+    await voteAs(60, "Aspen", voters[0]).rpc();
+
+    const result = await turnout();
+    expect(result.registeredVoterCount.toNumber()).toBe(4);
+    expect(result.uniqueVoters.toNumber()).toBe(1);
+    expect(result.turnoutBps.toNumber()).toBe(2500);
+  });
+
+  it("rejects candidates once the ballot is locked", async () => {
+    const now = await chainNow();
+    await createPoll(61, now + 100, now + 1000, ["Early"]);
+    await votingProgram.methods.lockCandidates(new anchor.BN(61)).rpc();
+
+    await expectError(initializeCandidate(61, "Late").rpc(), "CandidatesLocked");
+    const poll = await votingProgram.account.poll.fetch(pollAddress(61));
+    expect(poll.candidatesLocked).toBe(true);
+    expect(poll.candidateAmount.toNumber()).toBe(1);
+  });
+
+  it("rejects a voter record that points at another poll", async () => {
+    const now = await chainNow();
+    await createPoll(62, now + 10, now + 1000, ["Hazel"]);
+    await warpTo(now + 20);
+    await castVotes(62, "Hazel", 1);
+
+    await patchAccount(voterRecordAddress(62, provider.wallet.publicKey), "voterRecord", (record) => {
+      record.poll = pollAddress(61);
+    });
+    await warpTo(now + 21);
+    await expectError(vote(62, "Hazel").rpc(), "VoterPollMismatch");
+  });
+
+  it("counts each cast as votes_per_cast votes", async () => {
+    const now = await chainNow();
+    await createPoll(63, now + 10, now + 1000, ["Unit"]);
+    await createPoll(64, now + 10, now + 1000, ["Block"]);
+    await updateSettings(64, { votesPerCast: new anchor.BN(10) });
+    await expectError(updateSettings(63, { votesPerCast: new anchor.BN(0) }), "InvalidVotesPerCast");
+    await warpTo(now + 20);
+
+    await castVotes(63, "Unit", 2);
+    await castVotes(64, "Block", 2);
+
+    const unit = await votingProgram.account.candidate.fetch(candidateAddress(63, "Unit"));
+    expect(unit.candidateVotes.toNumber()).toBe(2);
+    const block = await votingProgram.account.candidate.fetch(candidateAddress(64, "Block"));
+    expect(block.candidateVotes.toNumber()).toBe(20);
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(64));
+    expect(poll.totalVotes.toNumber()).toBe(20);
+    expect(poll.weightedTotal.toNumber()).toBe(20 * 10_000);
+  });
+
+  it("reports countdowns to a poll's start and end", async () => {
+    const now = await chainNow();
+    await createPoll(65, now + 100, now + 300, []);
+    const timing = () =>
+      emittedEvent(votingProgram.methods.timeRemaining(new anchor.BN(65)), "PollTiming");
+
+    let result = await timing();
+    expect(result.status).toBe(0);
+    expect(result.secondsUntilStart.toNumber()).toBe(100);
+    expect(result.secondsUntilEnd.toNumber()).toBe(300);
+
+    await warpTo(now + 150);
+    result = await timing();
+    expect(result.status).toBe(1);
+    expect(result.secondsUntilStart.toNumber()).toBe(0);
+    expect(result.secondsUntilEnd.toNumber()).toBe(150);
+
+    await warpTo(now + 400);
+    result = await timing();
+    expect(result.status).toBe(2);
+    expect(result.secondsUntilEnd.toNumber()).toBe(0);
+  });
+
+  it("casts votes in several polls at once, all or nothing", async () => {
+    const now = await chainNow();
+    await createPoll(66, now + 10, now + 1000, ["Rowan"]);
+    await createPoll(67, now + 10, now + 1000, ["Willow"]);
+    await updateSettings(67, { votesPerVoter: new anchor.BN(1) });
+    await warpTo(now + 20);
+
+    const batch = (entries: [number, string][]) =>
+      votingProgram.methods
+        .voteMany(entries.map(([pollId, candidateName]) => ({ pollId: new anchor.BN(pollId), candidateName })))
+        .remainingAccounts(
+          entries.flatMap(([pollId, name]) => batchAccounts(pollId, name, provider.wallet.publicKey)),
+        )
+        .rpc();
+
+    await batch([[66, "Rowan"], [67, "Willow"]]);
+    const rowan = await votingProgram.account.candidate.fetch(candidateAddress(66, "Rowan"));
+    const willow = await votingProgram.account.candidate.fetch(candidateAddress(67, "Willow"));
+    expect(rowan.candidateVotes.toNumber()).toBe(1);
+    expect(willow.candidateVotes.toNumber()).toBe(1);
+
+    // poll 67 allows one vote per voter, so the second batch fails as a whole
+    await warpTo(now + 21);
+    await expectError(batch([[66, "Rowan"], [67, "Willow"]]), "VoteBudgetExhausted");
+    const rowanAfter = await votingProgram.account.candidate.fetch(candidateAddress(66, "Rowan"));
+    expect(rowanAfter.candidateVotes.toNumber()).toBe(1);
+    const poll = await votingProgram.account.poll.fetch(pollAddress(66));
+    expect(poll.totalVotes.toNumber()).toBe(1);
+  });
+
+  it("breaks down results for every candidate at once", async () => {
+    const now = await chainNow();
+    const names = ["Red", "Green", "Blue"];
+    await createPoll(68, now + 10, now + 1000, names);
+
+    const breakdown = () =>
+      emittedEvent(
+        votingProgram.methods.resultsBreakdown(new anchor.BN(68))
+          .remainingAccounts(candidateAccounts(68, names)),
+        "ResultsBreakdown",
+      );
+    let result = await breakdown();
+    expect(result.shareBps.map((bps) => bps.toNumber())).toEqual([0, 0, 0]);
+
+    await warpTo(now + 20);
+    await castVotes(68, "Red", 1);
+    await castVotes(68, "Green", 1);
+    await castVotes(68, "Blue", 1);
+
+    result = await breakdown();
+    expect(result.totalVotes.toNumber()).toBe(3);
+    expect(result.candidates.map((key) => key.toBase58())).toEqual(
+      names.map((name) => candidateAddress(68, name).toBase58()),
+    );
+    expect(result.votes.map((votes) => votes.toNumber())).toEqual([1, 1, 1]);
+    const shares = result.shareBps.map((bps) => bps.toNumber());
+    const sum = shares.reduce((a, b) => a + b, 0);
+    expect(sum).toBeLessThanOrEqual(10_000);
+    expect(sum).toBeGreaterThan(10_000 - names.length);
+  });
+
+  it("stores finalized results in one account", async () => {
+    const now = await chainNow();
+    const names = ["Plum", "Pear"];
+    await createPoll(69, now + 10, now + 100, names);
+    await warpTo(now + 20);
+    await castVotes(69, "Plum", 2);
+    await castVotes(69, "Pear", 1);
+    await warpTo(now + 200);
+    await votingProgram.methods.finalizePoll(new anchor.BN(69))
+      .remainingAccounts(candidateAccounts(69, names))
+      .rpc();
+
+    const [resultsAddress] = PublicKey.findProgramAddressSync(
+      [Buffer.from("results"), new anchor.BN(69).toArrayLike(Buffer, "le", 8)],
+      votingProgram.programId,
+    );
+    const results = await votingProgram.account.pollResults.fetch(resultsAddress);
+    expect(results.pollId.toNumber()).toBe(69);
+    expect(results.totalVotes.toNumber()).toBe(3);
+    expect(results.uniqueVoters.toNumber()).toBe(1);
+    expect(results.winner!.equals(candidateAddress(69, "Plum"))).toBe(true);
+
+    const expected = names
+      .map((name) => ({ key: candidateAddress(69, name), votes: name === "Plum" ? 2 : 1 }))
+      .sort((a, b) => a.key.toBuffer().compare(b.key.toBuffer()));
+    expect(results.candidates.map((entry) => [entry.candidate.toBase58(), entry.votes.toNumber()]))
+      .toEqual(expected.map((entry) => [entry.key.toBase58(), entry.votes]));
+  });
+
+  it("skips per-vote logs when verbose logging is off", async () => {
+    const now = await chainNow();
+    await createPoll(70, now + 10, now + 100, ["Loud"]);
+    await createPoll(71, now + 10, now + 100, ["Quiet"]);
+    await updateSettings(71, { verboseLogging: false });
+    await warpTo(now + 20);
+
+    const verbose = await transactionLogs(vote(70, "Loud"));
+    expect(verbose.some((log) => log.includes("Voted for candidate: Loud"))).toBe(true);
+    expect(verbose.some((log) => log.includes("Votes: 1"))).toBe(true);
+
+    const quiet = await transactionLogs(vote(71, "Quiet"));
+    expect(quiet.some((log) => log.includes("Voted for candidate"))).toBe(false);
+    expect(quiet.some((log) => log.includes("Votes:"))).toBe(false);
+    const candidate = await votingProgram.account.candidate.fetch(candidateAddress(71, "Quiet"));
+    expect(candidate.candidateVotes.toNumber()).toBe(1);
+  });
+
+  it("only counts votes for verified candidates when required", async () => {
+    const now = await chainNow();
+    await createPoll(72, now + 10, now + 100, ["Real", "Fake"]);
+    await updateSettings(72, { requireVerifiedCandidates: true });
+
+    const outsider = Keypair.generate();
+    await expectError(
+      votingProgram.methods.verifyCandidate("Real", new anchor.BN(72))
+        .accountsPartial({ authority: outsider.publicKey, candidate: candidateAddress(72, "Real") })
+        .signers([outsider])
+        .rpc(),
+      "Unauthorized",
+    );
+    await votingProgram.methods.verifyCandidate("Real", new anchor.BN(72))
+      .accountsPartial({ candidate: candidateAddress(72, "Real") })
+      .rpc();
+    const real = await votingProgram.account.candidate.fetch(candidateAddress(72, "Real"));
+    expect(real.verified).toBe(true);
+
+    await warpTo(now + 20);
+    await vote(72, "Real").rpc();
+    await expectError(vote(72, "Fake").rpc(), "Candidate is not verified");
+  });
+
+  it("merges a finalized poll's tallies into another poll", async () => {
+    const now = await chainNow();
+    await createPoll(73, now + 10, now + 50, ["North", "South"]);
+    await createPoll(74, now + 10, now + 200, ["North", "East"]);
+    await updateSettings(73, { includeAbstentionsInTotal: true });
+    await warpTo(now + 20);
+    await castVotes(73, "North", 2);
+    await castVotes(73, "South", 1);
+    await votingProgram.methods.voteAbstain(new anchor.BN(73)).rpc();
+    await castVotes(74, "North", 1);
+    await warpTo(now + 60);
+    await votingProgram.methods.finalizePoll(new anchor.BN(73))
+      .remainingAccounts(candidateAccounts(73, ["North", "South"]))
+      .rpc();
+
+    const pairs = ["North", "South"].flatMap((name) => [
+      { pubkey: candidateAddress(73, name), isSigner: false, isWritable: false },
+      { pubkey: candidateAddress(74, name), isSigner: false, isWritable: true },
+    ]);
+    const merge = () =>
+      votingProgram.methods.mergePolls(new anchor.BN(73), new anchor.BN(74))
+        .remainingAccounts(pairs);
+    await merge().rpc();
+
+    const north = await votingProgram.account.candidate.fetch(candidateAddress(74, "North"));
+    const south = await votingProgram.account.candidate.fetch(candidateAddress(74, "South"));
+    const east = await votingProgram.account.candidate.fetch(candidateAddress(74, "East"));
+    expect(north.candidateVotes.toNumber()).toBe(3);
+    expect(south.candidateVotes.toNumber()).toBe(1);
+    expect(east.candidateVotes.toNumber()).toBe(0);
+
+    const dest = await votingProgram.account.poll.fetch(pollAddress(74));
+    expect(dest.totalVotes.toNumber()).toBe(4);
+    expect(dest.abstentions.toNumber()).toBe(1);
+    expect(dest.candidateAmount.toNumber()).toBe(3);
+    expect(dest.mergedFrom.map((id) => id.toNumber())).toEqual([73]);
+    await votingProgram.methods.verifyPollIntegrity(new anchor.BN(74))
+      .remainingAccounts(candidateAccounts(74, ["North", "East", "South"]))
+      .rpc();
+
+    const [topKAddress] = PublicKey.findProgramAddressSync(
+      [Buffer.from("top_k"), new anchor.BN(74).toArrayLike(Buffer, "le", 8)],
+      votingProgram.programId,
+    );
+    const topK = await votingProgram.account.topK.fetch(topKAddress);
+    expect(topK.entries[0].candidate.equals(candidateAddress(74, "North"))).toBe(true);
+    expect(topK.entries[0].votes.toNumber()).toBe(3);
+    expect(topK.entries.some((entry) => entry.candidate.equals(candidateAddress(74, "South")))).toBe(true);
+    expect(south.createdAt.toNumber()).toBeLessThanOrEqual(dest.pollStart.toNumber());
+    await vote(74, "South").rpc();
+
+    await warpTo(now + 70);
+    await expectError(merge().rpc(), "Poll was already merged into the destination");
+  });
+
+  it("rejects a candidate whose poll id disagrees with the poll account", async () => {
+    const now = await chainNow();
+    await createPoll(75, now + 10, now + 100, []);
+
+    await expectError(
+      votingProgram.methods.initializeCandidate("Stray", new anchor.BN(76), "", "")
+        .accountsPartial({ poll: pollAddress(75), candidate: candidateAddress(75, "Stray") })
+        .rpc(),
+      "ConstraintSeeds",
+    );
+
+    await patchAccount(pollAddress(75), "poll", (poll) => {
+      poll.pollId = new anchor.BN(76);
+    });
+    await expectError(initializeCandidate(75, "Stray").rpc(), "Poll id does not match the poll account");
+  });
+
+  it("picks a reproducible sortition winner from a committed seed", async () => {
+    const now = await chainNow();
+    const names = ["Oak", "Ash", "Yew"];
+    await createPoll(76, now + 10, now + 100, names);
+    const seed = createHash("sha256").update("sortition seed").digest();
+    await votingProgram.methods.commitSortitionSeed(
+      new anchor.BN(76),
+      Array.from(createHash("sha256").update(seed).digest()),
+    ).rpc();
+
+    const voters = names.map(() => Keypair.generate());
+    await warpTo(now + 20);
+    for (const [i, voter] of voters.entries()) {
+      fund(voter.publicKey);
+      await voteAs(76, names[i], voter).rpc();
+    }
+    await warpTo(now + 200);
+
+    const records = voters.map((voter) => ({
+      pubkey: voterRecordAddress(76, voter.publicKey),
+      isSigner: false,
+      isWritable: false,
+    }));
+    await expectError(
+      votingProgram.methods.sortitionSelect(new anchor.BN(76), Array.from(Buffer.alloc(32, 1)))
+        .remainingAccounts(records)
+        .rpc(),
+      "Revealed seed does not match the commitment",
+    );
+
+    const draw = createHash("sha256")
+      .update(Buffer.concat([seed, new anchor.BN(76).toArrayLike(Buffer, "le", 8)]))
+      .digest()
+      .readBigUInt64LE(0);
+    const order = voters
+      .map((voter, i) => ({ voter: voter.publicKey, name: names[i] }))
+      .sort((a, b) => a.voter.toBuffer().compare(b.voter.toBuffer()));
+    const picked = order[Number(draw % BigInt(order.length))];
+
+    const event = await emittedEvent(
+      votingProgram.methods.sortitionSelect(new anchor.BN(76), Array.from(seed))
+        .remainingAccounts(records.reverse()),
+      "SortitionSelected",
+    );
+    expect(event.voter.equals(picked.voter)).toBe(true);
+    const poll = await votingProgram.account.poll.fetch(pollAddress(76));
+    expect(poll.isFinalized).toBe(true);
+    expect(poll.winner!.equals(candidateAddress(76, picked.name))).toBe(true);
+  });
+
+  it("flags walkover results under a minimum of contested candidates", async () => {
+    const now = await chainNow();
+    const names = ["Fir", "Elm"];
+    for (const pollId of [77, 78]) {
+      await createPoll(pollId, now + 10, now + 100, names);
+      await updateSettings(pollId, { minContestedCandidates: new anchor.BN(2) });
+    }
+    await warpTo(now + 20);
+    await castVotes(77, "Fir", 2);
+    await castVotes(77, "Elm", 1);
+    await castVotes(78, "Fir", 2);
+    await warpTo(now + 200);
+
+    for (const pollId of [77, 78]) {
+      await votingProgram.methods.finalizePoll(new anchor.BN(pollId))
+        .remainingAccounts(candidateAccounts(pollId, names))
+        .rpc();
+    }
+    const contested = await votingProgram.account.poll.fetch(pollAddress(77));
+    const walkover = await votingProgram.account.poll.fetch(pollAddress(78));
+    expect(contested.validResult).toBe(true);
+    expect(walkover.validResult).toBe(false);
+    expect(walkover.winner!.equals(candidateAddress(78, "Fir"))).toBe(true);
+  });
+
+  it("reports turnout against a snapshotted token supply", async () => {
+    const now = await chainNow();
+    await createPoll(79, now + 10, now + 100, ["Gold"]);
+    await expectError(
+      votingProgram.methods.supplyTurnout(new anchor.BN(79)).rpc(),
+      "Poll has no supply snapshot",
+    );
+
+    const mint = Keypair.generate().publicKey;
+    const data = Buffer.alloc(82);
+    data.writeBigUInt64LE(BigInt(1_000), 36);
+    data[45] = 1;
+    context.setAccount(mint, {
+      lamports: anchor.web3.LAMPORTS_PER_SOL,
+      data,
+      owner: new PublicKey("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+      executable: false,
+    });
+    const notMint = Keypair.generate().publicKey;
+    fund(notMint);
+
+    await expectError(
+      votingProgram.methods.snapshotSupply(new anchor.BN(79)).accounts({ mint: notMint }).rpc(),
+      "Account is not an initialized SPL Token mint",
+    );
+    await votingProgram.methods.snapshotSupply(new anchor.BN(79)).accounts({ mint }).rpc();
+
+    await warpTo(now + 20);
+    await castVotes(79, "Gold", 3);
+    const event = await emittedEvent(
+      votingProgram.methods.supplyTurnout(new anchor.BN(79)),
+      "SupplyTurnout",
+    );
+    expect(event.snapshotSupply.toNumber()).toBe(1_000);
+    expect(event.turnoutBps.toNumber()).toBe(30);
+  });
+
+  it("rejects a hook that calls back into the voting program", async () => {
+    const hookProgram = new anchor.Program<VoteHook>(HOOK_IDL, provider);
+    const [hookState] = PublicKey.findProgramAddressSync(
+      [Buffer.from("hook_state")],
+      hookProgram.programId,
+    );
+    if (!(await context.banksClient.getAccount(hookState))) {
+      await hookProgram.methods.initialize().rpc();
+    }
+    await hookProgram.methods.setReenter(true).rpc();
+
+    const now = await chainNow();
+    await createPoll(80, now + 10, now + 100, ["Trap"]);
+    await updateSettings(80, { hookProgram: hookProgram.programId });
+    await warpTo(now + 20);
+
+    await expectError(
+      vote(80, "Trap")
+        .remainingAccounts([
+          { pubkey: hookProgram.programId, isSigner: false, isWritable: false },
+          { pubkey: hookState, isSigner: false, isWritable: true },
+          { pubkey: votingProgram.programId, isSigner: false, isWritable: false },
+        ])
+        .rpc(),
+      "reentrancy not allowed",
+    );
+    const candidate = await votingProgram.account.candidate.fetch(candidateAddress(80, "Trap"));
+    expect(candidate.candidateVotes.toNumber()).toBe(0);
+    const poll = await votingProgram.account.poll.fetch(pollAddress(80));
+    expect(poll.processing).toBe(false);
+
+    await hookProgram.methods.setReenter(false).rpc();
+  });
+
+  it("sums votes per party", async () => {
+    const now = await chainNow();
+    await createPoll(81, now + 10, now + 100, []);
+    const candidates = [["Ada", "Reds"], ["Bo", "Blues"], ["Cy", "Reds"], ["Di", ""]];
+    for (const [name, party] of candidates) {
+      await initializeCandidate(81, name, name, "", party).rpc();
+    }
+    await expectError(
+      initializeCandidate(81, "Ed", "Ed", "", "x".repeat(33)).rpc(),
+      "Candidate party name is longer than 32 bytes",
+    );
+    const ada = await votingProgram.account.candidate.fetch(candidateAddress(81, "Ada"));
+    expect(ada.party).toBe("Reds");
+
+    await warpTo(now + 20);
+    await castVotes(81, "Ada", 2);
+    await castVotes(81, "Bo", 1);
+    await castVotes(81, "Cy", 3);
+    await castVotes(81, "Di", 1);
+
+    const event = await emittedEvent(
+      votingProgram.methods.partyTotals(new anchor.BN(81))
+        .remainingAccounts(candidateAccounts(81, candidates.map(([name]) => name))),
+      "PartyTotals",
+    );
+    expect(event.parties).toEqual(["", "Blues", "Reds"]);
+    expect(event.votes.map((votes) => votes.toNumber())).toEqual([1, 1, 5]);
+  });
+
+  it("shows voters only their own ballot", async () => {
+    const now = await chainNow();
+    await createPoll(82, now + 10, now + 100, ["Mine"]);
+    const voter = Keypair.generate();
+    const abstainer = Keypair.generate();
+    fund(voter.publicKey);
+    fund(abstainer.publicKey);
+    const myBallot = (signer: Keypair) =>
+      votingProgram.methods.myBallot(new anchor.BN(82))
+        .accounts({ signer: signer.publicKey })
+        .signers([signer]);
+
+    await expectError(myBallot(voter).rpc(), "Signer has not voted in this poll");
+
+    await warpTo(now + 20);
+    await voteAs(82, "Mine", voter).rpc();
+    await votingProgram.methods.voteAbstain(new anchor.BN(82))
+      .accounts({ signer: abstainer.publicKey })
+      .signers([abstainer])
+      .rpc();
+
+    const voted = await emittedEvent(myBallot(voter), "MyBallot", [voter]);
+    expect(voted.voter.equals(voter.publicKey)).toBe(true);
+    expect(voted.candidate.equals(candidateAddress(82, "Mine"))).toBe(true);
+    expect(voted.abstained).toBe(false);
+    expect(voted.weightBps.toNumber()).toBe(10_000);
+
+    const abstained = await emittedEvent(myBallot(abstainer), "MyBallot", [abstainer]);
+    expect(abstained.candidate).toBeNull();
+    expect(abstained.abstained).toBe(true);
+    expect(abstained.votesUsed.toNumber()).toBe(1);
+  });
+
+  it("widens turnout buckets to fit long polls", async () => {
+    const now = await chainNow();
+    const start = now + 10;
+    await createPoll(83, start, start + 12 * 3600, ["Short"]);
+    await createPoll(84, start, start + 48 * 3600, ["Long"]);
+
+    await warpTo(start + 5 * 3600 + 1);
+    await castVotes(83, "Short");
+    await castVotes(84, "Long");
+    await warpTo(start + 11 * 3600);
+    await castVotes(83, "Short");
+    await warpTo(start + 47 * 3600);
+    await castVotes(84, "Long");
+
+    const short = await votingProgram.account.poll.fetch(pollAddress(83));
+    expect(short.bucketSeconds.toNumber()).toBe(3600);
+    expect(short.voteBuckets[5].toNumber()).toBe(1);
+    expect(short.voteBuckets[11].toNumber()).toBe(1);
+
+    const long = await votingProgram.account.poll.fetch(pollAddress(84));
+    expect(long.bucketSeconds.toNumber()).toBe(7200);
+    expect(long.voteBuckets[2].toNumber()).toBe(1);
+    expect(long.voteBuckets[23].toNumber()).toBe(1);
+  });
+
+  it("rejects voters first seen too recently", async () => {
+    const now = await chainNow();
+    await createPoll(85, now + 10, now + 100, ["Elder"]);
+    await updateSettings(85, { minAccountAgeSlots: new anchor.BN(100) });
+    const voter = Keypair.generate();
+    const stranger = Keypair.generate();
+    fund(voter.publicKey);
+    fund(stranger.publicKey);
+    await votingProgram.methods.registerVoter(new anchor.BN(85), voter.publicKey).rpc();
+
+    await warpTo(now + 20);
+    await expectError(voteAs(85, "Elder", voter).rpc(), "Voter was first seen too recently");
+    await expectError(voteAs(85, "Elder", stranger).rpc(), "Voter was first seen too recently");
+
+    await warpTo(now + 30, 100);
+    await voteAs(85, "Elder", voter).rpc();
+    const candidate = await votingProgram.account.candidate.fetch(candidateAddress(85, "Elder"));
+    expect(candidate.candidateVotes.toNumber()).toBe(1);
+  });
+
+  it("preflights candidate names with the on-chain rules", async () => {
+    const now = await chainNow();
+    await createPoll(86, now + 10, now + 100, []);
+    const validate = (name: string) =>
+      emittedEvent(
+        votingProgram.methods.validateCandidateName(name, new anchor.BN(86)),
+        "NameValidation",
+      );
+
+    const valid = await validate("Alice");
+    expect(valid.valid).toBe(true);
+    expect(valid.reasonCode).toBe(0);
+    expect(valid.seedName).toBe("Alice");
+
+    // initialize_candidate accepts empty names, so the preflight does too
+    const empty = await validate("");
+    expect(empty.valid).toBe(true);
+    expect(empty.reasonCode).toBe(0);
+
+    const tooLong = await validate("x".repeat(33));
+    expect(tooLong.valid).toBe(false);
+    expect(tooLong.reasonCode).toBe(
+      IDL.errors.find((e: any) => e.name === "CandidateNameTooLong").code,
+    );
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(86));
+    expect(poll.candidateAmount.toNumber()).toBe(0);
+  });
+
+  it("checks integrity against the poll's counting mode", async () => {
+    const now = await chainNow();
+    const names = ["Red", "Green"];
+    await createPoll(87, now + 10, now + 100, names);
+    await createPoll(88, now + 10, now + 100, names);
+    await updateSettings(88, { countingMode: 1 });
+    await expectError(updateSettings(87, { countingMode: 2 }), "Unknown counting mode");
+
+    await warpTo(now + 20);
+    for (const pollId of [87, 88]) {
+      await castVotes(pollId, "Red");
+      await castVotes(pollId, "Green");
+    }
+    const verify = (pollId: number) =>
+      votingProgram.methods.verifyPollIntegrity(new anchor.BN(pollId))
+        .remainingAccounts(candidateAccounts(pollId, names));
+    await verify(87).rpc();
+    await verify(88).rpc();
+
+    // one voter selecting both candidates: fine for approval, but a
+    // single-choice poll expects the selections to match total_votes
+    for (const pollId of [87, 88]) {
+      await patchAccount(pollAddress(pollId), "poll", (poll) => {
+        poll.totalVotes = new anchor.BN(1);
+      });
+    }
+    await warpTo(now + 30);
+    await expectError(verify(87).rpc(), "Candidate votes do not add up to the poll's total votes");
+    await verify(88).rpc();
+
+    await patchAccount(pollAddress(88), "poll", (poll) => {
+      poll.uniqueVoters = new anchor.BN(3);
+    });
+    await warpTo(now + 40);
+    await expectError(verify(88).rpc(), "Candidate votes do not add up to the poll's total votes");
+  });
+
+  it("treats finalizing twice as a no-op", async () => {
+    const now = await chainNow();
+    const names = ["First", "Second"];
+    await createPoll(89, now + 10, now + 100, names);
+    await warpTo(now + 20);
+    await castVotes(89, "First", 2);
+    await castVotes(89, "Second", 1);
+    await warpTo(now + 200);
+    await votingProgram.methods.finalizePoll(new anchor.BN(89))
+      .remainingAccounts(candidateAccounts(89, names))
+      .rpc();
+    const [auditLog] = PublicKey.findProgramAddressSync(
+      [Buffer.from("audit_log"), new anchor.BN(89).toArrayLike(Buffer, "le", 8)],
+      votingProgram.programId,
+    );
+    const first = await votingProgram.account.poll.fetch(pollAddress(89));
+    const entries = (await votingProgram.account.auditLog.fetch(auditLog)).entries.length;
+
+    // a later vote can't sneak in, and the retry passes no candidates at all
+    await patchAccount(candidateAddress(89, "Second"), "candidate", (candidate) => {
+      candidate.candidateVotes = new anchor.BN(9);
+    });
+    await warpTo(now + 300);
+    await votingProgram.methods.finalizePoll(new anchor.BN(89)).rpc();
+
+    const second = await votingProgram.account.poll.fetch(pollAddress(89));
+    expect(second.winner!.equals(first.winner!)).toBe(true);
+    expect(second.winner!.equals(candidateAddress(89, "First"))).toBe(true);
+    expect(second.finalizedAt.toNumber()).toBe(first.finalizedAt.toNumber());
+    expect((await votingProgram.account.auditLog.fetch(auditLog)).entries.length).toBe(entries);
+  });
+
+  it("rejects lookalike candidate names when the charset is restricted", async () => {
+    const now = await chainNow();
+    await createPoll(90, now + 100, now + 200, []);
+    await updateSettings(90, { restrictCandidateCharset: true });
+
+    await initializeCandidate(90, "Clean Name_1-A").rpc();
+    const candidate = await votingProgram.account.candidate.fetch(candidateAddress(90, "Clean Name_1-A"));
+    expect(candidate.candidateName).toBe("Clean Name_1-A");
+
+    // Cyrillic "А" in place of the Latin one
+    await expectError(initializeCandidate(90, "Аlice").rpc(), "outside the allowed set");
+    await expectError(initializeCandidate(90, "Bob 🗳").rpc(), "outside the allowed set");
+  });
+
+  it("recomputes total_votes from the candidate tallies", async () => {
+    const now = await chainNow();
+    const names = ["Alpha", "Beta"];
+    await createPoll(91, now + 10, now + 100, names);
+    await warpTo(now + 20);
+    await castVotes(91, "Alpha", 2);
+    await castVotes(91, "Beta", 1);
+
+    const recompute = () =>
+      votingProgram.methods.recomputeTotalVotes(new anchor.BN(91))
+        .remainingAccounts(candidateAccounts(91, names));
+
+    const synced = await emittedEvent(recompute(), "TotalRecomputed");
+    expect(synced.oldTotal.toNumber()).toBe(3);
+    expect(synced.newTotal.toNumber()).toBe(3);
+
+    await patchAccount(pollAddress(91), "poll", (poll) => {
+      poll.totalVotes = new anchor.BN(7);
+    });
+    await warpTo(await chainNow());
+    const repaired = await emittedEvent(recompute(), "TotalRecomputed");
+    expect(repaired.oldTotal.toNumber()).toBe(7);
+    expect(repaired.newTotal.toNumber()).toBe(3);
+    const poll = await votingProgram.account.poll.fetch(pollAddress(91));
+    expect(poll.totalVotes.toNumber()).toBe(3);
+
+    await expectError(
+      votingProgram.methods.recomputeTotalVotes(new anchor.BN(91))
+        .remainingAccounts(candidateAccounts(91, ["Alpha"]))
+        .rpc(),
+      "do not match the poll's candidate count",
+    );
+  });
+
+  it("stores the result callback hash given at creation", async () => {
+    const now = await chainNow();
+    const url = "https://results.example/poll/92";
+    const callbackHash = [...createHash("sha256").update(url).digest()];
+    await votingProgram.methods.initializePoll(
+      new anchor.BN(92),
+      "Callback test",
+      new anchor.BN(now + 10),
+      new anchor.BN(now + 100),
+      callbackHash,
+    ).rpc();
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(92));
+    expect(poll.resultCallbackHash).toEqual(callbackHash);
+
+    // what a relayer checks before posting
+    const matches = (candidate: string) =>
+      Buffer.from(poll.resultCallbackHash).equals(createHash("sha256").update(candidate).digest());
+    expect(matches(url)).toBe(true);
+    expect(matches("https://results.example/poll/93")).toBe(false);
+
+    const plain = await votingProgram.account.poll.fetch(pollAddress(91));
+    expect(plain.resultCallbackHash).toEqual(noCallback);
+  });
+
+  it("reports candidates without any votes", async () => {
+    const now = await chainNow();
+    const names = ["Busy", "Idle", "Quiet"];
+    await createPoll(93, now + 10, now + 100, names);
+    await warpTo(now + 20);
+    await castVotes(93, "Busy", 2);
+
+    const report = await emittedEvent(
+      votingProgram.methods.zeroVoteCandidates(new anchor.BN(93))
+        .remainingAccounts(candidateAccounts(93, names)),
+      "ZeroVoteReport",
+    );
+    expect(report.count.toNumber()).toBe(2);
+    expect(report.candidates.map((c: PublicKey) => c.toBase58())).toEqual(
+      [candidateAddress(93, "Idle"), candidateAddress(93, "Quiet")].map((c) => c.toBase58()),
+    );
+
+    await expectError(
+      votingProgram.methods.zeroVoteCandidates(new anchor.BN(93))
+        .remainingAccounts(candidateAccounts(93, ["Busy", "Idle"]))
+        .rpc(),
+      "do not match the poll's candidate count",
+    );
+  });
+
+  it("rejects a repeated display name when unique display names are required", async () => {
+    const now = await chainNow();
+    await createPoll(94, now + 100, now + 200, []);
+    const [displayNames] = PublicKey.findProgramAddressSync(
+      [Buffer.from("display_names"), new anchor.BN(94).toArrayLike(Buffer, "le", 8)],
+      votingProgram.programId,
+    );
+    // the set is created when the setting is turned on, not by every poll
+    expect(await context.banksClient.getAccount(displayNames)).toBeNull();
+    await expectError(
+      updateSettings(94, { requireUniqueDisplayNames: true }),
+      "Unique display names need the poll's display name set",
+    );
+    await votingProgram.methods.updatePollSettings(
+      new anchor.BN(94),
+      { ...defaultSettings(), requireUniqueDisplayNames: true },
+    ).accountsPartial({ displayNames }).rpc();
+
+    const addCandidate = (name: string, displayName = "") =>
+      votingProgram.methods.initializeCandidate(name, new anchor.BN(94), displayName, "")
+        .accountsPartial({ candidate: candidateAddress(94, name), displayNames });
+    await expectError(
+      initializeCandidate(94, "smith0", "smith0", "Jane Smith").rpc(),
+      "Unique display names need the poll's display name set",
+    );
+    await addCandidate("smith1", "Jane Smith").rpc();
+    await expectError(
+      addCandidate("smith2", "Jane Smith").rpc(),
+      "Another candidate already uses this display name",
+    );
+    await addCandidate("smith3", "John Smith").rpc();
+    // an empty display name falls back to the seed name, which is checked too
+    await expectError(
+      addCandidate("Jane Smith").rpc(),
+      "Another candidate already uses this display name",
+    );
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(94));
+    expect(poll.candidateAmount.toNumber()).toBe(2);
+    await expectError(
+      updateSettings(94, { requireUniqueDisplayNames: false }),
+      "can't change once candidates have been added",
+    );
+  });
+
+  it("charges quadratic votes against the voter's credits", async () => {
+    const now = await chainNow();
+    const names = ["North", "South"];
+    await createPoll(95, now + 10, now + 100, names);
+    await updateSettings(95, { creditsPerVoter: new anchor.BN(100) });
+    await warpTo(now + 20);
+
+    const voteQuadratic = (name: string, votes: number) =>
+      votingProgram.methods.voteQuadratic(name, new anchor.BN(95), new anchor.BN(votes))
+        .accountsPartial({ candidate: candidateAddress(95, name) })
+        .rpc();
+    const creditsUsed = async () =>
+      (await votingProgram.account.voterRecord.fetch(
+        voterRecordAddress(95, provider.wallet.publicKey),
+      )).creditsUsed.toNumber();
+
+    await voteQuadratic("North", 3);
+    expect(await creditsUsed()).toBe(9);
+    // 7 votes for North cost 49 in total, so the 4 extra cost 40
+    await warpTo(await chainNow());
+    await voteQuadratic("North", 4);
+    expect(await creditsUsed()).toBe(49);
+    await warpTo(await chainNow());
+    await voteQuadratic("South", 5);
+    expect(await creditsUsed()).toBe(74);
+
+    await warpTo(await chainNow());
+    await expectError(voteQuadratic("South", 1), "Not enough voting credits left");
+    await expectError(vote(95, "South").rpc(), "Poll does not accept this kind of vote");
+
+    const north = await votingProgram.account.candidate.fetch(candidateAddress(95, "North"));
+    const south = await votingProgram.account.candidate.fetch(candidateAddress(95, "South"));
+    expect(north.candidateVotes.toNumber()).toBe(7);
+    expect(south.candidateVotes.toNumber()).toBe(5);
+    const poll = await votingProgram.account.poll.fetch(pollAddress(95));
+    expect(poll.totalVotes.toNumber()).toBe(12);
+    expect(poll.uniqueVoters.toNumber()).toBe(1);
+  });
+
+  it("emits a participation proof across polls", async () => {
+    const voter = Keypair.generate();
+    await fund(voter.publicKey);
+    const proofOf = () =>
+      emittedEvent(
+        votingProgram.methods.participationProof()
+          .accountsPartial({ signer: voter.publicKey }),
+        "ParticipationProof",
+        [voter],
+      );
+
+    const empty = await proofOf();
+    expect(empty.pollCount.toNumber()).toBe(0);
+    expect(empty.pollIds).toEqual([]);
+
+    const now = await chainNow();
+    await createPoll(96, now + 10, now + 100, ["Yes"]);
+    await createPoll(97, now + 10, now + 100, ["No"]);
+    await warpTo(now + 20);
+    await voteAs(96, "Yes", voter).rpc();
+    await voteAs(97, "No", voter).rpc();
+
+    const proof = await proofOf();
+    expect(proof.voter.equals(voter.publicKey)).toBe(true);
+    expect(proof.pollCount.toNumber()).toBe(2);
+    expect(proof.pollIds.map((id: anchor.BN) => id.toNumber())).toEqual([96, 97]);
+  });
+
+  it("finalizes automatically once every registered voter has voted", async () => {
+    const now = await chainNow();
+    await createPoll(98, now + 10, now + 1000, ["Lake", "Hill"]);
+    await updateSettings(98, { requireRegistration: true, autoFinalizeOnFullTurnout: true });
+    const voters = [Keypair.generate(), Keypair.generate()];
+    for (const voter of voters) {
+      await fund(voter.publicKey);
+      await votingProgram.methods.registerVoter(new anchor.BN(98), voter.publicKey).rpc();
+    }
+    await warpTo(now + 20);
+
+    await voteAs(98, "Lake", voters[0]).rpc();
+    let poll = await votingProgram.account.poll.fetch(pollAddress(98));
+    expect(poll.isFinalized).toBe(false);
+
+    const event = await emittedEvent(voteAs(98, "Lake", voters[1]), "AutoFinalized", [voters[1]]);
+    expect(event.winner.equals(candidateAddress(98, "Lake"))).toBe(true);
+    expect(event.uniqueVoters.toNumber()).toBe(2);
+
+    poll = await votingProgram.account.poll.fetch(pollAddress(98));
+    expect(poll.isFinalized).toBe(true);
+    expect(poll.winner!.equals(candidateAddress(98, "Lake"))).toBe(true);
+    expect(poll.finalizedAt.toNumber()).toBe(now + 20);
+    await warpTo(now + 21);
+    await expectError(voteAs(98, "Hill", voters[0]).rpc(), "Poll is finalized and no longer accepts votes");
+  });
+
+  it("migrates a voter record from the unversioned layout", async () => {
+    const now = await chainNow();
+    await createPoll(99, now + 10, now + 100, ["Old"]);
+    await warpTo(now + 20);
+    await vote(99, "Old").rpc();
+
+    // drop credits_used, version, choice, round and jurisdiction to get the record as it was written before them
+    const address = voterRecordAddress(99, provider.wallet.publicKey);
+    const account = await context.banksClient.getAccount(address);
+    context.setAccount(address, { ...account, data: Buffer.from(account.data).subarray(0, account.data.length - 23) });
+    await expect(votingProgram.account.voterRecord.fetch(address)).rejects.toThrow();
+
+    await votingProgram.methods.migrateVoterRecord(new anchor.BN(99)).rpc();
+    const migrated = await votingProgram.account.voterRecord.fetch(address);
+    expect(migrated.version).toBe(4);
+    expect(migrated.choice).toEqual({ candidate: {} });
+    expect(migrated.round.toNumber()).toBe(0);
+    expect(migrated.creditsUsed.toNumber()).toBe(0);
+    expect(migrated.candidate.equals(candidateAddress(99, "Old"))).toBe(true);
+    expect(migrated.votesUsed.toNumber()).toBe(1);
+    expect((await context.banksClient.getAccount(address)).data.length).toBe(account.data.length);
+
+    // a current record is left alone
+    await warpTo(now + 21);
+    await votingProgram.methods.migrateVoterRecord(new anchor.BN(99)).rpc();
+    expect((await votingProgram.account.voterRecord.fetch(address)).version).toBe(4);
+  });
+
+  it("announces every candidate tied for the lead", async () => {
+    const now = await chainNow();
+    const names = ["Ash", "Birch", "Cedar", "Dogwood"];
+    await createPoll(100, now + 10, now + 100, names);
+    await createPoll(101, now + 10, now + 100, names);
+    await warpTo(now + 20);
+    for (const name of ["Ash", "Birch", "Cedar"]) {
+      await castVotes(100, name, 2);
+    }
+    await castVotes(100, "Dogwood", 1);
+    await castVotes(101, "Ash", 2);
+    await castVotes(101, "Birch", 1);
+    await warpTo(now + 200);
+
+    const tie = await emittedEvent(
+      votingProgram.methods.finalizePoll(new anchor.BN(100))
+        .remainingAccounts(candidateAccounts(100, names)),
+      "TieDetected",
+    );
+    const tied = ["Ash", "Birch", "Cedar"]
+      .map((name) => candidateAddress(100, name))
+      .sort((a, b) => a.toBuffer().compare(b.toBuffer()));
+    expect(tie.tiedCandidates.map((c: PublicKey) => c.toBase58())).toEqual(tied.map((c) => c.toBase58()));
+    expect(tie.voteCount.toNumber()).toBe(2);
+
+    const events = await emittedEvents(
+      votingProgram.methods.finalizePoll(new anchor.BN(101))
+        .remainingAccounts(candidateAccounts(101, names)),
+    );
+    expect(events.some((e) => e.name.toLowerCase() === "tiedetected")).toBe(false);
+  });
+
+  it("reserves candidate slots and fills them before the poll starts", async () => {
+    const now = await chainNow();
+    await createPoll(102, now + 100, now + 1000, []);
+    const slotAddress = (slot: number) => candidateAddress(102, `#${slot}`);
+    for (const slot of [0, 1]) {
+      await votingProgram.methods.reserveCandidateSlot(new anchor.BN(102))
+        .accountsPartial({ candidate: slotAddress(slot) })
+        .rpc();
+    }
+    let poll = await votingProgram.account.poll.fetch(pollAddress(102));
+    expect(poll.candidateAmount.toNumber()).toBe(2);
+    expect(poll.reservedSlots.toNumber()).toBe(2);
+    expect((await votingProgram.account.candidate.fetch(slotAddress(0))).unfilled).toBe(true);
+
+    await votingProgram.methods.fillCandidateSlot(new anchor.BN(102), new anchor.BN(0), "Dana Reyes", "Green")
+      .accountsPartial({ candidate: slotAddress(0) })
+      .rpc();
+    const filled = await votingProgram.account.candidate.fetch(slotAddress(0));
+    expect(filled.unfilled).toBe(false);
+    expect(filled.displayName).toBe("Dana Reyes");
+    expect(filled.party).toBe("Green");
+    await expectError(initializeCandidate(102, "#2").rpc(), "reserved for candidate slots");
+
+    await warpTo(now + 150);
+    await expectError(vote(102, "#1").rpc(), "Candidate slot has not been filled yet");
+    await vote(102, "#0").rpc();
+    expect((await votingProgram.account.candidate.fetch(slotAddress(0))).candidateVotes.toNumber()).toBe(1);
+    await expectError(
+      votingProgram.methods.fillCandidateSlot(new anchor.BN(102), new anchor.BN(1), "Late", "")
+        .accountsPartial({ candidate: slotAddress(1) })
+        .rpc(),
+      "Poll has already started",
+    );
+  });
+
+  it("tallies past u64::MAX on high precision polls", async () => {
+    const now = await chainNow();
+    const max = new anchor.BN("18446744073709551615");
+    for (const [pollId, highPrecision] of [[103, true], [104, false]] as const) {
+      await createPoll(pollId, now + 10, now + 100, ["Big"]);
+      await updateSettings(pollId, { highPrecision, votesPerCast: new anchor.BN(2) });
+      await patchAccount(candidateAddress(pollId, "Big"), "candidate", (candidate) => {
+        candidate.candidateVotes = max;
+      });
+      await patchAccount(pollAddress(pollId), "poll", (poll) => {
+        poll.totalVotes = max;
+      });
+    }
+    await warpTo(now + 20);
+
+    await vote(103, "Big").rpc();
+    const candidate = await votingProgram.account.candidate.fetch(candidateAddress(103, "Big"));
+    expect(candidate.candidateVotesHi.toNumber()).toBe(1);
+    expect(candidate.candidateVotes.toNumber()).toBe(1);
+    const poll = await votingProgram.account.poll.fetch(pollAddress(103));
+    expect(poll.totalVotesHi.toNumber()).toBe(1);
+    expect(poll.totalVotes.toNumber()).toBe(1);
+
+    await expectError(vote(104, "Big").rpc(), "Vote tally overflowed");
+  });
+
+  it("checks the client's expected voter record rent", async () => {
+    const now = await chainNow();
+    await createPoll(105, now + 10, now + 100, ["Rent"]);
+    await warpTo(now + 20);
+    await vote(105, "Rent").rpc();
+    const { lamports: rent } = await context.banksClient.getAccount(
+      voterRecordAddress(105, provider.wallet.publicKey),
+    );
+
+    const voteWithRent = (voter: Keypair, exactRent: number) =>
+      votingProgram.methods.vote("Rent", new anchor.BN(105), new anchor.BN(exactRent), null)
+        .accountsPartial({ candidate: candidateAddress(105, "Rent"), signer: voter.publicKey })
+        .signers([voter])
+        .rpc();
+    const [exact, off] = [Keypair.generate(), Keypair.generate()];
+    await fund(exact.publicKey);
+    await fund(off.publicKey);
+
+    await voteWithRent(exact, Number(rent));
+    await expectError(voteWithRent(off, Number(rent) + 1), "Voter record rent differs");
+    expect(await context.banksClient.getAccount(voterRecordAddress(105, off.publicKey))).toBeNull();
+    const candidate = await votingProgram.account.candidate.fetch(candidateAddress(105, "Rent"));
+    expect(candidate.candidateVotes.toNumber()).toBe(2);
+  });
+
+  it("tracks blank votes apart from abstentions and candidate votes", async () => {
+    const now = await chainNow();
+    await createPoll(106, now + 10, now + 100, ["Only"]);
+    await updateSettings(106, {
+      totalEligibleWeight: new anchor.BN(4),
+      quorumBps: new anchor.BN(5_000),
+      blankVotesCountTowardQuorum: true,
+    });
+    await warpTo(now + 20);
+    const [blank, abstainer] = [Keypair.generate(), Keypair.generate()];
+    await fund(blank.publicKey);
+    await fund(abstainer.publicKey);
+
+    await vote(106, "Only").rpc();
+    await votingProgram.methods.voteBlank(new anchor.BN(106))
+      .accountsPartial({ signer: blank.publicKey })
+      .signers([blank])
+      .rpc();
+    await votingProgram.methods.voteAbstain(new anchor.BN(106))
+      .accountsPartial({ signer: abstainer.publicKey })
+      .signers([abstainer])
+      .rpc();
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(106));
+    expect(poll.blankVotes.toNumber()).toBe(1);
+    expect(poll.abstentions.toNumber()).toBe(1);
+    expect(poll.totalVotes.toNumber()).toBe(1);
+    expect(poll.uniqueVoters.toNumber()).toBe(3);
+    const record = await votingProgram.account.voterRecord.fetch(voterRecordAddress(106, blank.publicKey));
+    expect(record.choice).toEqual({ blank: {} });
+    const abstained = await votingProgram.account.voterRecord.fetch(voterRecordAddress(106, abstainer.publicKey));
+    expect(abstained.choice).toEqual({ abstain: {} });
+
+    const ballot = await emittedEvent(
+      votingProgram.methods.myBallot(new anchor.BN(106)).accountsPartial({ signer: blank.publicKey }),
+      "MyBallot",
+      [blank],
+    );
+    expect(ballot.blank).toBe(true);
+    expect(ballot.abstained).toBe(false);
+    expect(ballot.candidate).toBeNull();
+
+    // one vote plus one blank is half of the four eligible
+    await warpTo(now + 200);
+    await votingProgram.methods.finalizePoll(new anchor.BN(106))
+      .remainingAccounts(candidateAccounts(106, ["Only"]))
+      .rpc();
+    const finalized = await votingProgram.account.poll.fetch(pollAddress(106));
+    expect(finalized.winner!.equals(candidateAddress(106, "Only"))).toBe(true);
+  });
+
+  it("pauses a poll that takes too many votes in one slot until the authority resumes it", async () => {
+    const now = await chainNow();
+    await createPoll(107, now + 10, now + 100, ["Flash"]);
+    await updateSettings(107, { maxVotesPerSlot: new anchor.BN(2) });
+    await warpTo(now + 20);
+
+    // every transaction below lands in the same slot
+    const voters = [Keypair.generate(), Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    for (const voter of voters) await fund(voter.publicKey);
+    await voteAs(107, "Flash", voters[0]).rpc();
+    await voteAs(107, "Flash", voters[1]).rpc();
+    const paused = await emittedEvent(voteAs(107, "Flash", voters[2]), "SuspiciousVelocityPaused", [voters[2]]);
+    expect(paused.pollId.toNumber()).toBe(107);
+    expect(paused.votesInSlot.toNumber()).toBe(3);
+
+    let poll = await votingProgram.account.poll.fetch(pollAddress(107));
+    expect(poll.paused).toBe(true);
+    expect(poll.totalVotes.toNumber()).toBe(3);
+    await expectError(voteAs(107, "Flash", voters[3]).rpc(), "Poll is paused after a burst of votes");
+
+    await votingProgram.methods.resumePoll(new anchor.BN(107)).rpc();
+    await expectError(votingProgram.methods.resumePoll(new anchor.BN(107)).rpc(), "Poll is not paused");
+    await warpTo(now + 21);
+    await voteAs(107, "Flash", voters[3]).rpc();
+    poll = await votingProgram.account.poll.fetch(pollAddress(107));
+    expect(poll.paused).toBe(false);
+    expect(poll.totalVotes.toNumber()).toBe(4);
+    expect(poll.slotVotes.toNumber()).toBe(1);
+  });
+
+  it("only declares a majority winner when the leader has more than half the votes", async () => {
+    const now = await chainNow();
+    const names = ["Ash", "Birch", "Cedar"];
+    for (const [pollId, winRule] of [[108, 0], [109, 1]]) {
+      await createPoll(pollId, now + 10, now + 100, names);
+      await updateSettings(pollId, { winRule });
+    }
+    await expectError(updateSettings(109, { winRule: 2 }), "Win rule must be plurality or majority");
+    await warpTo(now + 20);
+
+    // Ash leads with 2 of 4 votes: a plurality but not a majority
+    for (const pollId of [108, 109]) {
+      await castVotes(pollId, "Ash", 2);
+      await castVotes(pollId, "Birch");
+      await castVotes(pollId, "Cedar");
+    }
+
+    await warpTo(now + 200);
+    for (const pollId of [108, 109]) {
+      await votingProgram.methods.finalizePoll(new anchor.BN(pollId))
+        .remainingAccounts(candidateAccounts(pollId, names))
+        .rpc();
+    }
+    const plurality = await votingProgram.account.poll.fetch(pollAddress(108));
+    expect(plurality.winner!.equals(candidateAddress(108, "Ash"))).toBe(true);
+    const majority = await votingProgram.account.poll.fetch(pollAddress(109));
+    expect(majority.winner).toBeNull();
+    expect(majority.noWinner).toBe(true);
+  });
+
+  it("creates a runoff between the top two of a majority poll without a winner", async () => {
+    const now = await chainNow();
+    const names = ["North", "South", "East", "West"];
+    await createPoll(110, now + 10, now + 100, names);
+    await updateSettings(110, { winRule: 1 });
+    await warpTo(now + 20);
+    await castVotes(110, "North", 3);
+    await castVotes(110, "South", 2);
+    await castVotes(110, "East");
+    await castVotes(110, "West");
+
+    const runoffAccounts = [
+      ...candidateAccounts(110, names),
+      { pubkey: candidateAddress(111, "North"), isSigner: false, isWritable: true },
+      { pubkey: candidateAddress(111, "South"), isSigner: false, isWritable: true },
+    ];
+    const createRunoff = () =>
+      votingProgram.methods.createRunoff(
+        new anchor.BN(110),
+        new anchor.BN(111),
+        new anchor.BN(now + 300),
+        new anchor.BN(now + 400),
+      )
+        .remainingAccounts(runoffAccounts)
+        .rpc();
+    await warpTo(now + 200);
+    await expectError(createRunoff(), "Poll has not been finalized");
+
+    // North's 3 of 7 votes are the most but not a majority
+    await votingProgram.methods.finalizePoll(new anchor.BN(110))
+      .remainingAccounts(candidateAccounts(110, names))
+      .rpc();
+    await warpTo(now + 201);
+    await createRunoff();
+
+    const runoff = await votingProgram.account.poll.fetch(pollAddress(111));
+    expect(runoff.runoffOf!.toNumber()).toBe(110);
+    expect(runoff.candidateAmount.toNumber()).toBe(2);
+    expect(runoff.totalVotes.toNumber()).toBe(0);
+    for (const name of ["North", "South"]) {
+      const candidate = await votingProgram.account.candidate.fetch(candidateAddress(111, name));
+      expect(candidate.candidateVotes.toNumber()).toBe(0);
+      expect(candidate.poll.equals(pollAddress(111))).toBe(true);
+    }
+    expect(await context.banksClient.getAccount(candidateAddress(111, "East"))).toBeNull();
+  });
+
+  it("reports the margin of victory for clear, narrow and tied results", async () => {
+    const now = await chainNow();
+    const names = ["Red", "Blue", "Green"];
+    const tallies: [number, number[]][] = [[112, [5, 2, 1]], [113, [3, 2, 0]], [114, [2, 2, 1]]];
+    for (const [pollId, votes] of tallies) {
+      await createPoll(pollId, now + 10, now + 100, names);
+    }
+    await warpTo(now + 20);
+    for (const [pollId, votes] of tallies) {
+      for (const [i, count] of votes.entries()) {
+        if (count > 0) await castVotes(pollId, names[i], count);
+      }
+    }
+    const margin = (pollId: number, accountNames = names) =>
+      emittedEvent(
+        votingProgram.methods.victoryMargin(new anchor.BN(pollId))
+          .remainingAccounts(candidateAccounts(pollId, accountNames)),
+        "VictoryMarginReport",
+      );
+
+    const clear = await margin(112);
+    expect(clear.winner.equals(candidateAddress(112, "Red"))).toBe(true);
+    expect(clear.runnerUp.equals(candidateAddress(112, "Blue"))).toBe(true);
+    expect(clear.marginVotes.toNumber()).toBe(3);
+    expect(clear.marginBps.toNumber()).toBe(3_750);
+
+    const narrow = await margin(113);
+    expect(narrow.marginVotes.toNumber()).toBe(1);
+    expect(narrow.marginBps.toNumber()).toBe(2_000);
+
+    const tied = await margin(114);
+    expect(tied.marginVotes.toNumber()).toBe(0);
+    expect(tied.marginBps.toNumber()).toBe(0);
+    const leaders = [candidateAddress(114, "Red"), candidateAddress(114, "Blue")].map((c) => c.toBase58());
+    expect(leaders).toContain(tied.winner.toBase58());
+    expect(leaders).toContain(tied.runnerUp.toBase58());
+
+    await expectError(
+      votingProgram.methods.victoryMargin(new anchor.BN(112))
+        .remainingAccounts(candidateAccounts(112, ["Red", "Blue"]))
+        .rpc(),
+      "do not match the poll's candidate count",
+    );
+  });
+
+  it("only opens a dependent poll once its prerequisite passed", async () => {
+    const now = await chainNow();
+    await createPoll(115, now + 10, now + 100, ["Yes", "No"]);
+    await createPoll(117, now + 10, now + 100, ["Yes", "No"]);
+    await createPoll(116, now + 50, now + 400, ["Plan"]);
+    await createPoll(118, now + 50, now + 400, ["Plan"]);
+    await expectError(updateSettings(116, { dependsOn: new anchor.BN(116) }), "can't depend on itself");
+    await updateSettings(116, { dependsOn: new anchor.BN(115), requiredWinner: candidateAddress(115, "Yes") });
+    await updateSettings(118, { dependsOn: new anchor.BN(117), requiredWinner: candidateAddress(117, "Yes") });
+
+    await warpTo(now + 20);
+    await castVotes(115, "Yes", 2);
+    await castVotes(117, "No", 2);
+    await warpTo(now + 200);
+    for (const pollId of [115, 117]) {
+      await votingProgram.methods.finalizePoll(new anchor.BN(pollId))
+        .remainingAccounts(candidateAccounts(pollId, ["Yes", "No"]))
+        .rpc();
+    }
+
+    const voteWith = (pollId: number, prerequisite: number | null) =>
+      vote(pollId, "Plan").accountsPartial({
+        prerequisitePoll: prerequisite === null ? null : pollAddress(prerequisite),
+      });
+    await expectError(voteWith(116, null).rpc(), "The poll this one depends on has not passed");
+    await expectError(voteWith(116, 117).rpc(), "The poll this one depends on has not passed");
+    await voteWith(116, 115).rpc();
+    expect((await votingProgram.account.poll.fetch(pollAddress(116))).totalVotes.toNumber()).toBe(1);
+
+    // poll 117 elected "No", so poll 118 stays closed
+    await expectError(voteWith(118, 117).rpc(), "The poll this one depends on has not passed");
+  });
+
+  it("starts each round with fresh tallies and ballots while keeping the cumulative count", async () => {
+    const now = await chainNow();
+    const names = ["Alpha", "Beta"];
+    await createPoll(119, now + 10, now + 500, names);
+    await updateSettings(119, { votesPerVoter: new anchor.BN(1) });
+    await warpTo(now + 20);
+    const voters = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    for (const voter of voters) await fund(voter.publicKey);
+
+    await voteAs(119, "Alpha", voters[0]).rpc();
+    await voteAs(119, "Alpha", voters[1]).rpc();
+    await voteAs(119, "Beta", voters[2]).rpc();
+    await warpTo(now + 21);
+    await expectError(voteAs(119, "Beta", voters[0]).rpc(), "Voter has no votes left in this poll");
+
+    const advance = () =>
+      votingProgram.methods.advanceRound(new anchor.BN(119))
+        .remainingAccounts(candidateAccounts(119, names).map((account) => ({ ...account, isWritable: true })))
+        .rpc();
+    await advance();
+    let poll = await votingProgram.account.poll.fetch(pollAddress(119));
+    expect(poll.round.toNumber()).toBe(1);
+    expect(poll.totalVotes.toNumber()).toBe(0);
+    expect(poll.uniqueVoters.toNumber()).toBe(0);
+    expect(poll.totalVotesAllRounds.toNumber()).toBe(3);
+    let alpha = await votingProgram.account.candidate.fetch(candidateAddress(119, "Alpha"));
+    expect(alpha.candidateVotes.toNumber()).toBe(0);
+    expect(alpha.totalVotesAllRounds.toNumber()).toBe(2);
+
+    // the voter who had used up their vote gets a new one
+    await warpTo(now + 22);
+    await voteAs(119, "Beta", voters[0]).rpc();
+    await voteAs(119, "Alpha", voters[1]).rpc();
+    const record = await votingProgram.account.voterRecord.fetch(voterRecordAddress(119, voters[0].publicKey));
+    expect(record.round.toNumber()).toBe(1);
+    expect(record.votesUsed.toNumber()).toBe(1);
+
+    await warpTo(now + 23);
+    await advance();
+    poll = await votingProgram.account.poll.fetch(pollAddress(119));
+    expect(poll.round.toNumber()).toBe(2);
+    expect(poll.totalVotesAllRounds.toNumber()).toBe(5);
+    alpha = await votingProgram.account.candidate.fetch(candidateAddress(119, "Alpha"));
+    const beta = await votingProgram.account.candidate.fetch(candidateAddress(119, "Beta"));
+    expect(alpha.totalVotesAllRounds.toNumber()).toBe(3);
+    expect(beta.totalVotesAllRounds.toNumber()).toBe(2);
+    expect(beta.candidateVotes.toNumber()).toBe(0);
+  });
+
+  it("caps how many polls one voter can join across the program", async () => {
+    const now = await chainNow();
+    for (const pollId of [120, 121, 122]) {
+      await createPoll(pollId, now + 10, now + 100, ["Only"]);
+    }
+    await warpTo(now + 20);
+    const voter = Keypair.generate();
+    await fund(voter.publicKey);
+    await votingProgram.methods.setMaxPollsPerVoter(new anchor.BN(2)).rpc();
+
+    await voteAs(120, "Only", voter).rpc();
+    await voteAs(121, "Only", voter).rpc();
+    await expectError(voteAs(122, "Only", voter).rpc(), "limit of polls per voter");
+    // more votes in a poll already joined don't count against the cap
+    await warpTo(now + 21);
+    await voteAs(120, "Only", voter).rpc();
+
+    const [globalState] = PublicKey.findProgramAddressSync(
+      [Buffer.from("voter_global"), voter.publicKey.toBuffer()],
+      votingProgram.programId,
+    );
+    expect((await votingProgram.account.voterGlobalState.fetch(globalState)).pollsVoted.toNumber()).toBe(2);
+
+    await votingProgram.methods.setMaxPollsPerVoter(new anchor.BN(0)).rpc();
+    await voteAs(122, "Only", voter).rpc();
+    expect((await votingProgram.account.voterGlobalState.fetch(globalState)).pollsVoted.toNumber()).toBe(3);
+  });
+
+  it("hands the poll status and winner to a calling program as return data", async () => {
+    const hookProgram = new anchor.Program<VoteHook>(HOOK_IDL, provider);
+    const [hookState] = PublicKey.findProgramAddressSync(
+      [Buffer.from("hook_state")],
+      hookProgram.programId,
+    );
+    if (!(await context.banksClient.getAccount(hookState))) {
+      await hookProgram.methods.initialize().rpc();
+    }
+    const now = await chainNow();
+    await createPoll(123, now + 10, now + 100, ["Gold", "Silver"]);
+    await warpTo(now + 20);
+    await castVotes(123, "Gold", 2);
+
+    const active = await votingProgram.methods.getPollStatus(new anchor.BN(123)).view();
+    expect(active.status).toBe(1);
+    expect(active.winner).toBeNull();
+
+    await warpTo(now + 200);
+    await votingProgram.methods.finalizePoll(new anchor.BN(123))
+      .remainingAccounts(candidateAccounts(123, ["Gold", "Silver"]))
+      .rpc();
+    await hookProgram.methods.readPollStatus(new anchor.BN(123))
+      .accounts({ poll: pollAddress(123), votingProgram: votingProgram.programId })
+      .rpc();
+
+    const state = await hookProgram.account.hookState.fetch(hookState);
+    expect(state.lastPollId.toNumber()).toBe(123);
+    expect(state.lastStatus).toBe(2);
+    expect(state.lastWinner!.equals(candidateAddress(123, "Gold"))).toBe(true);
+  });
+
+  it("weights token-gated votes by the tier in each token's Token-2022 metadata", async () => {
+    const token2022 = new PublicKey("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+    const borshString = (value: string) => {
+      const length = Buffer.alloc(4);
+      length.writeUInt32LE(Buffer.byteLength(value));
+      return Buffer.concat([length, Buffer.from(value)]);
+    };
+    const extension = (type: number, value: Buffer) => {
+      const header = Buffer.alloc(4);
+      header.writeUInt16LE(type, 0);
+      header.writeUInt16LE(value.length, 2);
+      return Buffer.concat([header, value]);
+    };
+    // a collection member mint whose metadata carries its tier
+    const memberMint = (group: PublicKey, tier: string) => {
+      const mint = Keypair.generate().publicKey;
+      const base = Buffer.alloc(166);
+      base[45] = 1;
+      base[165] = 1;
+      const pairs = Buffer.alloc(4);
+      pairs.writeUInt32LE(1);
+      const metadata = Buffer.concat([
+        Buffer.alloc(32), mint.toBuffer(),
+        borshString("Pass"), borshString("PASS"), borshString(""),
+        pairs, borshString("tier"), borshString(tier),
+      ]);
+      const member = Buffer.concat([mint.toBuffer(), group.toBuffer(), Buffer.alloc(8)]);
+      const data = Buffer.concat([base, extension(19, metadata), extension(23, member)]);
+      context.setAccount(mint, { lamports: 1_000_000_000, data, owner: token2022, executable: false });
+      return mint;
+    };
+    const tokenAccount = (mint: PublicKey, owner: PublicKey) => {
+      const address = Keypair.generate().publicKey;
+      const data = Buffer.alloc(165);
+      mint.toBuffer().copy(data, 0);
+      owner.toBuffer().copy(data, 32);
+      data.writeBigUInt64LE(BigInt(1), 64);
+      data[108] = 1;
+      context.setAccount(address, { lamports: 1_000_000_000, data, owner: token2022, executable: false });
+      return address;
+    };
+
+    const collection = Keypair.generate().publicKey;
+    const now = await chainNow();
+    await createPoll(124, now + 10, now + 100, ["Cloud", "Rain"]);
+    await updateSettings(124, {
+      gateMint: collection,
+      tierWeights: [1, 3, 5, 0].map((weight) => new anchor.BN(weight)),
+    });
+    await warpTo(now + 20);
+
+    const [bronze, gold, outsider] = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    for (const voter of [bronze, gold, outsider]) await fund(voter.publicKey);
+    const voteByTier = (name: string, voter: Keypair, mint: PublicKey, account: PublicKey) =>
+      votingProgram.methods.voteByTokenTier(name, new anchor.BN(124))
+        .accountsPartial({
+          candidate: candidateAddress(124, name),
+          signer: voter.publicKey,
+          tokenAccount: account,
+          tokenMint: mint,
+          tokenBallot: PublicKey.findProgramAddressSync(
+            [Buffer.from("token_ballot"), new anchor.BN(124).toArrayLike(Buffer, "le", 8), mint.toBuffer()],
+            votingProgram.programId,
+          )[0],
+        })
+        .signers([voter])
+        .rpc();
+
+    const bronzeMint = memberMint(collection, "0");
+    const goldMint = memberMint(collection, "2");
+    await voteByTier("Cloud", bronze, bronzeMint, tokenAccount(bronzeMint, bronze.publicKey));
+    await voteByTier("Rain", gold, goldMint, tokenAccount(goldMint, gold.publicKey));
+    expect((await votingProgram.account.candidate.fetch(candidateAddress(124, "Cloud"))).candidateVotes.toNumber()).toBe(1);
+    expect((await votingProgram.account.candidate.fetch(candidateAddress(124, "Rain"))).candidateVotes.toNumber()).toBe(5);
+
+    // a token from another collection, and someone else's token, are rejected
+    const strangerMint = memberMint(Keypair.generate().publicKey, "2");
+    await expectError(
+      voteByTier("Rain", outsider, strangerMint, tokenAccount(strangerMint, outsider.publicKey)),
+      "doesn't hold the poll's gate mint",
+    );
+    const spareMint = memberMint(collection, "2");
+    await expectError(
+      voteByTier("Rain", outsider, spareMint, tokenAccount(spareMint, gold.publicKey)),
+      "doesn't hold the poll's gate mint",
+    );
+    // handing a token that already voted to a fresh wallet doesn't earn another ballot
+    const recipient = Keypair.generate();
+    await fund(recipient.publicKey);
+    await expectError(
+      voteByTier("Rain", recipient, goldMint, tokenAccount(goldMint, recipient.publicKey)),
+      "already in use",
+    );
+    const unweightedMint = memberMint(collection, "3");
+    await expectError(
+      voteByTier("Rain", outsider, unweightedMint, tokenAccount(unweightedMint, outsider.publicKey)),
+      "Must cast at least one vote",
+    );
+    await expectError(voteAs(124, "Cloud", outsider).rpc(), "Poll does not accept this kind of vote");
+  });
+
+
+  it("reports the first voter gate a signer fails", async () => {
+    const errorCode = (name: string) => IDL.errors.find((e: any) => e.name === name).code;
+    const check = (pollId: number, voter: Keypair) =>
+      emittedEvent(
+        votingProgram.methods.checkEligibility(new anchor.BN(pollId))
+          .accountsPartial({ signer: voter.publicKey }),
+        "Eligibility",
+        [voter],
+      );
+
+    const now = await chainNow();
+    await createPoll(125, now + 10, now + 100, ["Gate"]);
+    await updateSettings(125, {
+      requireRegistration: true,
+      minAccountAgeSlots: new anchor.BN(100),
+      requireIdentity: true,
+    });
+    const voter = Keypair.generate();
+    await fund(voter.publicKey);
+
+    let outcome = await check(125, voter);
+    expect(outcome.eligible).toBe(false);
+    expect(outcome.reasonCode).toBe(errorCode("VoterNotRegistered"));
+
+    await votingProgram.methods.registerVoter(new anchor.BN(125), voter.publicKey).rpc();
+    await warpTo(now + 1);
+    outcome = await check(125, voter);
+    expect(outcome.reasonCode).toBe(errorCode("AccountTooNew"));
+
+    await warpTo(now + 2, 100);
+    outcome = await check(125, voter);
+    expect(outcome.reasonCode).toBe(errorCode("IdentityNotRegistered"));
+
+    const identityHash = Array.from(Keypair.generate().publicKey.toBytes());
+    await votingProgram.methods.registerIdentity(identityHash, voter.publicKey).rpc();
+    await warpTo(now + 3);
+    outcome = await check(125, voter);
+    expect(outcome.eligible).toBe(true);
+    expect(outcome.reasonCode).toBe(0);
+
+    // a gated poll without the voter's gate token
+    await createPoll(126, now + 10, now + 100, ["Gate"]);
+    await updateSettings(126, { gateMint: Keypair.generate().publicKey });
+    outcome = await check(126, voter);
+    expect(outcome.eligible).toBe(false);
+    expect(outcome.reasonCode).toBe(errorCode("InvalidGateToken"));
+  });
+
+  it("seals the ballot before voting and verifies it against the seal", async () => {
+    const now = await chainNow();
+    await createPoll(127, now + 100, now + 1000, ["Alder", "Birch"]);
+    const candidates = candidateAccounts(127, ["Alder", "Birch"]);
+    const verify = () =>
+      votingProgram.methods.verifyCandidateSeal(new anchor.BN(127))
+        .remainingAccounts(candidates)
+        .rpc();
+
+    await expectError(verify(), "Candidate set has not been sealed");
+    await votingProgram.methods.sealCandidates(new anchor.BN(127))
+      .remainingAccounts(candidates)
+      .rpc();
+    const poll = await votingProgram.account.poll.fetch(pollAddress(127));
+    expect(poll.candidatesLocked).toBe(true);
+    expect(poll.sealedCandidateHash.some((byte: number) => byte !== 0)).toBe(true);
+
+    await verify();
+    await expectError(initializeCandidate(127, "Cedar").rpc(), "CandidatesLocked");
+    await expectError(
+      votingProgram.methods.sealCandidates(new anchor.BN(127)).remainingAccounts(candidates).rpc(),
+      "Candidate set is already sealed",
+    );
+
+    // a display name rewritten after sealing no longer matches
+    await patchAccount(candidateAddress(127, "Birch"), "candidate", (candidate) => {
+      candidate.displayName = "Birds";
+    });
+    await warpTo(now + 1);
+    await expectError(verify(), "Candidates don't match the sealed ballot");
+
+    await createPoll(128, now + 100, now + 1000, []);
+    await expectError(
+      votingProgram.methods.sealCandidates(new anchor.BN(128)).rpc(),
+      "NoCandidates",
+    );
+  });
+
+  it("logs the results as CSV lines", async () => {
+    const now = await chainNow();
+    await createPoll(129, now + 10, now + 100, ["Oak", "Pine", "Yew"]);
+    await warpTo(now + 20);
+    await castVotes(129, "Oak", 2);
+    await castVotes(129, "Yew", 1);
+
+    const logs = await transactionLogs(
+      votingProgram.methods.exportCsv(new anchor.BN(129))
+        .remainingAccounts(candidateAccounts(129, ["Yew", "Oak", "Pine"])),
+    );
+    // rows in candidate index order, i.e. the order the candidates were added
+    const rows = [
+      { address: candidateAddress(129, "Oak"), votes: 2 },
+      { address: candidateAddress(129, "Pine"), votes: 0 },
+      { address: candidateAddress(129, "Yew"), votes: 1 },
+    ];
+    const expected = [
+      "candidate,index,votes",
+      ...rows.map(({ address, votes }, index) => `${address.toBase58()},${index},${votes}`),
+    ];
+
+    const csv = logs
+      .filter((log: string) => log.startsWith("Program log: "))
+      .map((log: string) => log.slice("Program log: ".length));
+    expect(csv.slice(csv.indexOf("candidate,index,votes"))).toEqual(expected);
+  });
+
+  it("sums candidates under results_min_votes into an other total", async () => {
+    const names = ["Major", "Minor", "Fringe"];
+    const now = await chainNow();
+    await createPoll(130, now + 10, now + 100, names);
+    await updateSettings(130, { resultsMinVotes: new anchor.BN(2) });
+    await warpTo(now + 20);
+    await castVotes(130, "Major", 3);
+    await castVotes(130, "Minor", 1);
+
+    const breakdown = await emittedEvent(
+      votingProgram.methods.resultsBreakdown(new anchor.BN(130))
+        .remainingAccounts(candidateAccounts(130, names)),
+      "ResultsBreakdown",
+    );
+    expect(breakdown.candidates.map((key: PublicKey) => key.toBase58())).toEqual([
+      candidateAddress(130, "Major").toBase58(),
+    ]);
+    expect(breakdown.shareBps.map((bps: anchor.BN) => bps.toNumber())).toEqual([7500]);
+    expect(breakdown.otherVotes.toNumber()).toBe(1);
+    expect(breakdown.totalVotes.toNumber()).toBe(4);
+
+    const ranking = await emittedEvent(
+      votingProgram.methods.rankCandidates(new anchor.BN(130))
+        .remainingAccounts(candidateAccounts(130, names)),
+      "CandidateRanking",
+    );
+    expect(ranking.ranking.map((c: any) => c.name)).toEqual(["Major"]);
+    expect(ranking.otherVotes.toNumber()).toBe(1);
+  });
+
+  it("creates a poll and its candidates in one transaction", async () => {
+    const now = await chainNow();
+    const create = (pollId: number, names: string[]) =>
+      votingProgram.methods.initializePollWithCandidates(
+        new anchor.BN(pollId),
+        `Poll ${pollId}`,
+        new anchor.BN(now + 10),
+        new anchor.BN(now + 100),
+        names,
+      )
+        .remainingAccounts(
+          candidateAccounts(pollId, names).map((meta) => ({ ...meta, isWritable: true })),
+        )
+        .rpc();
+
+    const names = ["Ash", "Elm", "Fir"];
+    await create(131, names);
+    const poll = await votingProgram.account.poll.fetch(pollAddress(131));
+    expect(poll.candidateAmount.toNumber()).toBe(3);
+    for (const name of names) {
+      const candidate = await votingProgram.account.candidate.fetch(candidateAddress(131, name));
+      expect(candidate.candidateName).toBe(name);
+      expect(candidate.displayName).toBe(name);
+      expect(candidate.poll.equals(pollAddress(131))).toBe(true);
+    }
+
+    // sending lamports to a predictable candidate address doesn't block it
+    context.setAccount(candidateAddress(144, "Oak"), {
+      lamports: 1,
+      data: Buffer.alloc(0),
+      owner: anchor.web3.SystemProgram.programId,
+      executable: false,
+    });
+    await create(144, ["Oak"]);
+    const oak = await votingProgram.account.candidate.fetch(candidateAddress(144, "Oak"));
+    expect(oak.candidateName).toBe("Oak");
+    expect(oak.poll.equals(pollAddress(144))).toBe(true);
+    await warpTo(now + 20);
+    await vote(131, "Elm").rpc();
+
+    // a reserved name fails the whole transaction, poll included
+    await expectError(create(132, ["Oak", "#0"]), "ReservedCandidateName");
+    expect(await context.banksClient.getAccount(pollAddress(132))).toBeNull();
+    expect(await context.banksClient.getAccount(candidateAddress(132, "Oak"))).toBeNull();
+  });
+
+  it("ranks an endorsed candidate by its multiplied votes", async () => {
+    const names = ["Lark", "Wren"];
+    const endorser = Keypair.generate();
+    await fund(endorser.publicKey);
+    const now = await chainNow();
+    await createPoll(133, now + 10, now + 100, names);
+    await updateSettings(133, { endorser: endorser.publicKey });
+    const endorse = (name: string, multiplierBps: number, signer: Keypair) =>
+      votingProgram.methods.endorseCandidate(name, new anchor.BN(133), new anchor.BN(multiplierBps))
+        .accountsPartial({ endorser: signer.publicKey, candidate: candidateAddress(133, name) })
+        .signers([signer])
+        .rpc();
+
+    await expectError(endorse("Wren", 15_000, Keypair.generate()), "Signer is not the poll's endorser");
+    await expectError(endorse("Wren", 25_000, endorser), "Endorsement multiplier must be between");
+    await endorse("Wren", 15_000, endorser);
+
+    await warpTo(now + 20);
+    await castVotes(133, "Lark", 3);
+    await castVotes(133, "Wren", 1);
+    const voters = [Keypair.generate(), Keypair.generate()];
+    for (const voter of voters) {
+      await fund(voter.publicKey);
+      await voteAs(133, "Wren", voter).rpc();
+    }
+
+    await warpTo(now + 200);
+    await votingProgram.methods.finalizePoll(new anchor.BN(133))
+      .remainingAccounts(candidateAccounts(133, names))
+      .rpc();
+    const poll = await votingProgram.account.poll.fetch(pollAddress(133));
+    expect(poll.winner!.equals(candidateAddress(133, "Wren"))).toBe(true);
+    // 3 votes as cast, ranked as 4 against Lark's 3
+    const wren = await votingProgram.account.candidate.fetch(candidateAddress(133, "Wren"));
+    expect(wren.candidateVotes.toNumber()).toBe(3);
+    expect(wren.endorsementMultiplierBps.toNumber()).toBe(15_000);
+  });
+
+  it("only counts votes declared from an allowed jurisdiction", async () => {
+    const now = await chainNow();
+    await createPoll(134, now + 10, now + 100, ["Coast", "Inland"]);
+    await updateSettings(134, { allowedJurisdictions: [11, 12] });
+    await warpTo(now + 20);
+    const voteFrom = (name: string, voter: Keypair, jurisdiction: number | null) =>
+      votingProgram.methods.vote(name, new anchor.BN(134), null, jurisdiction)
+        .accountsPartial({ candidate: candidateAddress(134, name), signer: voter.publicKey })
+        .signers([voter])
+        .rpc();
+
+    const [local, visitor] = [Keypair.generate(), Keypair.generate()];
+    await fund(local.publicKey);
+    await fund(visitor.publicKey);
+    await voteFrom("Coast", local, 12);
+    const record = await votingProgram.account.voterRecord.fetch(voterRecordAddress(134, local.publicKey));
+    expect(record.jurisdiction).toBe(12);
+
+    await expectError(voteFrom("Inland", visitor, 30), "declared jurisdiction isn't allowed");
+    await expectError(voteFrom("Inland", visitor, null), "declared jurisdiction isn't allowed");
+    const coast = await votingProgram.account.candidate.fetch(candidateAddress(134, "Coast"));
+    const inland = await votingProgram.account.candidate.fetch(candidateAddress(134, "Inland"));
+    expect(coast.candidateVotes.toNumber()).toBe(1);
+    expect(inland.candidateVotes.toNumber()).toBe(0);
+  });
+
+  it("reclaims the rent of registered voters who never voted", async () => {
+    const now = await chainNow();
+    await createPoll(135, now + 10, now + 100, ["Moss"]);
+    await updateSettings(135, { requireRegistration: true });
+    const [absent, voter] = [Keypair.generate(), Keypair.generate()];
+    await fund(voter.publicKey);
+    for (const wallet of [absent, voter]) {
+      await votingProgram.methods.registerVoter(new anchor.BN(135), wallet.publicKey).rpc();
+    }
+    await warpTo(now + 20);
+    await voteAs(135, "Moss", voter).rpc();
+    const reclaim = (wallet: PublicKey) =>
+      votingProgram.methods.reclaimUnvotedRecord(new anchor.BN(135), wallet).rpc();
+
+    await expectError(reclaim(absent.publicKey), "PollNotFinalized");
+    await warpTo(now + 200);
+    // an ended poll can still be reopened until it is finalized
+    await expectError(reclaim(absent.publicKey), "PollNotFinalized");
+    await votingProgram.methods.finalizePoll(new anchor.BN(135))
+      .remainingAccounts(candidateAccounts(135, ["Moss"]))
+      .rpc();
+    await expectError(reclaim(voter.publicKey), "Voter record has cast a ballot and can't be reclaimed");
+
+    const record = voterRecordAddress(135, absent.publicKey);
+    const rent = (await context.banksClient.getAccount(record))!.lamports;
+    const before = await context.banksClient.getBalance(provider.wallet.publicKey);
+    await reclaim(absent.publicKey);
+    expect(await context.banksClient.getAccount(record)).toBeNull();
+    const after = await context.banksClient.getBalance(provider.wallet.publicKey);
+    // the authority gets the rent back, less the transaction fee
+    expect(after - before).toBeGreaterThan(BigInt(rent) - BigInt(10_000));
+    const poll = await votingProgram.account.poll.fetch(pollAddress(135));
+    expect(poll.registeredVoterCount.toNumber()).toBe(2);
+  });
+  it("logs each counted vote to the poll's vote log in order", async () => {
+    const now = await chainNow();
+    await createPoll(136, now + 10, now + 100, ["Ash", "Birch", "Cedar"]);
+    await votingProgram.methods.enableVoteLog(new anchor.BN(136)).rpc();
+    const [voteLog] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vote_log"), new anchor.BN(136).toArrayLike(Buffer, "le", 8)],
+      votingProgram.programId,
+    );
+    await warpTo(now + 20);
+
+    const ballots = ["Cedar", "Ash", "Cedar", "Birch"];
+    for (const name of ballots) {
+      const voter = Keypair.generate();
+      await fund(voter.publicKey);
+      await votingProgram.methods.vote(name, new anchor.BN(136), null, null)
+        .accountsPartial({ candidate: candidateAddress(136, name), signer: voter.publicKey, voteLog })
+        .signers([voter])
+        .rpc();
+    }
+    const unlogged = Keypair.generate();
+    await fund(unlogged.publicKey);
+    await expectError(voteAs(136, "Ash", unlogged).rpc(), "Poll logs its votes, pass its vote log");
+
+    const log = await votingProgram.account.voteLog.fetch(voteLog);
+    expect(log.totalEntries.toNumber()).toBe(ballots.length);
+    // candidates are indexed in creation order: Ash 0, Birch 1, Cedar 2
+    expect(log.entries.map((entry) => entry.candidateIndex)).toEqual([2, 0, 2, 1]);
+    expect(log.entries.every((entry) => entry.weight === 1)).toBe(true);
+  });
+  it("only draws sortition winners from records that voted", async () => {
+    const now = await chainNow();
+    const names = ["Lime", "Pine"];
+    await createPoll(137, now + 10, now + 100, names);
+    const seed = createHash("sha256").update("unvoted sortition seed").digest();
+    await votingProgram.methods.commitSortitionSeed(
+      new anchor.BN(137),
+      Array.from(createHash("sha256").update(seed).digest()),
+    ).rpc();
+    const [first, second, bystander] = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    await votingProgram.methods.registerVoter(new anchor.BN(137), bystander.publicKey).rpc();
+    await warpTo(now + 20);
+    for (const [i, voter] of [first, second].entries()) {
+      await fund(voter.publicKey);
+      await voteAs(137, names[i], voter).rpc();
+    }
+    await warpTo(now + 200);
+
+    const records = [first, bystander].map((voter) => ({
+      pubkey: voterRecordAddress(137, voter.publicKey),
+      isSigner: false,
+      isWritable: false,
+    }));
+    await expectError(
+      votingProgram.methods.sortitionSelect(new anchor.BN(137), Array.from(seed))
+        .remainingAccounts(records)
+        .rpc(),
+      "Voter record has not cast a ballot",
+    );
+  });
+  it("rejects sortition records whose ballot is from an earlier round", async () => {
+    const now = await chainNow();
+    const names = ["Gale", "Haze"];
+    await createPoll(138, now + 10, now + 100, names);
+    const seed = createHash("sha256").update("round sortition seed").digest();
+    await votingProgram.methods.commitSortitionSeed(
+      new anchor.BN(138),
+      Array.from(createHash("sha256").update(seed).digest()),
+    ).rpc();
+    await warpTo(now + 20);
+    const [current, stale] = [Keypair.generate(), Keypair.generate()];
+    for (const [i, voter] of [current, stale].entries()) {
+      await fund(voter.publicKey);
+      await voteAs(138, names[i], voter).rpc();
+    }
+    await votingProgram.methods.advanceRound(new anchor.BN(138))
+      .remainingAccounts(candidateAccounts(138, names).map((account) => ({ ...account, isWritable: true })))
+      .rpc();
+    await warpTo(now + 21);
+    await voteAs(138, "Gale", current).rpc();
+    await warpTo(now + 200);
+
+    const select = (voter: Keypair) =>
+      votingProgram.methods.sortitionSelect(new anchor.BN(138), Array.from(seed))
+        .remainingAccounts([{ pubkey: voterRecordAddress(138, voter.publicKey), isSigner: false, isWritable: false }])
+        .rpc();
+    await expectError(select(stale), "Voter record's ballot is from an earlier round");
+    await select(current);
+    const poll = await votingProgram.account.poll.fetch(pollAddress(138));
+    expect(poll.winner!.equals(candidateAddress(138, "Gale"))).toBe(true);
+  });
+  it("applies the account age gate to abstentions and blank ballots", async () => {
+    const now = await chainNow();
+    await createPoll(139, now + 10, now + 100, ["Sage"]);
+    await updateSettings(139, { minAccountAgeSlots: new anchor.BN(100) });
+    const [abstainer, blank] = [Keypair.generate(), Keypair.generate()];
+    for (const voter of [abstainer, blank]) {
+      await fund(voter.publicKey);
+      await votingProgram.methods.registerVoter(new anchor.BN(139), voter.publicKey).rpc();
+    }
+    const abstain = () =>
+      votingProgram.methods.voteAbstain(new anchor.BN(139))
+        .accountsPartial({ signer: abstainer.publicKey })
+        .signers([abstainer])
+        .rpc();
+    const castBlank = () =>
+      votingProgram.methods.voteBlank(new anchor.BN(139))
+        .accountsPartial({ signer: blank.publicKey })
+        .signers([blank])
+        .rpc();
+
+    await warpTo(now + 20);
+    await expectError(abstain(), "Voter was first seen too recently");
+    await expectError(castBlank(), "Voter was first seen too recently");
+    const stranger = Keypair.generate();
+    await fund(stranger.publicKey);
+    await expectError(
+      votingProgram.methods.voteBlank(new anchor.BN(139))
+        .accountsPartial({ signer: stranger.publicKey })
+        .signers([stranger])
+        .rpc(),
+      "Voter was first seen too recently",
+    );
+
+    await warpTo(now + 30, 100);
+    await abstain();
+    await castBlank();
+    const poll = await votingProgram.account.poll.fetch(pollAddress(139));
+    expect(poll.abstentions.toNumber()).toBe(1);
+    expect(poll.blankVotes.toNumber()).toBe(1);
+    expect(poll.uniqueVoters.toNumber()).toBe(2);
+  });
+  it("keeps abstentions and blank ballots out of a dependent poll whose prerequisite failed", async () => {
+    const now = await chainNow();
+    await createPoll(140, now + 10, now + 100, ["Yes", "No"]);
+    await createPoll(141, now + 50, now + 400, ["Plan"]);
+    await updateSettings(141, { dependsOn: new anchor.BN(140), requiredWinner: candidateAddress(140, "Yes") });
+    await warpTo(now + 20);
+    await castVotes(140, "No", 1);
+    await warpTo(now + 200);
+    await votingProgram.methods.finalizePoll(new anchor.BN(140))
+      .remainingAccounts(candidateAccounts(140, ["Yes", "No"]))
+      .rpc();
+
+    const voter = Keypair.generate();
+    await fund(voter.publicKey);
+    for (const method of ["voteAbstain", "voteBlank"] as const) {
+      await expectError(
+        votingProgram.methods[method](new anchor.BN(141))
+          .accountsPartial({ signer: voter.publicKey, prerequisitePoll: pollAddress(140) })
+          .signers([voter])
+          .rpc(),
+        "The poll this one depends on has not passed",
+      );
+    }
+    const poll = await votingProgram.account.poll.fetch(pollAddress(141));
+    expect(poll.abstentions.toNumber()).toBe(0);
+    expect(poll.blankVotes.toNumber()).toBe(0);
+  });
+  it("previews the account age and vote log checks vote runs", async () => {
+    const errorCode = (name: string) => IDL.errors.find((e: any) => e.name === name).code;
+    const now = await chainNow();
+    await createPoll(142, now + 10, now + 100, ["Wick"]);
+    await createPoll(143, now + 10, now + 100, ["Wick"]);
+    await updateSettings(142, { minAccountAgeSlots: new anchor.BN(100) });
+    await votingProgram.methods.enableVoteLog(new anchor.BN(143)).rpc();
+    const [voteLog] = PublicKey.findProgramAddressSync(
+      [Buffer.from("vote_log"), new anchor.BN(143).toArrayLike(Buffer, "le", 8)],
+      votingProgram.programId,
+    );
+    await warpTo(now + 20);
+    // a fresh wallet has never been seen, so it is too new for poll 142
+    const voter = Keypair.generate();
+    await fund(voter.publicKey);
+    const simulate = (pollId: number, accounts: object = {}) =>
+      emittedEvent(
+        votingProgram.methods.simulateVote("Wick", new anchor.BN(pollId))
+          .accountsPartial({ candidate: candidateAddress(pollId, "Wick"), signer: voter.publicKey, ...accounts }),
+        "VoteSimulation",
+        [voter],
+      );
+
+    let outcome = await simulate(142);
+    expect(outcome.wouldSucceed).toBe(false);
+    expect(outcome.reasonCode).toBe(errorCode("AccountTooNew"));
+    outcome = await simulate(143);
+    expect(outcome.wouldSucceed).toBe(false);
+    expect(outcome.reasonCode).toBe(errorCode("VoteLogMissing"));
+    outcome = await simulate(143, { voteLog });
+    expect(outcome.wouldSucceed).toBe(true);
+  });
+  it("auto-finalizes from a leaderboard that includes batched votes", async () => {
+    const now = await chainNow();
+    await createPoll(145, now + 10, now + 1000, ["Lake", "Hill"]);
+    await updateSettings(145, { requireRegistration: true, autoFinalizeOnFullTurnout: true });
+    const batchers = [Keypair.generate(), Keypair.generate()];
+    const voter = Keypair.generate();
+    for (const wallet of [...batchers, voter]) {
+      await fund(wallet.publicKey);
+      await votingProgram.methods.registerVoter(new anchor.BN(145), wallet.publicKey).rpc();
+    }
+    await warpTo(now + 20);
+
+    for (const batcher of batchers) {
+      await votingProgram.methods
+        .voteMany([{ pollId: new anchor.BN(145), candidateName: "Hill" }])
+        .accountsPartial({ signer: batcher.publicKey })
+        .remainingAccounts(batchAccounts(145, "Hill", batcher.publicKey))
+        .signers([batcher])
+        .rpc();
+    }
+    const [topKAddress] = PublicKey.findProgramAddressSync(
+      [Buffer.from("top_k"), new anchor.BN(145).toArrayLike(Buffer, "le", 8)],
+      votingProgram.programId,
+    );
+    const topK = await votingProgram.account.topK.fetch(topKAddress);
+    expect(topK.entries[0].candidate.equals(candidateAddress(145, "Hill"))).toBe(true);
+    expect(topK.entries[0].votes.toNumber()).toBe(2);
+
+    // the last registered voter completes turnout; the batched votes still win
+    const event = await emittedEvent(voteAs(145, "Lake", voter), "AutoFinalized", [voter]);
+    expect(event.winner.equals(candidateAddress(145, "Hill"))).toBe(true);
+    const poll = await votingProgram.account.poll.fetch(pollAddress(145));
+    expect(poll.isFinalized).toBe(true);
+    expect(poll.totalVotes.toNumber()).toBe(3);
+  });
+
+  it("keeps results, leaderboard and weights in step with adjusted votes", async () => {
+    const now = await chainNow();
+    await createPoll(146, now + 10, now + 100, ["Oak", "Elm"]);
+    await updateSettings(146, { disputeWindowSeconds: new anchor.BN(600) });
+    await warpTo(now + 20);
+    await castVotes(146, "Oak", 3);
+    await castVotes(146, "Elm", 1);
+    await warpTo(now + 200);
+    const accounts = candidateAccounts(146, ["Oak", "Elm"]);
+    await votingProgram.methods.finalizePoll(new anchor.BN(146)).remainingAccounts(accounts).rpc();
+    await votingProgram.methods.computeResultsCommitment(new anchor.BN(146)).remainingAccounts(accounts).rpc();
+    const committed = await votingProgram.account.poll.fetch(pollAddress(146));
+
+    await votingProgram.methods.adjustVotes(new anchor.BN(146), candidateAddress(146, "Oak"), new anchor.BN(0))
+      .remainingAccounts(accounts.map((meta) => ({ ...meta, isWritable: true })))
+      .rpc();
+
+    const [resultsAddress] = PublicKey.findProgramAddressSync(
+      [Buffer.from("results"), new anchor.BN(146).toArrayLike(Buffer, "le", 8)],
+      votingProgram.programId,
+    );
+    const results = await votingProgram.account.pollResults.fetch(resultsAddress);
+    expect(results.totalVotes.toNumber()).toBe(1);
+    expect(results.winner!.equals(candidateAddress(146, "Elm"))).toBe(true);
+    const oak = results.candidates.find((entry) => entry.candidate.equals(candidateAddress(146, "Oak")))!;
+    expect(oak.votes.toNumber()).toBe(0);
+
+    const [topKAddress] = PublicKey.findProgramAddressSync(
+      [Buffer.from("top_k"), new anchor.BN(146).toArrayLike(Buffer, "le", 8)],
+      votingProgram.programId,
+    );
+    const topK = await votingProgram.account.topK.fetch(topKAddress);
+    expect(topK.entries[0].candidate.equals(candidateAddress(146, "Elm"))).toBe(true);
+    expect(topK.entries[0].votes.toNumber()).toBe(1);
+
+    const poll = await votingProgram.account.poll.fetch(pollAddress(146));
+    expect(poll.weightedTotal.toNumber()).toBe(10000);
+    expect(Buffer.from(poll.resultsRoot).equals(Buffer.from(committed.resultsRoot))).toBe(false);
+  });
+
+  it("writes the results of a poll that finalized itself", async () => {
+    const now = await chainNow();
+    await createPoll(147, now + 10, now + 1000, ["Lake", "Hill"]);
+    await updateSettings(147, { requireRegistration: true, autoFinalizeOnFullTurnout: true });
+    const voter = Keypair.generate();
+    await fund(voter.publicKey);
+    await votingProgram.methods.registerVoter(new anchor.BN(147), voter.publicKey).rpc();
+    await warpTo(now + 20);
+    await voteAs(147, "Hill", voter).rpc();
+    const finalized = await votingProgram.account.poll.fetch(pollAddress(147));
+    expect(finalized.isFinalized).toBe(true);
+
+    const [resultsAddress] = PublicKey.findProgramAddressSync(
+      [Buffer.from("results"), new anchor.BN(147).toArrayLike(Buffer, "le", 8)],
+      votingProgram.programId,
+    );
+    expect(await context.banksClient.getAccount(resultsAddress)).toBeNull();
+
+    await votingProgram.methods.finalizePoll(new anchor.BN(147))
+      .remainingAccounts(candidateAccounts(147, ["Lake", "Hill"]))
+      .rpc();
+    const results = await votingProgram.account.pollResults.fetch(resultsAddress);
+    expect(results.pollId.toNumber()).toBe(147);
+    expect(results.totalVotes.toNumber()).toBe(1);
+    expect(results.winner!.equals(candidateAddress(147, "Hill"))).toBe(true);
+    expect(results.candidates).toHaveLength(2);
+    const poll = await votingProgram.account.poll.fetch(pollAddress(147));
+    expect(poll.finalizedAt.toNumber()).toBe(finalized.finalizedAt.toNumber());
+  });
+
+  it("takes votes for candidates added to an open poll before its first vote", async () => {
+    const now = await chainNow();
+    await createPoll(148, now - 10, now + 1000, ["First"]);
+    await warpTo(now + 30);
+    await initializeCandidate(148, "Second").rpc();
+    await vote(148, "Second").rpc();
+
+    await warpTo(now + 40);
+    await initializeCandidate(148, "Third").rpc();
+    await expectError(vote(148, "Third").rpc(), "Candidate was added after the poll started");
+    const second = await votingProgram.account.candidate.fetch(candidateAddress(148, "Second"));
+    expect(second.candidateVotes.toNumber()).toBe(1);
+  });
+
+  it("finalizes automatically when an abstention or blank ballot completes turnout", async () => {
+    const now = await chainNow();
+    for (const pollId of [149, 150]) {
+      await createPoll(pollId, now + 10, now + 1000, ["Lake", "Hill"]);
+      await updateSettings(pollId, { requireRegistration: true, autoFinalizeOnFullTurnout: true });
+    }
+    const voters = [Keypair.generate(), Keypair.generate()];
+    for (const voter of voters) {
+      await fund(voter.publicKey);
+      for (const pollId of [149, 150]) {
+        await votingProgram.methods.registerVoter(new anchor.BN(pollId), voter.publicKey).rpc();
+      }
+    }
+    await warpTo(now + 20);
+    await voteAs(149, "Lake", voters[0]).rpc();
+    await voteAs(150, "Hill", voters[0]).rpc();
+
+    const abstain = votingProgram.methods.voteAbstain(new anchor.BN(149))
+      .accountsPartial({ signer: voters[1].publicKey })
+      .signers([voters[1]]);
+    const abstained = await emittedEvent(abstain, "AutoFinalized", [voters[1]]);
+    expect(abstained.winner.equals(candidateAddress(149, "Lake"))).toBe(true);
+
+    const blank = votingProgram.methods.voteBlank(new anchor.BN(150))
+      .accountsPartial({ signer: voters[1].publicKey })
+      .signers([voters[1]]);
+    const blanked = await emittedEvent(blank, "AutoFinalized", [voters[1]]);
+    expect(blanked.winner.equals(candidateAddress(150, "Hill"))).toBe(true);
+
+    for (const pollId of [149, 150]) {
+      const poll = await votingProgram.account.poll.fetch(pollAddress(pollId));
+      expect(poll.isFinalized).toBe(true);
+      expect(poll.uniqueVoters.toNumber()).toBe(2);
+    }
+  });
+
+  it("mints each voter one soulbound receipt from the poll's collection", async () => {
+    const token2022 = new PublicKey("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+    const pollId = new anchor.BN(151).toArrayLike(Buffer, "le", 8);
+    const [receiptAuthority] = PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt_authority"), pollId],
+      votingProgram.programId,
+    );
+    const extension = (type: number, value: Buffer) => {
+      const header = Buffer.alloc(4);
+      header.writeUInt16LE(type, 0);
+      header.writeUInt16LE(value.length, 2);
+      return Buffer.concat([header, value]);
+    };
+    // an unminted 0-decimal mint of the receipt authority, then its extensions
+    const mintAccount = (mint: PublicKey, extensions: Buffer[]) => {
+      const base = Buffer.alloc(166);
+      base.writeUInt32LE(1, 0);
+      receiptAuthority.toBuffer().copy(base, 4);
+      base[45] = 1;
+      base[165] = 1;
+      const data = Buffer.concat([base, ...extensions]);
+      context.setAccount(mint, { lamports: 1_000_000_000, data, owner: token2022, executable: false });
+    };
+    // group pointer to itself, then the group: update authority, mint, size and max size
+    const collection = Keypair.generate().publicKey;
+    const group = Buffer.alloc(80);
+    receiptAuthority.toBuffer().copy(group, 0);
+    collection.toBuffer().copy(group, 32);
+    group.writeBigUInt64LE(BigInt(100), 72);
+    mintAccount(collection, [
+      extension(20, Buffer.concat([receiptAuthority.toBuffer(), collection.toBuffer()])),
+      extension(21, group),
+    ]);
+    // non-transferable unless asked otherwise, with a member pointer to itself
+    const receiptMint = (transferable = false) => {
+      const mint = Keypair.generate().publicKey;
+      mintAccount(mint, [
+        ...(transferable ? [] : [extension(9, Buffer.alloc(0))]),
+        extension(22, Buffer.concat([receiptAuthority.toBuffer(), mint.toBuffer()])),
+      ]);
+      return mint;
+    };
+    // empty, with the immutable owner a non-transferable mint's accounts need
+    const receiptAccount = (mint: PublicKey, owner: PublicKey) => {
+      const address = Keypair.generate().publicKey;
+      const base = Buffer.alloc(166);
+      mint.toBuffer().copy(base, 0);
+      owner.toBuffer().copy(base, 32);
+      base[108] = 1;
+      base[165] = 2;
+      const data = Buffer.concat([base, extension(7, Buffer.alloc(0)), extension(13, Buffer.alloc(0))]);
+      context.setAccount(address, { lamports: 1_000_000_000, data, owner: token2022, executable: false });
+      return address;
+    };
+    const receiptAddress = (voter: PublicKey) =>
+      PublicKey.findProgramAddressSync([Buffer.from("receipt"), pollId, voter.toBuffer()], votingProgram.programId)[0];
+    const voteWithReceipt = (name: string, voter: Keypair, mint: PublicKey, account: PublicKey, group = collection) =>
+      votingProgram.methods.voteWithReceipt(name, new anchor.BN(151))
+        .accountsPartial({
+          vote: { candidate: candidateAddress(151, name), signer: voter.publicKey },
+          receipt: receiptAddress(voter.publicKey),
+          receiptMint: mint,
+          receiptTokenAccount: account,
+          receiptCollection: group,
+        })
+        .signers([voter])
+        .rpc();
+
+    const now = await chainNow();
+    await createPoll(151, now + 10, now + 100, ["Yes", "No"]);
+    const [first, second] = [Keypair.generate(), Keypair.generate()];
+    for (const voter of [first, second]) await fund(voter.publicKey);
+    const firstMint = receiptMint();
+    const firstAccount = receiptAccount(firstMint, first.publicKey);
+    await warpTo(now + 20);
+    // receipts are opt-in per poll
+    await expectError(voteWithReceipt("Yes", first, firstMint, firstAccount), "Poll does not accept this kind of vote");
+
+    await updateSettings(151, { receiptCollection: collection });
+    await voteWithReceipt("Yes", first, firstMint, firstAccount);
+    const receipt = await votingProgram.account.receiptRecord.fetch(receiptAddress(first.publicKey));
+    expect(receipt.poll.equals(pollAddress(151))).toBe(true);
+    expect(receipt.voter.equals(first.publicKey)).toBe(true);
+    expect(receipt.mint.equals(firstMint)).toBe(true);
+    const held = Buffer.from((await context.banksClient.getAccount(firstAccount))!.data);
+    expect(held.readBigUInt64LE(64)).toBe(BigInt(1));
+    const minted = Buffer.from((await context.banksClient.getAccount(firstMint))!.data);
+    expect(minted.readBigUInt64LE(36)).toBe(BigInt(1));
+    // the mint authority is revoked, so no second token can be minted
+    expect(minted.readUInt32LE(0)).toBe(0);
+    expect((await votingProgram.account.candidate.fetch(candidateAddress(151, "Yes"))).candidateVotes.toNumber()).toBe(1);
+
+    // voting again doesn't earn a second receipt
+    const spareMint = receiptMint();
+    await expectError(
+      voteWithReceipt("No", first, spareMint, receiptAccount(spareMint, first.publicKey)),
+      "already in use",
+    );
+
+    // the collection must be the poll's, and the mint soulbound and the signer's to receive
+    await expectError(
+      voteWithReceipt("No", second, spareMint, receiptAccount(spareMint, second.publicKey), receiptMint()),
+      "doesn't match the poll's receipt collection",
+    );
+    const transferable = receiptMint(true);
+    await expectError(
+      voteWithReceipt("No", second, transferable, receiptAccount(transferable, second.publicKey)),
+      "Receipt mint must be an unminted, non-transferable mint",
+    );
+    await expectError(
+      voteWithReceipt("No", second, firstMint, receiptAccount(firstMint, second.publicKey)),
+      "Receipt mint must be an unminted, non-transferable mint",
+    );
+    await expectError(
+      voteWithReceipt("No", second, spareMint, receiptAccount(spareMint, first.publicKey)),
+      "doesn't hold the receipt mint for the signer",
+    );
+    await voteWithReceipt("No", second, spareMint, receiptAccount(spareMint, second.publicKey));
+    expect((await votingProgram.account.receiptRecord.fetch(receiptAddress(second.publicKey))).mint.equals(spareMint)).toBe(true);
+  });
 });
\ No newline at end of file